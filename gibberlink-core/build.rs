@@ -0,0 +1,87 @@
+fn main() {
+    // With `--features system-ggwave`, link an installed libggwave instead of building the sources.
+    #[cfg(feature = "system-ggwave")]
+    {
+        link_system_ggwave();
+        return;
+    }
+
+    #[allow(unreachable_code)]
+    build_bundled_ggwave();
+}
+
+#[cfg(feature = "system-ggwave")]
+fn link_system_ggwave() {
+    // GGWAVE_LIB_DIR wins over pkg-config, for installs without a ggwave.pc
+    println!("cargo:rerun-if-env-changed=GGWAVE_LIB_DIR");
+    if let Some(dir) = std::env::var_os("GGWAVE_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", std::path::Path::new(&dir).display());
+        println!("cargo:rustc-link-lib=ggwave");
+        return;
+    }
+    if let Err(e) = pkg_config::Config::new().probe("ggwave") {
+        panic!("system-ggwave: libggwave not found via pkg-config ({}); set GGWAVE_LIB_DIR to the directory containing it", e);
+    }
+}
+
+// `vendored`: the ggwave release this crate is tested against, fetched when `../ggwave` is empty.
+// GGWAVE_VENDOR_REV overrides it with another tag or commit.
+#[cfg(feature = "vendored")]
+const GGWAVE_REV: &str = "ggwave-v0.4.0";
+
+// Fetches only that revision into OUT_DIR; the source tree (and its submodules) is never touched
+#[cfg(feature = "vendored")]
+fn fetch_ggwave() -> std::path::PathBuf {
+    use std::process::Command;
+    println!("cargo:rerun-if-env-changed=GGWAVE_VENDOR_REV");
+    let rev = std::env::var("GGWAVE_VENDOR_REV").unwrap_or_else(|_| GGWAVE_REV.to_owned());
+    let out = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("ggwave");
+    let git = |args: &[&str]| -> bool {
+        Command::new("git").args(args).current_dir(&out).status().map(|s| s.success()).unwrap_or(false)
+    };
+    std::fs::create_dir_all(&out).expect("vendored: create OUT_DIR/ggwave");
+    // A commit or a tag alike; GitHub serves single commits to shallow fetches
+    let fetched = git(&["init", "--quiet"])
+        && git(&["fetch", "--quiet", "--depth", "1", "https://github.com/ggerganov/ggwave.git", &rev])
+        && git(&["checkout", "--quiet", "--force", "FETCH_HEAD"]);
+    if !fetched {
+        panic!("vendored: fetching ggwave {} failed", rev);
+    }
+    out
+}
+
+fn build_bundled_ggwave() {
+    // Build the ggwave static library from the vendored source cloned next to this crate.
+    // The repository was cloned to `../ggwave`.
+    let ggwave_dir = std::path::Path::new("..").join("ggwave");
+    #[cfg(feature = "vendored")]
+    let ggwave_dir = if ggwave_dir.join("src").join("ggwave.cpp").exists() { ggwave_dir } else { fetch_ggwave() };
+    let src = ggwave_dir.join("src").join("ggwave.cpp");
+    let include = ggwave_dir.join("include");
+
+    if !src.exists() {
+        panic!("Expected ggwave source at {} (run `git submodule update --init`, or build with --features vendored or system-ggwave)", src.display());
+    }
+
+    let mut build = cc::Build::new();
+    build.cpp(true)
+        .file(src)
+        .include(include)
+        .flag_if_supported("-std=c++11")
+        .define("GGWAVE_BUILD", None);
+
+    // Raspberry Pi 2/3/4 running 32-bit OS: the default armv7 target only assumes vfpv3-d16,
+    // so let the compiler use NEON for ggwave's FFT and tone loops. armv6 (Pi Zero/1) has no NEON.
+    let target = std::env::var("TARGET").unwrap_or_default();
+    if target.starts_with("armv7") && target.ends_with("eabihf") {
+        build.flag_if_supported("-mfpu=neon-vfpv4").flag_if_supported("-mfloat-abi=hard");
+    }
+
+    // On MSVC, enable multi-processor compilation if possible
+    #[cfg(target_env = "msvc")]
+    {
+        build.flag("/MP");
+    }
+
+    build.compile("ggwave");
+}
@@ -0,0 +1,216 @@
+//! Live audio through cpal (the `audio` feature), for programs with no audio I/O of their own:
+//! [`listen`] decodes the microphone on a thread of its own and [`transmit`] plays a message. On
+//! Android, cpal captures and plays through Oboe (AAudio, or OpenSL ES on older devices).
+
+use crate::message::{self, Receiver};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, sync_channel, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// How often the capture thread checks whether it has been stopped
+const POLL: Duration = Duration::from_millis(100);
+
+/// A running [`listen`]; dropping it stops capture.
+pub struct Listening {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<(), String>>>,
+    description: String,
+}
+
+impl Listening {
+    /// The device being captured, its rate and channel count, e.g. for a startup message.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Stops capture and waits for the thread to finish.
+    pub fn stop(mut self) -> Result<(), String> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.join()
+    }
+
+    /// Waits until capture fails (it otherwise runs until the process ends).
+    pub fn wait(mut self) -> Result<(), String> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<(), String> {
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err("capture thread panicked".into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Listening {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.join();
+    }
+}
+
+/// Captures the input device called `device` (or the default one) and calls `on_message` on the
+/// capture thread with every message heard, once all of its chunks are in, or with why one was
+/// dropped. Returns once capture has started.
+pub fn listen(device: Option<&str>, mut on_message: impl FnMut(Result<Vec<u8>, String>) + Send + 'static) -> Result<Listening, String> {
+    let device = device.map(str::to_owned);
+    let stop = Arc::new(AtomicBool::new(false));
+    let (started_tx, started) = sync_channel(1);
+    let stopped = stop.clone();
+    // The stream lives on the thread that opened it; cpal streams can't move between threads everywhere
+    let thread = std::thread::spawn(move || {
+        let input = match open_input(device.as_deref()) {
+            Ok(input) => {
+                let _ = started_tx.send(Ok(input.description.clone()));
+                input
+            }
+            Err(e) => {
+                let _ = started_tx.send(Err(e.clone()));
+                return Err(e);
+            }
+        };
+        let receiver = Receiver::new(input.sample_rate).map_err(|e| format!("ggwave init failed: {}", e))?;
+        while !stopped.load(Ordering::Relaxed) {
+            match input.chunks.recv_timeout(POLL) {
+                Ok(chunk) => receiver.push(&chunk).into_iter().for_each(&mut on_message),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Err("capture stream closed".into()),
+            }
+        }
+        Ok(())
+    });
+    match started.recv() {
+        Ok(Ok(description)) => Ok(Listening { stop, thread: Some(thread), description }),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err("capture thread exited".into()),
+    }
+}
+
+// A started capture stream and the mono buffers it delivers
+struct Input {
+    _stream: cpal::Stream,
+    sample_rate: u32,
+    chunks: std::sync::mpsc::Receiver<Vec<f32>>,
+    description: String,
+}
+
+fn open_input(device: Option<&str>) -> Result<Input, String> {
+    let host = cpal::default_host();
+    let device = match device {
+        Some(name) => pick_device(host.input_devices().map_err(|e| format!("input devices: {}", e))?, name)?,
+        None => host.default_input_device().ok_or("no input device available")?,
+    };
+    let supported = device.default_input_config().map_err(|e| format!("input config: {}", e))?;
+    let config = supported.config();
+    let (tx, rx) = channel();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_input::<f32>(&device, &config, tx),
+        cpal::SampleFormat::I16 => build_input::<i16>(&device, &config, tx),
+        cpal::SampleFormat::U16 => build_input::<u16>(&device, &config, tx),
+        cpal::SampleFormat::I32 => build_input::<i32>(&device, &config, tx),
+        other => return Err(format!("unsupported input sample format {}", other)),
+    }
+    .map_err(|e| format!("open input stream: {}", e))?;
+    stream.play().map_err(|e| format!("start capture: {}", e))?;
+    let name = device.name().unwrap_or_else(|_| "default input".into());
+    let description = format!("{} ({} Hz, {} ch)", name, config.sample_rate.0, config.channels);
+    Ok(Input { _stream: stream, sample_rate: config.sample_rate.0, chunks: rx, description })
+}
+
+/// Encodes `payload` (chunked if need be) with `protocol` at `volume` and plays it on the output
+/// device called `device` (or the default one), returning once it has been heard.
+pub fn transmit(payload: &[u8], protocol: i32, volume: i32, device: Option<&str>) -> Result<(), String> {
+    let host = cpal::default_host();
+    let device = match device {
+        Some(name) => pick_device(host.output_devices().map_err(|e| format!("output devices: {}", e))?, name)?,
+        None => host.default_output_device().ok_or("no output device available")?,
+    };
+    let supported = device.default_output_config().map_err(|e| format!("output config: {}", e))?;
+    let config = supported.config();
+    // Encoded at the device's own rate, so nothing needs resampling
+    let samples = message::encode(payload, protocol, volume, config.sample_rate.0)?;
+    let duration = Duration::from_secs_f64(samples.len() as f64 / config.sample_rate.0 as f64);
+    let (done_tx, done_rx) = channel();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_output::<f32>(&device, &config, samples, done_tx),
+        cpal::SampleFormat::I16 => build_output::<i16>(&device, &config, samples, done_tx),
+        cpal::SampleFormat::U16 => build_output::<u16>(&device, &config, samples, done_tx),
+        cpal::SampleFormat::I32 => build_output::<i32>(&device, &config, samples, done_tx),
+        other => return Err(format!("unsupported output sample format {}", other)),
+    }
+    .map_err(|e| format!("open output stream: {}", e))?;
+    stream.play().map_err(|e| format!("start playback: {}", e))?;
+    // The callback reports when it runs out of samples; then give the device buffer time to drain
+    done_rx.recv_timeout(duration + Duration::from_secs(5)).map_err(|_| "output stream stalled".to_string())?;
+    std::thread::sleep(Duration::from_millis(250));
+    Ok(())
+}
+
+// An exact name, else the one device whose name contains `wanted` ignoring case, as gibberlink-tx
+// picks them
+fn pick_device(devices: impl Iterator<Item = cpal::Device>, wanted: &str) -> Result<cpal::Device, String> {
+    let mut named: Vec<(String, cpal::Device)> = devices.filter_map(|d| Some((d.name().ok()?, d))).collect();
+    if let Some(i) = named.iter().position(|(name, _)| name == wanted) {
+        return Ok(named.swap_remove(i).1);
+    }
+    let lower = wanted.to_lowercase();
+    named.retain(|(name, _)| name.to_lowercase().contains(&lower));
+    match named.len() {
+        1 => Ok(named.remove(0).1),
+        0 => Err(format!("no audio device matches {:?}", wanted)),
+        _ => {
+            let names: Vec<&str> = named.iter().map(|(name, _)| name.as_str()).collect();
+            Err(format!("{:?} matches several devices: {}", wanted, names.join(", ")))
+        }
+    }
+}
+
+// Downmixes each callback buffer to mono f32 and hands it to the decoding thread
+fn build_input<T>(device: &cpal::Device, config: &cpal::StreamConfig, tx: Sender<Vec<f32>>) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let mono = data
+                .chunks(channels)
+                .map(|frame| frame.iter().map(|&s| s.to_sample::<f32>()).sum::<f32>() / channels as f32)
+                .collect();
+            let _ = tx.send(mono);
+        },
+        |e| eprintln!("Capture error: {}", e),
+        None,
+    )
+}
+
+// Writes the mono samples to every channel, then silence; `done` fires once the last sample is queued
+fn build_output<T>(device: &cpal::Device, config: &cpal::StreamConfig, samples: Vec<f32>, done: Sender<()>) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let mut samples = samples.into_iter();
+    let mut finished = false;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            for frame in data.chunks_mut(channels) {
+                frame.fill(T::from_sample(samples.next().unwrap_or(0.0)));
+            }
+            if samples.len() == 0 && !finished {
+                finished = true;
+                let _ = done.send(());
+            }
+        },
+        |e| eprintln!("Playback error: {}", e),
+        None,
+    )
+}
@@ -0,0 +1,40 @@
+//! Lines of a `chat` session, tagged with the sender's peer ID:
+//!
+//! ```text
+//! 0x1A | peer id length | peer id (UTF-8) | text (UTF-8)
+//! ```
+//!
+//! 0x1A is ASCII substitute; see [`crate::framing`] for how other payloads stay clear of it.
+
+const MAGIC: u8 = 0x1A;
+/// Longest peer ID, so the tag costs little airtime on every line
+pub const MAX_PEER_LEN: usize = 16;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChatMessage {
+    pub peer: String,
+    pub text: String,
+}
+
+/// Tags `text` with `peer`.
+pub fn pack(peer: &str, text: &str) -> Result<Vec<u8>, String> {
+    if peer.is_empty() || peer.len() > MAX_PEER_LEN {
+        return Err(format!("peer ID must be 1..={} bytes", MAX_PEER_LEN));
+    }
+    let mut out = Vec::with_capacity(2 + peer.len() + text.len());
+    out.extend_from_slice(&[MAGIC, peer.len() as u8]);
+    out.extend_from_slice(peer.as_bytes());
+    out.extend_from_slice(text.as_bytes());
+    Ok(out)
+}
+
+/// `None` unless `payload` is a well-formed chat line.
+pub fn unpack(payload: &[u8]) -> Option<ChatMessage> {
+    let [MAGIC, peer_len, rest @ ..] = payload else { return None };
+    let peer_len = *peer_len as usize;
+    if peer_len == 0 || peer_len > MAX_PEER_LEN || rest.len() < peer_len {
+        return None;
+    }
+    let (peer, text) = rest.split_at(peer_len);
+    Some(ChatMessage { peer: std::str::from_utf8(peer).ok()?.to_owned(), text: String::from_utf8(text.to_vec()).ok()? })
+}
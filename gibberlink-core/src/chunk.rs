@@ -0,0 +1,369 @@
+//! Framing for payloads longer than one ggwave transmission. Each chunk carries a small header so
+//! the receiver can reassemble them in any order and drop corrupted ones:
+//!
+//! ```text
+//! 0x1E | message id | index | count | CRC-32 of data (LE) | data
+//! ```
+//!
+//! 0x1E is the ASCII record separator. Payloads of the application's own that start with it, or
+//! with any other frame marker, are escaped before sending (see [`crate::framing`]).
+//!
+//! Reliable transfer adds control frames starting with 0x1D: after each round of chunks the sender
+//! polls (`0x1D '?' id count`) and the receiver answers with a bitmap of the chunks it holds
+//! (`0x1D '!' id count bitmap`, LSB first).
+//!
+//! With the `fec` feature, [`split_fec`] adds Reed-Solomon parity across the chunks, so any
+//! `data count` of the `count` frames rebuild the message:
+//!
+//! ```text
+//! 0x0E | message id | index | count | data count | CRC-32 of shard (LE) | shard
+//! ```
+//!
+//! The data shards hold the payload length (u16 LE), the payload and zero padding.
+
+use std::collections::HashMap;
+
+const MAGIC: u8 = 0x1E;
+const CONTROL_MAGIC: u8 = 0x1D;
+const FEC_MAGIC: u8 = 0x0E;
+const HEADER_LEN: usize = 8;
+const FEC_HEADER_LEN: usize = 9;
+/// Payload bytes per chunk, so a whole frame fits in one variable-length ggwave transmission
+pub const CHUNK_DATA_LEN: usize = crate::MAX_LENGTH_VARIABLE - HEADER_LEN;
+/// Longest message that can be chunked (255 frames)
+pub const MAX_MESSAGE_LEN: usize = CHUNK_DATA_LEN * u8::MAX as usize;
+
+/// Splits `payload` into framed chunks tagged with `id`; the receiver keys reassembly on it.
+pub fn split(payload: &[u8], id: u8) -> Result<Vec<Vec<u8>>, String> {
+    if payload.len() > MAX_MESSAGE_LEN {
+        return Err(format!("payload is {} bytes; chunking allows at most {}", payload.len(), MAX_MESSAGE_LEN));
+    }
+    let chunks: Vec<&[u8]> = payload.chunks(CHUNK_DATA_LEN).collect();
+    let count = chunks.len() as u8;
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(index, data)| {
+            let mut frame = Vec::with_capacity(HEADER_LEN + data.len());
+            frame.extend_from_slice(&[MAGIC, id, index as u8, count]);
+            frame.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+            frame.extend_from_slice(data);
+            frame
+        })
+        .collect())
+}
+
+/// Splits `payload` into data shards plus Reed-Solomon parity, `n - k` parity frames for every `k`
+/// data frames (rounded up), all tagged with `id`.
+#[cfg(feature = "fec")]
+pub fn split_fec(payload: &[u8], id: u8, n: usize, k: usize) -> Result<Vec<Vec<u8>>, String> {
+    use reed_solomon_erasure::galois_8::ReedSolomon;
+    if k == 0 || n <= k {
+        return Err(format!("rs({},{}) needs N > K > 0", n, k));
+    }
+    let len = u16::try_from(payload.len()).map_err(|_| format!("payload is {} bytes; FEC allows at most {}", payload.len(), u16::MAX))?;
+    let framed_len = 2 + payload.len();
+    let data_count = framed_len.div_ceil(crate::MAX_LENGTH_VARIABLE - FEC_HEADER_LEN);
+    let parity_count = (data_count * (n - k)).div_ceil(k);
+    let count = data_count + parity_count;
+    if count > u8::MAX as usize {
+        return Err(format!("payload would need {} frames with rs({},{}); at most {}", count, n, k, u8::MAX));
+    }
+    // Equal shards, as short as the payload allows
+    let shard_len = framed_len.div_ceil(data_count);
+    let mut data = Vec::with_capacity(data_count * shard_len);
+    data.extend_from_slice(&len.to_le_bytes());
+    data.extend_from_slice(payload);
+    data.resize(data_count * shard_len, 0);
+    let mut shards: Vec<Vec<u8>> = data.chunks(shard_len).map(<[u8]>::to_vec).collect();
+    shards.resize(count, vec![0; shard_len]);
+    let rs = ReedSolomon::new(data_count, parity_count).map_err(|e| format!("reed-solomon: {:?}", e))?;
+    rs.encode(&mut shards).map_err(|e| format!("reed-solomon: {:?}", e))?;
+    Ok(shards
+        .iter()
+        .enumerate()
+        .map(|(index, shard)| {
+            let mut frame = Vec::with_capacity(FEC_HEADER_LEN + shard.len());
+            frame.extend_from_slice(&[FEC_MAGIC, id, index as u8, count as u8, data_count as u8]);
+            frame.extend_from_slice(&crc32fast::hash(shard).to_le_bytes());
+            frame.extend_from_slice(shard);
+            frame
+        })
+        .collect())
+}
+
+/// What the reassembler made of one received payload.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Received {
+    /// A complete message: either unchunked, or the last missing chunk of one
+    Message(Vec<u8>),
+    /// A chunk was stored; `received` of `count` chunks of message `id` are in
+    Partial { id: u8, received: usize, count: usize },
+    /// A retransmitted chunk of a message that was already completed
+    Duplicate { id: u8 },
+}
+
+/// Control frames for reliable transfer.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Control {
+    /// Sender asks which chunks of message `id` have arrived
+    Poll { id: u8, count: u8 },
+    /// Receiver's answer: one flag per chunk index
+    Ack { id: u8, received: Vec<bool> },
+}
+
+impl Control {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Control::Poll { id, count } => vec![CONTROL_MAGIC, b'?', *id, *count],
+            Control::Ack { id, received } => {
+                let mut frame = vec![CONTROL_MAGIC, b'!', *id, received.len() as u8];
+                frame.extend(received.chunks(8).map(|bits| bits.iter().rev().fold(0u8, |acc, &b| acc << 1 | b as u8)));
+                frame
+            }
+        }
+    }
+
+    pub fn parse(bytes: &[u8]) -> Option<Control> {
+        match bytes {
+            [CONTROL_MAGIC, b'?', id, count] => Some(Control::Poll { id: *id, count: *count }),
+            [CONTROL_MAGIC, b'!', id, count, bitmap @ ..] if bitmap.len() == (*count as usize).div_ceil(8) => Some(Control::Ack {
+                id: *id,
+                received: (0..*count as usize).map(|i| bitmap[i / 8] >> (i % 8) & 1 == 1).collect(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The message id a chunk frame (plain or Reed-Solomon) belongs to, read without checking the
+/// frame; `None` for payloads [`Reassembler::push`] passes through.
+pub fn message_id(frame: &[u8]) -> Option<u8> {
+    match frame.first() {
+        Some(&FEC_MAGIC) if frame.len() > FEC_HEADER_LEN => Some(frame[1]),
+        Some(&MAGIC) if frame.len() >= HEADER_LEN => Some(frame[1]),
+        _ => None,
+    }
+}
+
+// Chunks of one message by index, `None` until received
+type Shards = Vec<Option<Vec<u8>>>;
+
+/// Collects chunks per message id until every index has arrived.
+#[derive(Default)]
+pub struct Reassembler {
+    pending: HashMap<u8, Shards>,
+    // id -> chunk count of finished messages, to recognise retransmissions and answer polls
+    completed: HashMap<u8, usize>,
+    // Reed-Solomon messages, by id: data count and the shards so far
+    fec_pending: HashMap<u8, (usize, Shards)>,
+    fec_completed: HashMap<u8, usize>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Payloads that aren't chunk frames come straight back as `Received::Message`.
+    pub fn push(&mut self, payload: Vec<u8>) -> Result<Received, String> {
+        if payload.len() > FEC_HEADER_LEN && payload[0] == FEC_MAGIC {
+            return self.push_fec(&payload);
+        }
+        if payload.len() < HEADER_LEN || payload[0] != MAGIC {
+            return Ok(Received::Message(payload));
+        }
+        let (id, index, count) = (payload[1], payload[2] as usize, payload[3] as usize);
+        let crc = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+        let data = &payload[HEADER_LEN..];
+        if crc32fast::hash(data) != crc {
+            return Err(format!("chunk {}/{} of message {} failed its CRC check", index + 1, count, id));
+        }
+        if index >= count {
+            return Err(format!("chunk index {} out of range for a {}-chunk message", index + 1, count));
+        }
+        if !self.pending.contains_key(&id) && self.completed.get(&id) == Some(&count) {
+            return Ok(Received::Duplicate { id });
+        }
+        let parts = self.pending.entry(id).or_default();
+        // A different count under the same id means the id was reused for a new message
+        if parts.len() != count {
+            *parts = vec![None; count];
+        }
+        parts[index] = Some(data.to_vec());
+        let received = parts.iter().filter(|p| p.is_some()).count();
+        if received < count {
+            return Ok(Received::Partial { id, received, count });
+        }
+        let parts = self.pending.remove(&id).unwrap_or_default();
+        self.completed.insert(id, count);
+        Ok(Received::Message(parts.into_iter().flatten().flatten().collect()))
+    }
+
+    /// Which chunks of message `id` have arrived, for answering a `Control::Poll`.
+    pub fn received(&self, id: u8, count: usize) -> Vec<bool> {
+        match self.pending.get(&id) {
+            Some(parts) if parts.len() == count => parts.iter().map(Option::is_some).collect(),
+            _ => vec![self.completed.get(&id) == Some(&count); count],
+        }
+    }
+
+    // Like push(), but complete as soon as any `data count` shards are in
+    fn push_fec(&mut self, payload: &[u8]) -> Result<Received, String> {
+        let (id, index, count, data_count) = (payload[1], payload[2] as usize, payload[3] as usize, payload[4] as usize);
+        let crc = u32::from_le_bytes([payload[5], payload[6], payload[7], payload[8]]);
+        let shard = &payload[FEC_HEADER_LEN..];
+        if crc32fast::hash(shard) != crc {
+            return Err(format!("FEC chunk {}/{} of message {} failed its CRC check", index + 1, count, id));
+        }
+        if index >= count || data_count == 0 || data_count >= count {
+            return Err(format!("FEC chunk {}/{} of message {} has an invalid header", index + 1, count, id));
+        }
+        if !self.fec_pending.contains_key(&id) && self.fec_completed.get(&id) == Some(&count) {
+            return Ok(Received::Duplicate { id });
+        }
+        let entry = self.fec_pending.entry(id).or_default();
+        if entry.0 != data_count || entry.1.len() != count {
+            *entry = (data_count, vec![None; count]);
+        }
+        entry.1[index] = Some(shard.to_vec());
+        let received = entry.1.iter().filter(|p| p.is_some()).count();
+        if received < data_count {
+            return Ok(Received::Partial { id, received, count: data_count });
+        }
+        let (_, shards) = self.fec_pending.remove(&id).unwrap_or_default();
+        self.fec_completed.insert(id, count);
+        rebuild(data_count, shards).map(Received::Message)
+    }
+
+    /// Messages still missing chunks, as `(id, received, count)`; for Reed-Solomon messages
+    /// `count` is how many chunks are needed.
+    pub fn incomplete(&self) -> impl Iterator<Item = (u8, usize, usize)> + '_ {
+        let plain = self.pending.iter().map(|(&id, parts)| (id, parts.iter().filter(|p| p.is_some()).count(), parts.len()));
+        let fec = self.fec_pending.iter().map(|(&id, (data_count, shards))| (id, shards.iter().filter(|p| p.is_some()).count(), *data_count));
+        plain.chain(fec)
+    }
+}
+
+#[cfg(feature = "fec")]
+fn rebuild(data_count: usize, mut shards: Shards) -> Result<Vec<u8>, String> {
+    use reed_solomon_erasure::galois_8::ReedSolomon;
+    let rs = ReedSolomon::new(data_count, shards.len() - data_count).map_err(|e| format!("reed-solomon: {:?}", e))?;
+    rs.reconstruct_data(&mut shards).map_err(|e| format!("reed-solomon: {:?}", e))?;
+    let data: Vec<u8> = shards.into_iter().take(data_count).flatten().flatten().collect();
+    // Shards from a corrupted or forged header can be too short for the length prefix
+    let len = data.get(..2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize).ok_or("reed-solomon: data shorter than its length prefix")?;
+    data.get(2..2 + len).map(<[u8]>::to_vec).ok_or_else(|| "reed-solomon: length past the end of the data".to_string())
+}
+
+#[cfg(not(feature = "fec"))]
+fn rebuild(_data_count: usize, _shards: Shards) -> Result<Vec<u8>, String> {
+    Err("message uses Reed-Solomon FEC, which this build was compiled without (the `fec` feature)".into())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn chunks_reassemble_in_any_order() {
+        let message = payload(CHUNK_DATA_LEN * 2 + 5);
+        let mut frames = split(&message, 3).unwrap();
+        assert_eq!(frames.len(), 3);
+        frames.reverse();
+        let mut reassembler = Reassembler::new();
+        assert!(matches!(reassembler.push(frames[0].clone()), Ok(Received::Partial { id: 3, received: 1, count: 3 })));
+        assert!(matches!(reassembler.push(frames[1].clone()), Ok(Received::Partial { id: 3, received: 2, count: 3 })));
+        assert!(matches!(reassembler.push(frames[2].clone()), Ok(Received::Message(m)) if m == message));
+        assert!(matches!(reassembler.push(frames[0].clone()), Ok(Received::Duplicate { id: 3 })));
+        assert_eq!(reassembler.incomplete().count(), 0);
+    }
+
+    #[test]
+    fn payloads_that_are_not_chunks_pass_through() {
+        let mut reassembler = Reassembler::new();
+        for bytes in [b"hello".to_vec(), vec![MAGIC, 1, 0], vec![]] {
+            assert_eq!(message_id(&bytes), None);
+            assert!(matches!(reassembler.push(bytes.clone()), Ok(Received::Message(m)) if m == bytes));
+        }
+    }
+
+    #[test]
+    fn chunks_carry_their_message_id() {
+        assert!(split(&payload(CHUNK_DATA_LEN + 1), 9).unwrap().iter().all(|f| message_id(f) == Some(9)));
+    }
+
+    #[test]
+    fn truncated_chunks_fail_their_crc() {
+        let mut frame = split(&payload(20), 1).unwrap().remove(0);
+        frame.truncate(frame.len() - 3);
+        assert!(Reassembler::new().push(frame).is_err());
+    }
+
+    #[test]
+    fn corrupted_chunks_fail_their_crc() {
+        let mut frame = split(&payload(20), 1).unwrap().remove(0);
+        frame[HEADER_LEN + 4] ^= 0x01;
+        let err = Reassembler::new().push(frame).err().unwrap();
+        assert!(err.contains("CRC"), "{}", err);
+    }
+
+    #[test]
+    fn chunk_index_past_the_count_is_an_error() {
+        let data = b"abc";
+        let mut frame = vec![MAGIC, 1, 2, 2];
+        frame.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        frame.extend_from_slice(data);
+        assert!(Reassembler::new().push(frame).is_err());
+    }
+
+    #[test]
+    fn oversized_payloads_are_refused() {
+        assert!(split(&payload(MAX_MESSAGE_LEN), 0).is_ok());
+        assert!(split(&payload(MAX_MESSAGE_LEN + 1), 0).is_err());
+    }
+
+    #[test]
+    fn control_frames_round_trip() {
+        let poll = Control::Poll { id: 9, count: 4 };
+        assert_eq!(Control::parse(&poll.to_bytes()), Some(poll));
+        let ack = Control::Ack { id: 9, received: vec![true, false, false, true, true, false, true, false, true] };
+        assert_eq!(Control::parse(&ack.to_bytes()), Some(ack));
+        // Bitmap shorter than the count
+        assert_eq!(Control::parse(&[CONTROL_MAGIC, b'!', 9, 9, 0xff]), None);
+    }
+
+    #[cfg(feature = "fec")]
+    fn fec_frame(id: u8, index: u8, count: u8, data_count: u8, shard: &[u8]) -> Vec<u8> {
+        let mut frame = vec![FEC_MAGIC, id, index, count, data_count];
+        frame.extend_from_slice(&crc32fast::hash(shard).to_le_bytes());
+        frame.extend_from_slice(shard);
+        frame
+    }
+
+    #[cfg(feature = "fec")]
+    #[test]
+    fn fec_rebuilds_lost_chunks() {
+        let message = payload(300);
+        let frames = split_fec(&message, 5, 6, 4).unwrap();
+        let data_count = frames[0][4] as usize;
+        // Lose as many frames as there are parity frames, from the front so data shards go missing
+        let lost = frames.len() - data_count;
+        let mut reassembler = Reassembler::new();
+        let results: Vec<Received> = frames.into_iter().skip(lost).map(|f| reassembler.push(f).unwrap()).collect();
+        assert!(matches!(results.last(), Some(Received::Message(m)) if *m == message));
+    }
+
+    #[cfg(feature = "fec")]
+    #[test]
+    fn fec_shards_too_short_for_the_length_prefix_are_an_error() {
+        let mut reassembler = Reassembler::new();
+        assert!(reassembler.push(fec_frame(7, 0, 2, 1, &[0x42])).is_err());
+    }
+}
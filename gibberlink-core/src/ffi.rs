@@ -0,0 +1,77 @@
+// Raw declarations for ggwave's C API (ggwave.h). Everything here is unsafe to call directly;
+// lib.rs wraps it.
+
+use std::ffi::c_int;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(non_snake_case)]
+pub struct GgwaveParameters {
+    pub payloadLength: c_int,
+    pub sampleRateInp: f32,
+    pub sampleRateOut: f32,
+    pub sampleRate: f32,
+    pub samplesPerFrame: c_int,
+    pub soundMarkerThreshold: f32,
+    pub sampleFormatInp: c_int,
+    pub sampleFormatOut: c_int,
+    pub operatingMode: c_int,
+}
+
+#[allow(non_camel_case_types)]
+pub type ggwave_Instance = c_int;
+
+// Enums from ggwave.h
+#[allow(non_camel_case_types)]
+pub mod ggwave_consts {
+    pub const GGWAVE_SAMPLE_FORMAT_UNDEFINED: i32 = 0;
+    pub const GGWAVE_SAMPLE_FORMAT_U8: i32 = 1;
+    pub const GGWAVE_SAMPLE_FORMAT_I8: i32 = 2;
+    pub const GGWAVE_SAMPLE_FORMAT_U16: i32 = 3;
+    pub const GGWAVE_SAMPLE_FORMAT_I16: i32 = 4;
+    pub const GGWAVE_SAMPLE_FORMAT_F32: i32 = 5;
+
+    pub const GGWAVE_PROTOCOL_AUDIBLE_NORMAL: i32 = 0;
+    pub const GGWAVE_PROTOCOL_AUDIBLE_FAST: i32 = 1;
+    pub const GGWAVE_PROTOCOL_AUDIBLE_FASTEST: i32 = 2;
+    pub const GGWAVE_PROTOCOL_ULTRASOUND_NORMAL: i32 = 3;
+    pub const GGWAVE_PROTOCOL_ULTRASOUND_FAST: i32 = 4;
+    pub const GGWAVE_PROTOCOL_ULTRASOUND_FASTEST: i32 = 5;
+    pub const GGWAVE_PROTOCOL_DT_NORMAL: i32 = 6;
+    pub const GGWAVE_PROTOCOL_DT_FAST: i32 = 7;
+    pub const GGWAVE_PROTOCOL_DT_FASTEST: i32 = 8;
+    pub const GGWAVE_PROTOCOL_MT_NORMAL: i32 = 9;
+    pub const GGWAVE_PROTOCOL_MT_FAST: i32 = 10;
+    pub const GGWAVE_PROTOCOL_MT_FASTEST: i32 = 11;
+
+    pub const GGWAVE_OPERATING_MODE_RX: i32 = 1 << 1;
+    pub const GGWAVE_OPERATING_MODE_TX: i32 = 1 << 2;
+    pub const GGWAVE_OPERATING_MODE_RX_AND_TX: i32 = GGWAVE_OPERATING_MODE_RX | GGWAVE_OPERATING_MODE_TX;
+}
+
+#[link(name = "ggwave")]
+extern "C" {
+    pub(crate) fn ggwave_getDefaultParameters() -> GgwaveParameters;
+    pub(crate) fn ggwave_setLogFile(fptr: *mut core::ffi::c_void);
+    pub(crate) fn ggwave_init(parameters: GgwaveParameters) -> ggwave_Instance;
+    pub(crate) fn ggwave_free(instance: ggwave_Instance);
+    pub(crate) fn ggwave_rxToggleProtocol(protocolId: c_int, state: c_int);
+    pub(crate) fn ggwave_encode(
+        instance: ggwave_Instance,
+        payloadBuffer: *const core::ffi::c_void,
+        payloadSize: c_int,
+        protocolId: c_int,
+        volume: c_int,
+        waveformBuffer: *mut core::ffi::c_void,
+        query: c_int,
+    ) -> c_int;
+    pub(crate) fn ggwave_ndecode(
+        instance: ggwave_Instance,
+        waveformBuffer: *const core::ffi::c_void,
+        waveformSize: c_int,
+        payloadBuffer: *mut core::ffi::c_void,
+        payloadSize: c_int,
+    ) -> c_int;
+}
+
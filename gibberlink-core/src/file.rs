@@ -0,0 +1,61 @@
+//! Envelope for sending a whole file as one payload (chunked like any other long payload):
+//!
+//! ```text
+//! 0x1C | version | name length | name (UTF-8) | size (u32 LE) | SHA-256 of contents | contents
+//! ```
+//!
+//! 0x1C is the ASCII file separator; see [`crate::framing`] for how other payloads stay clear of it.
+
+use sha2::{Digest, Sha256};
+
+const MAGIC: u8 = 0x1C;
+const VERSION: u8 = 1;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMessage {
+    pub name: String,
+    pub contents: Vec<u8>,
+}
+
+/// Wraps `contents` with its name, size and hash. Only the final path component of `name` is kept.
+pub fn pack(name: &str, contents: &[u8]) -> Result<Vec<u8>, String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    if name.is_empty() || name.len() > u8::MAX as usize {
+        return Err(format!("file name must be 1..={} bytes", u8::MAX));
+    }
+    let size = u32::try_from(contents.len()).map_err(|_| "file too large".to_string())?;
+    let mut out = Vec::with_capacity(3 + name.len() + 4 + 32 + contents.len());
+    out.extend_from_slice(&[MAGIC, VERSION, name.len() as u8]);
+    out.extend_from_slice(name.as_bytes());
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&Sha256::digest(contents));
+    out.extend_from_slice(contents);
+    Ok(out)
+}
+
+/// `None` if `payload` isn't a file envelope; an error if it is one but fails its size or hash check.
+pub fn unpack(payload: &[u8]) -> Option<Result<FileMessage, String>> {
+    let [MAGIC, version, name_len, rest @ ..] = payload else { return None };
+    let name_len = *name_len as usize;
+    if rest.len() < name_len + 4 + 32 {
+        return None;
+    }
+    if *version != VERSION {
+        return Some(Err(format!("unsupported file envelope version {}", version)));
+    }
+    let (name, rest) = rest.split_at(name_len);
+    let (size, rest) = rest.split_at(4);
+    let (hash, contents) = rest.split_at(32);
+    let name = match std::str::from_utf8(name) {
+        Ok(name) if !name.is_empty() && !name.contains(['/', '\\']) && name != "." && name != ".." => name,
+        _ => return Some(Err("file envelope has an invalid name".into())),
+    };
+    let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+    if contents.len() != size {
+        return Some(Err(format!("{}: expected {} bytes, got {}", name, size, contents.len())));
+    }
+    if Sha256::digest(contents).as_slice() != hash {
+        return Some(Err(format!("{}: SHA-256 mismatch", name)));
+    }
+    Some(Ok(FileMessage { name: name.to_owned(), contents: contents.to_vec() }))
+}
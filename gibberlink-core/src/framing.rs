@@ -0,0 +1,79 @@
+//! First bytes the framing layers claim, and the escape that keeps application payloads clear of
+//! them. Each layer marks its frames with one leading byte:
+//!
+//! ```text
+//! 0x0E  Reed-Solomon chunk (chunk)        0x1A  chat line (chat)
+//! 0x0F  integrity envelope (integrity)    0x1C  file (file)
+//! 0x16  deflate (gibberlink-tx)           0x1D  reliable-transfer control (chunk)
+//! 0x17  zstd (gibberlink-tx)              0x1E  chunk (chunk)
+//!                                         0x1F  encryption (gibberlink-tx)
+//! ```
+//!
+//! A payload of the application's own (text, CBOR, raw bytes) may start with any of those, so a
+//! sender passes it through [`escape`], which puts [`ESCAPE`] in front of one that does, and a
+//! receiver [`unescape`]s it once no layer has claimed it. The escape byte never starts a frame, so
+//! an escaped payload passes every layer untouched.
+
+/// DLE (data link escape)
+pub const ESCAPE: u8 = 0x10;
+
+/// Every first byte that needs escaping: the frame markers above and [`ESCAPE`] itself.
+pub const RESERVED: [u8; 10] = [0x0E, 0x0F, ESCAPE, 0x16, 0x17, 0x1A, 0x1C, 0x1D, 0x1E, 0x1F];
+
+/// `payload` as it must be sent: behind [`ESCAPE`] if its first byte is reserved, else unchanged.
+pub fn escape(payload: Vec<u8>) -> Vec<u8> {
+    match payload.first() {
+        Some(first) if RESERVED.contains(first) => [&[ESCAPE][..], &payload].concat(),
+        _ => payload,
+    }
+}
+
+/// The application payload a received message carries, once no framing layer has claimed it.
+pub fn unescape(mut payload: Vec<u8>) -> Vec<u8> {
+    if payload.first() == Some(&ESCAPE) {
+        payload.remove(0);
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{self, Control, Reassembler, Received};
+
+    #[test]
+    fn payloads_starting_with_reserved_bytes_round_trip() {
+        for first in RESERVED {
+            let payload = vec![first, b'A', b'B', b'C'];
+            let escaped = escape(payload.clone());
+            assert_eq!(escaped[0], ESCAPE);
+            assert_eq!(unescape(escaped), payload);
+        }
+        assert_eq!(escape(b"hello".to_vec()), b"hello");
+        assert_eq!(unescape(b"hello".to_vec()), b"hello");
+        assert_eq!(escape(Vec::new()), b"");
+    }
+
+    #[test]
+    fn every_core_frame_marker_is_reserved() {
+        let markers = [
+            chunk::split(b"x", 0).unwrap()[0][0],
+            Control::Poll { id: 0, count: 1 }.to_bytes()[0],
+            crate::integrity::pack(b"x").unwrap()[0],
+            crate::file::pack("a.txt", b"x").unwrap()[0],
+            crate::chat::pack("peer", "x").unwrap()[0],
+        ];
+        assert!(markers.iter().all(|m| RESERVED.contains(m)), "{:02x?}", markers);
+        #[cfg(feature = "fec")]
+        assert!(RESERVED.contains(&chunk::split_fec(b"x", 0, 2, 1).unwrap()[0][0]));
+    }
+
+    #[test]
+    fn escaped_frames_are_not_mistaken_for_chunks() {
+        // A raw payload shaped exactly like a chunk frame
+        let frame = chunk::split(b"not really a chunk", 4).unwrap().remove(0);
+        let mut reassembler = Reassembler::new();
+        assert!(matches!(reassembler.push(escape(frame.clone())), Ok(Received::Message(ref m)) if unescape(m.clone()) == frame));
+        assert_eq!(crate::integrity::unpack(&escape(crate::integrity::pack(b"x").unwrap())), None);
+    }
+}
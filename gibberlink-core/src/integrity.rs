@@ -0,0 +1,75 @@
+//! Length and checksum around a whole payload, for catching bytes ggwave's own error correction
+//! let through under heavy noise:
+//!
+//! ```text
+//! 0x0F | length (u16 LE) | CRC-32 of payload (LE) | payload
+//! ```
+//!
+//! Payloads that start with 0x0F themselves are escaped (see [`crate::framing`]).
+
+const MAGIC: u8 = 0x0F;
+const HEADER_LEN: usize = 7;
+
+/// Prefixes `payload` with its length and CRC-32.
+pub fn pack(payload: &[u8]) -> Result<Vec<u8>, String> {
+    let len = u16::try_from(payload.len()).map_err(|_| format!("payload is {} bytes; the integrity envelope allows at most {}", payload.len(), u16::MAX))?;
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.push(MAGIC);
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    out.extend_from_slice(payload);
+    Ok(out)
+}
+
+/// `None` if `bytes` isn't an integrity envelope; the payload if its length and CRC check out.
+pub fn unpack(bytes: &[u8]) -> Option<Result<Vec<u8>, String>> {
+    let [MAGIC, l0, l1, c0, c1, c2, c3, payload @ ..] = bytes else { return None };
+    let len = u16::from_le_bytes([*l0, *l1]) as usize;
+    if payload.len() != len {
+        return Some(Err(format!("integrity check failed: expected {} bytes, got {}", len, payload.len())));
+    }
+    let expected = u32::from_le_bytes([*c0, *c1, *c2, *c3]);
+    let actual = crc32fast::hash(payload);
+    if actual != expected {
+        return Some(Err(format!("integrity check failed: CRC-32 {:08x}, expected {:08x}", actual, expected)));
+    }
+    Some(Ok(payload.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for payload in [&b""[..], b"hello", &[0u8; 300]] {
+            assert_eq!(unpack(&pack(payload).unwrap()), Some(Ok(payload.to_vec())));
+        }
+    }
+
+    #[test]
+    fn other_payloads_are_not_envelopes() {
+        assert_eq!(unpack(b"hello"), None);
+        // Magic byte, but shorter than the header
+        assert_eq!(unpack(&[MAGIC, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn truncated_payload_is_an_error() {
+        let mut packed = pack(b"hello world").unwrap();
+        packed.pop();
+        assert!(matches!(unpack(&packed), Some(Err(_))));
+    }
+
+    #[test]
+    fn bad_crc_is_an_error() {
+        let mut packed = pack(b"hello world").unwrap();
+        packed[HEADER_LEN] ^= 0x20;
+        assert!(matches!(unpack(&packed), Some(Err(e)) if e.contains("CRC-32")));
+    }
+
+    #[test]
+    fn oversized_payloads_are_refused() {
+        assert!(pack(&vec![0; u16::MAX as usize + 1]).is_err());
+    }
+}
@@ -0,0 +1,629 @@
+//! Safe Rust interface to ggwave plus the WAV helpers used by `gibberlink-tx`, so other programs
+//! can encode and decode Gibberlink audio in-process instead of shelling out to the CLI.
+//!
+//! ```no_run
+//! let samples = gibberlink_core::encode(b"hello", gibberlink_core::parse_protocol("audible:fast")?, 25, None)?;
+//! let floats: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+//! assert_eq!(gibberlink_core::decode(&floats, 48000)?, b"hello");
+//! # Ok::<(), gibberlink_core::Error>(())
+//! ```
+
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod chat;
+pub mod chunk;
+mod ffi;
+pub mod file;
+pub mod framing;
+pub mod integrity;
+pub mod message;
+pub mod wav;
+
+use ffi::*;
+use std::ffi::c_int;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub use ffi::{ggwave_consts, GgwaveParameters};
+
+// Limits from ggwave.h
+pub const SAMPLE_RATE_MIN: f32 = 1000.0;
+pub const SAMPLE_RATE_MAX: f32 = 96000.0;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+pub const MAX_SAMPLES_PER_FRAME: i32 = 1024;
+pub const MAX_LENGTH_VARIABLE: usize = 140;
+pub const MAX_LENGTH_FIXED: i32 = 64;
+/// Built-in protocols (audible, ultrasound, dt and mt, at three speeds each); ids are `0..PROTOCOL_COUNT`
+pub const PROTOCOL_COUNT: i32 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperatingMode {
+    Rx,
+    Tx,
+    RxAndTx,
+}
+
+#[derive(Debug)]
+pub enum ParamsError {
+    SampleRate(&'static str, f32),
+    PayloadLength(i32),
+    SamplesPerFrame(i32),
+    MarkerThreshold(f32),
+    MissingInputFormat,
+    MissingOutputFormat,
+    Init(i32),
+}
+
+impl std::fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamsError::SampleRate(which, sr) => write!(f, "{} sample rate {} Hz is outside {}..={} Hz", which, sr, SAMPLE_RATE_MIN, SAMPLE_RATE_MAX),
+            ParamsError::PayloadLength(n) => write!(f, "fixed payload length {} must be 2..={}", n, MAX_LENGTH_FIXED),
+            ParamsError::SamplesPerFrame(n) => write!(f, "samples per frame {} must be 1..={}", n, MAX_SAMPLES_PER_FRAME),
+            ParamsError::MarkerThreshold(t) => write!(f, "sound marker threshold {} must be positive", t),
+            ParamsError::MissingInputFormat => f.write_str("rx mode needs an input sample format"),
+            ParamsError::MissingOutputFormat => f.write_str("tx mode needs an output sample format"),
+            ParamsError::Init(id) => write!(f, "ggwave_init rejected the parameters (instance {})", id),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+#[derive(Debug)]
+pub enum Error {
+    Params(ParamsError),
+    /// ggwave refused or failed to encode the payload (too long, bad protocol id)
+    Encode(String),
+    /// The audio contained no complete transmission
+    NoPayload,
+    PayloadTooLarge,
+    /// A protocol name [`parse_protocol`] does not know
+    UnknownProtocol(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Params(e) => write!(f, "ggwave init failed: {}", e),
+            Error::Encode(e) => f.write_str(e),
+            Error::NoPayload => f.write_str("No payload decoded"),
+            Error::PayloadTooLarge => f.write_str("Decoded payload too large"),
+            Error::UnknownProtocol(name) => {
+                write!(f, "unknown protocol {:?}; expected audible, ultrasound, dt or mt, optionally followed by :normal, :fast or :fastest", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Params(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParamsError> for Error {
+    fn from(e: ParamsError) -> Self {
+        Error::Params(e)
+    }
+}
+
+/// Validated construction of `GgwaveParameters`, so bad combinations fail with a reason instead of a negative instance id.
+#[derive(Clone)]
+pub struct ParametersBuilder {
+    params: GgwaveParameters,
+    rx_protocols: Option<Vec<i32>>,
+}
+
+impl Default for ParametersBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParametersBuilder {
+    pub fn new() -> Self {
+        let mut params = unsafe { ggwave_getDefaultParameters() };
+        params.sampleFormatInp = ggwave_consts::GGWAVE_SAMPLE_FORMAT_UNDEFINED;
+        params.sampleFormatOut = ggwave_consts::GGWAVE_SAMPLE_FORMAT_UNDEFINED;
+        ParametersBuilder { params, rx_protocols: None }
+    }
+
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        let sr = sample_rate as f32;
+        self.params.sampleRate = sr;
+        self.params.sampleRateInp = sr;
+        self.params.sampleRateOut = sr;
+        self
+    }
+
+    pub fn operating_mode(mut self, mode: OperatingMode) -> Self {
+        self.params.operatingMode = match mode {
+            OperatingMode::Rx => ggwave_consts::GGWAVE_OPERATING_MODE_RX,
+            OperatingMode::Tx => ggwave_consts::GGWAVE_OPERATING_MODE_TX,
+            OperatingMode::RxAndTx => ggwave_consts::GGWAVE_OPERATING_MODE_RX_AND_TX,
+        };
+        self
+    }
+
+    pub fn input_format(mut self, format: i32) -> Self {
+        self.params.sampleFormatInp = format;
+        self
+    }
+
+    pub fn output_format(mut self, format: i32) -> Self {
+        self.params.sampleFormatOut = format;
+        self
+    }
+
+    /// Fixed-length mode: every transmission carries exactly `len` bytes and no ggwave length header,
+    /// which decodes far more reliably. The first of them holds the payload's own length, so a
+    /// payload can be up to `len - 1` bytes; the rest is zero padding the receiver cuts off.
+    pub fn payload_len_fixed(mut self, len: i32) -> Self {
+        self.params.payloadLength = len;
+        self
+    }
+
+    /// Audio frame size: tone spacing is the sample rate divided by it, so both ends must agree.
+    /// Smaller frames react faster but separate tones less well.
+    pub fn samples_per_frame(mut self, n: i32) -> Self {
+        self.params.samplesPerFrame = n;
+        self
+    }
+
+    /// How far the start/end markers must stand out before a receiver locks on (default 3.0):
+    /// higher ignores more noise, lower catches weaker signals.
+    pub fn sound_marker_threshold(mut self, threshold: f32) -> Self {
+        self.params.soundMarkerThreshold = threshold;
+        self
+    }
+
+    /// Only listen for these protocols; by default a receiver tries every built-in one.
+    pub fn rx_protocols(mut self, protocols: &[i32]) -> Self {
+        self.rx_protocols = Some(protocols.to_vec());
+        self
+    }
+
+    pub fn build(self) -> Result<GgwaveParameters, ParamsError> {
+        use ggwave_consts::*;
+        let p = self.params;
+        for (which, sr) in [("base", p.sampleRate), ("input", p.sampleRateInp), ("output", p.sampleRateOut)] {
+            if !(SAMPLE_RATE_MIN..=SAMPLE_RATE_MAX).contains(&sr) {
+                return Err(ParamsError::SampleRate(which, sr));
+            }
+        }
+        if p.payloadLength != -1 && !(2..=MAX_LENGTH_FIXED).contains(&p.payloadLength) {
+            return Err(ParamsError::PayloadLength(p.payloadLength));
+        }
+        if !(1..=MAX_SAMPLES_PER_FRAME).contains(&p.samplesPerFrame) {
+            return Err(ParamsError::SamplesPerFrame(p.samplesPerFrame));
+        }
+        if !(p.soundMarkerThreshold > 0.0 && p.soundMarkerThreshold.is_finite()) {
+            return Err(ParamsError::MarkerThreshold(p.soundMarkerThreshold));
+        }
+        if p.operatingMode & GGWAVE_OPERATING_MODE_RX != 0 && p.sampleFormatInp == GGWAVE_SAMPLE_FORMAT_UNDEFINED {
+            return Err(ParamsError::MissingInputFormat);
+        }
+        if p.operatingMode & GGWAVE_OPERATING_MODE_TX != 0 && p.sampleFormatOut == GGWAVE_SAMPLE_FORMAT_UNDEFINED {
+            return Err(ParamsError::MissingOutputFormat);
+        }
+        Ok(p)
+    }
+
+    pub fn init(self) -> Result<GgWave, ParamsError> {
+        let rx_protocols = self.rx_protocols.clone();
+        GgWave::create(self.build()?, rx_protocols.as_deref())
+    }
+}
+
+// The C API keeps every instance in one unsynchronised global table (and shares scratch buffers
+// between them), so all calls into it are serialised through this lock.
+static GGWAVE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+fn ggwave_lock() -> std::sync::MutexGuard<'static, ()> {
+    GGWAVE_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Stop ggwave from logging to stderr (it does by default).
+pub fn disable_logging() {
+    let _guard = ggwave_lock();
+    unsafe { ggwave_setLogFile(std::ptr::null_mut()) }
+}
+
+/// Owned ggwave instance, freed on drop. It is `Send` because every FFI call holds `GGWAVE_LOCK`;
+/// methods take `&mut self` since encode/decode mutate per-instance rx/tx state.
+pub struct GgWave {
+    id: ggwave_Instance,
+    params: GgwaveParameters,
+}
+
+unsafe impl Send for GgWave {}
+
+impl GgWave {
+    /// Prefer `ParametersBuilder::init`, which validates the parameters first.
+    pub fn new(params: GgwaveParameters) -> Result<Self, ParamsError> {
+        Self::create(params, None)
+    }
+
+    fn create(params: GgwaveParameters, rx_protocols: Option<&[i32]>) -> Result<Self, ParamsError> {
+        let _guard = ggwave_lock();
+        // ggwave keeps one global rx protocol set and copies it into each new instance, so it is
+        // set before every init rather than left to whatever the previous caller chose
+        for protocol in 0..PROTOCOL_COUNT {
+            let enabled = rx_protocols.is_none_or(|p| p.contains(&protocol));
+            unsafe { ggwave_rxToggleProtocol(protocol, enabled as c_int) };
+        }
+        let id = unsafe { ggwave_init(params) };
+        if id < 0 {
+            return Err(ParamsError::Init(id));
+        }
+        Ok(GgWave { id, params })
+    }
+
+    pub fn parameters(&self) -> &GgwaveParameters {
+        &self.params
+    }
+
+    /// Encodes `payload` into a waveform in the instance's output sample format.
+    pub fn encode(&mut self, payload: &[u8], protocol: i32, volume: i32) -> Result<Vec<u8>, Error> {
+        let padded;
+        let payload = match self.fixed_len() {
+            Some(len) if payload.len() >= len => {
+                return Err(Error::Encode(format!(
+                    "payload is {} bytes, more than the {} a fixed length of {} carries",
+                    payload.len(),
+                    len - 1,
+                    len
+                )));
+            }
+            Some(len) => {
+                padded = [&[payload.len() as u8][..], payload, &vec![0; len - 1 - payload.len()]].concat();
+                &padded
+            }
+            None => payload,
+        };
+        let _guard = ggwave_lock();
+        unsafe {
+            // Query size
+            let nbytes = ggwave_encode(
+                self.id,
+                payload.as_ptr() as *const _,
+                payload.len() as c_int,
+                protocol,
+                volume,
+                std::ptr::null_mut(),
+                1,
+            );
+            if nbytes <= 0 {
+                return Err(Error::Encode("ggwave_encode size query failed".into()));
+            }
+
+            let mut buf = vec![0u8; nbytes as usize];
+            let nwritten = ggwave_encode(
+                self.id,
+                payload.as_ptr() as *const _,
+                payload.len() as c_int,
+                protocol,
+                volume,
+                buf.as_mut_ptr() as *mut _,
+                0,
+            );
+            if nwritten != nbytes {
+                return Err(Error::Encode(format!("ggwave_encode wrote {} but expected {}", nwritten, nbytes)));
+            }
+            Ok(buf)
+        }
+    }
+
+    /// Raw `ggwave_ndecode`: the payload length, 0 if nothing was decoded, or -2 if `out` is too small.
+    pub fn ndecode(&mut self, waveform: &[u8], out: &mut [u8]) -> c_int {
+        let _guard = ggwave_lock();
+        unsafe {
+            ggwave_ndecode(
+                self.id,
+                waveform.as_ptr() as *const _,
+                waveform.len() as c_int,
+                out.as_mut_ptr() as *mut _,
+                out.len() as c_int,
+            )
+        }
+    }
+
+    /// Feeds `waveform` (in the instance's input sample format) and returns the decoded payload.
+    pub fn decode(&mut self, waveform: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut cap = 256usize;
+        loop {
+            let mut out = vec![0u8; cap];
+            let n = self.ndecode(waveform, &mut out);
+            if n == -2 {
+                cap *= 2;
+                if cap > 65536 { return Err(Error::PayloadTooLarge); }
+                continue;
+            }
+            if n <= 0 { return Err(Error::NoPayload); }
+            out.truncate(n as usize);
+            return self.unpad(out).ok_or(Error::NoPayload);
+        }
+    }
+
+    /// Streaming receive: feeds the next chunk of live audio and returns a payload once a whole
+    /// transmission has been heard. Partial transmissions carry over to the next call.
+    pub fn receive(&mut self, chunk: &[u8]) -> Option<Vec<u8>> {
+        let mut out = [0u8; 2 * MAX_LENGTH_VARIABLE];
+        let n = self.ndecode(chunk, &mut out);
+        (n > 0).then(|| self.unpad(out[..n as usize].to_vec())).flatten()
+    }
+
+    fn fixed_len(&self) -> Option<usize> {
+        (self.params.payloadLength > 0).then_some(self.params.payloadLength as usize)
+    }
+
+    // Cuts a fixed-length frame down to the payload its length byte gives, so payloads ending in
+    // zero bytes survive. A length that doesn't fit the frame means it was misheard: None
+    fn unpad(&self, mut payload: Vec<u8>) -> Option<Vec<u8>> {
+        if self.fixed_len().is_some() {
+            let len = *payload.first()? as usize;
+            if len >= payload.len() {
+                return None;
+            }
+            payload.truncate(len + 1);
+            payload.remove(0);
+        }
+        Some(payload)
+    }
+}
+
+impl Drop for GgWave {
+    fn drop(&mut self) {
+        let _guard = ggwave_lock();
+        unsafe { ggwave_free(self.id) }
+    }
+}
+
+/// A [`GgWave`] whose clones share one instance, for daemons and GUIs whose threads take turns
+/// with it. Each call holds the instance throughout, so calls from different threads never overlap,
+/// and a streaming [`SharedDecoder::receive`] carries over to whichever thread feeds it next.
+#[derive(Clone)]
+pub struct SharedDecoder(Arc<Mutex<GgWave>>);
+
+impl SharedDecoder {
+    pub fn new(instance: GgWave) -> Self {
+        SharedDecoder(Arc::new(Mutex::new(instance)))
+    }
+
+    // A call that panicked inside ggwave leaves nothing half-done on the Rust side
+    fn instance(&self) -> MutexGuard<'_, GgWave> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn parameters(&self) -> GgwaveParameters {
+        *self.instance().parameters()
+    }
+
+    /// [`GgWave::encode`] on the shared instance.
+    pub fn encode(&self, payload: &[u8], protocol: i32, volume: i32) -> Result<Vec<u8>, Error> {
+        self.instance().encode(payload, protocol, volume)
+    }
+
+    /// [`GgWave::decode`] on the shared instance.
+    pub fn decode(&self, waveform: &[u8]) -> Result<Vec<u8>, Error> {
+        self.instance().decode(waveform)
+    }
+
+    /// [`GgWave::receive`] on the shared instance.
+    pub fn receive(&self, chunk: &[u8]) -> Option<Vec<u8>> {
+        self.instance().receive(chunk)
+    }
+}
+
+impl From<GgWave> for SharedDecoder {
+    fn from(instance: GgWave) -> Self {
+        SharedDecoder::new(instance)
+    }
+}
+
+/// Encodes `payload` to mono 16-bit samples at `sample_rate` (48 kHz by default).
+pub fn encode(payload: &[u8], protocol: i32, volume: i32, sample_rate: Option<u32>) -> Result<Vec<i16>, Error> {
+    let mut builder = ParametersBuilder::new()
+        .operating_mode(OperatingMode::Tx)
+        .output_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16);
+    if let Some(sr) = sample_rate { builder = builder.sample_rate(sr); }
+    let buf = builder.init()?.encode(payload, protocol, volume)?;
+    Ok(buf.chunks_exact(2).map(|c| i16::from_le_bytes([c[0], c[1]])).collect())
+}
+
+/// Decodes the first transmission found in mono `samples` (-1.0..1.0).
+pub fn decode(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, Error> {
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    ParametersBuilder::new()
+        .operating_mode(OperatingMode::Rx)
+        .input_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32)
+        .sample_rate(sample_rate)
+        .init()?
+        .decode(&bytes)
+}
+
+/// Decodes up to `max` transmissions from a long recording, in order, by streaming it through one
+/// instance a frame at a time.
+pub fn decode_all(samples: &[f32], sample_rate: u32, max: usize) -> Result<Vec<Vec<u8>>, Error> {
+    decode_all_with(samples, sample_rate, max, None)
+}
+
+/// Like [`decode_all`], listening only for `rx_protocols` when given: faster, and an unexpected
+/// protocol can't produce a false decode.
+pub fn decode_all_with(samples: &[f32], sample_rate: u32, max: usize, rx_protocols: Option<&[i32]>) -> Result<Vec<Vec<u8>>, Error> {
+    let mut builder = ParametersBuilder::new();
+    if let Some(protocols) = rx_protocols {
+        builder = builder.rx_protocols(protocols);
+    }
+    decode_all_using(samples, sample_rate, max, builder)
+}
+
+/// Like [`decode_all`], with the receiver's other settings (fixed payload length, rx protocols)
+/// taken from `builder`; its operating mode, input format and sample rate are set here.
+pub fn decode_all_using(samples: &[f32], sample_rate: u32, max: usize, builder: ParametersBuilder) -> Result<Vec<Vec<u8>>, Error> {
+    let mut messages = Vec::new();
+    if max > 0 {
+        decode_each_using(samples, sample_rate, builder, |message| {
+            messages.push(message);
+            if messages.len() < max { ControlFlow::Continue(()) } else { ControlFlow::Break(()) }
+        })?;
+    }
+    if messages.is_empty() {
+        return Err(Error::NoPayload);
+    }
+    Ok(messages)
+}
+
+/// Like [`decode_all_using`], handing each message to `on_message` as soon as it is decoded, so a
+/// caller that has what it needs can stop the rest of the recording being decoded with `Break`.
+pub fn decode_each_using(samples: &[f32], sample_rate: u32, builder: ParametersBuilder, mut on_message: impl FnMut(Vec<u8>) -> ControlFlow<()>) -> Result<(), Error> {
+    let mut instance = builder
+        .operating_mode(OperatingMode::Rx)
+        .input_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32)
+        .sample_rate(sample_rate)
+        .init()?;
+    let mut found = false;
+    for frame in samples.chunks(MAX_SAMPLES_PER_FRAME as usize) {
+        let bytes: Vec<u8> = frame.iter().flat_map(|s| s.to_le_bytes()).collect();
+        if let Some(message) = instance.receive(&bytes) {
+            found = true;
+            if on_message(message).is_break() {
+                break;
+            }
+        }
+    }
+    if !found {
+        return Err(Error::NoPayload);
+    }
+    Ok(())
+}
+
+/// The protocol and sample rate [`detect`] found a recording's messages with.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Detection {
+    pub protocol: i32,
+    pub sample_rate: u32,
+    pub messages: Vec<Vec<u8>>,
+}
+
+/// Finds which protocol a recording was encoded with by decoding it with one protocol family and
+/// then one protocol enabled at a time. `sample_rate` is tried first, then 48 and 44.1 kHz, for
+/// files whose header doesn't match how the audio was actually sampled.
+pub fn detect(samples: &[f32], sample_rate: u32) -> Result<Detection, Error> {
+    let mut rates = vec![sample_rate];
+    rates.extend([DEFAULT_SAMPLE_RATE, 44100].into_iter().filter(|&r| r != sample_rate));
+    for rate in rates {
+        for family in (0..PROTOCOL_COUNT).step_by(3) {
+            let protocols: Vec<i32> = (family..family + 3).collect();
+            if decode_all_with(samples, rate, 1, Some(&protocols)).is_err() {
+                continue;
+            }
+            for protocol in protocols {
+                if let Ok(messages) = decode_all_with(samples, rate, usize::MAX, Some(&[protocol])) {
+                    return Ok(Detection { protocol, sample_rate: rate, messages });
+                }
+            }
+        }
+    }
+    Err(Error::NoPayload)
+}
+
+/// The `family:speed` name of a protocol id, as accepted by [`parse_protocol`].
+pub fn protocol_name(protocol: i32) -> String {
+    let family = ["audible", "ultrasound", "dt", "mt"].get(protocol as usize / 3).copied().unwrap_or("unknown");
+    let speed = ["normal", "fast", "fastest"][protocol.rem_euclid(3) as usize];
+    format!("{}:{}", family, speed)
+}
+
+/// Maps `family[:speed]` (e.g. `audible:fast`, `ultrasound`) to a protocol id; the speed defaults
+/// to normal, and any other name is an [`Error::UnknownProtocol`].
+pub fn parse_protocol(s: &str) -> Result<i32, Error> {
+    use ggwave_consts::*;
+    let (family, speed) = if let Some((a, b)) = s.split_once(':') { (a, b) } else { (s, "normal") };
+    Ok(match (family.to_ascii_lowercase().as_str(), speed.to_ascii_lowercase().as_str()) {
+        ("audible", "normal") => GGWAVE_PROTOCOL_AUDIBLE_NORMAL,
+        ("audible", "fast") => GGWAVE_PROTOCOL_AUDIBLE_FAST,
+        ("audible", "fastest") => GGWAVE_PROTOCOL_AUDIBLE_FASTEST,
+        ("ultrasound", "normal") => GGWAVE_PROTOCOL_ULTRASOUND_NORMAL,
+        ("ultrasound", "fast") => GGWAVE_PROTOCOL_ULTRASOUND_FAST,
+        ("ultrasound", "fastest") => GGWAVE_PROTOCOL_ULTRASOUND_FASTEST,
+        ("dt", "normal") => GGWAVE_PROTOCOL_DT_NORMAL,
+        ("dt", "fast") => GGWAVE_PROTOCOL_DT_FAST,
+        ("dt", "fastest") => GGWAVE_PROTOCOL_DT_FASTEST,
+        ("mt", "normal") => GGWAVE_PROTOCOL_MT_NORMAL,
+        ("mt", "fast") => GGWAVE_PROTOCOL_MT_FAST,
+        ("mt", "fastest") => GGWAVE_PROTOCOL_MT_FASTEST,
+        _ => return Err(Error::UnknownProtocol(s.to_owned())),
+    })
+}
+
+// A protocol's tone band in bins: tones start at `start` bins and span 16 tones per nibble, two
+// nibbles per byte, `bytes_per_tx` bytes per frame, spaced `spacing` bins apart (ggwave.cpp)
+fn tone_bins(protocol: i32) -> (i32, i32) {
+    let (start, bytes_per_tx, spacing) = match protocol / 3 {
+        0 => (40, 3, 1),  // audible
+        1 => (320, 3, 1), // ultrasound
+        2 => (24, 1, 2),  // dt
+        _ => (24, 1, 1),  // mt
+    };
+    (start, start + 2 * 16 * bytes_per_tx * spacing)
+}
+
+// Upper edge of a protocol's tone band. Bins are `sample_rate / samples_per_frame` Hz wide, so
+// smaller frames push every tone higher
+pub fn protocol_max_hz(protocol: i32, sample_rate: u32, samples_per_frame: i32) -> f32 {
+    tone_bins(protocol).1 as f32 * sample_rate as f32 / samples_per_frame as f32
+}
+
+// Lower edge of a protocol's tone band, in the same terms as protocol_max_hz
+pub fn protocol_min_hz(protocol: i32, sample_rate: u32, samples_per_frame: i32) -> f32 {
+    tone_bins(protocol).0 as f32 * sample_rate as f32 / samples_per_frame as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_length_frames_keep_trailing_zero_bytes() {
+        let fixed = || ParametersBuilder::new().payload_len_fixed(8);
+        let mut tx = fixed().operating_mode(OperatingMode::Tx).output_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32).init().unwrap();
+        for payload in [&b"AB\0\0"[..], b"\0", b"", b"1234567"] {
+            let waveform = tx.encode(payload, parse_protocol("audible:fastest").unwrap(), 50).unwrap();
+            let samples: Vec<f32> = waveform.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect();
+            assert_eq!(decode_all_using(&samples, DEFAULT_SAMPLE_RATE, 1, fixed()).unwrap(), [payload]);
+        }
+        assert!(matches!(tx.encode(b"12345678", parse_protocol("audible:fastest").unwrap(), 50), Err(Error::Encode(_))));
+    }
+
+    #[test]
+    fn protocol_names_are_checked() {
+        assert_eq!(parse_protocol("Ultrasound:FAST").unwrap(), ggwave_consts::GGWAVE_PROTOCOL_ULTRASOUND_FAST);
+        assert_eq!(parse_protocol("dt").unwrap(), ggwave_consts::GGWAVE_PROTOCOL_DT_NORMAL);
+        for name in ["audibel", "audible:slow", ""] {
+            assert!(matches!(parse_protocol(name), Err(Error::UnknownProtocol(ref n)) if n == name));
+        }
+    }
+
+    #[test]
+    fn shared_decoders_decode_from_several_threads() {
+        let builder = || ParametersBuilder::new().input_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32).output_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32);
+        let encoder = SharedDecoder::new(builder().operating_mode(OperatingMode::Tx).init().unwrap());
+        let decoder = SharedDecoder::new(builder().operating_mode(OperatingMode::Rx).init().unwrap());
+        let waveforms: Vec<Vec<u8>> = [&b"first"[..], b"second"].iter().map(|p| encoder.encode(p, parse_protocol("audible:fastest").unwrap(), 50).unwrap()).collect();
+        std::thread::scope(|scope| {
+            for (waveform, payload) in waveforms.iter().zip([&b"first"[..], b"second"]) {
+                let decoder = decoder.clone();
+                scope.spawn(move || {
+                    for _ in 0..3 {
+                        assert_eq!(decoder.decode(waveform).unwrap(), payload);
+                    }
+                });
+            }
+        });
+    }
+}
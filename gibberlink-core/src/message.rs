@@ -0,0 +1,112 @@
+//! Whole messages to and from audio, for programs that bring their own audio I/O (the C, JNI and
+//! Node.js bindings): [`encode`] chunks payloads longer than one transmission and [`Receiver`]
+//! turns captured samples back into messages, reassembled and integrity-checked as the CLI does.
+
+use crate::chunk::{self, Control, Reassembler, Received};
+use crate::{framing, ggwave_consts, integrity, OperatingMode, ParametersBuilder, ParamsError, SharedDecoder};
+use std::sync::{Arc, Mutex};
+
+/// Encodes `payload` to mono samples (-1.0..1.0) at `sample_rate`: one transmission, or chunks
+/// (see [`chunk`]) with a tenth of a second of silence between them when it is too long for one.
+/// The payload is escaped (see [`framing`]) so no receiver takes it for a frame.
+pub fn encode(payload: &[u8], protocol: i32, volume: i32, sample_rate: u32) -> Result<Vec<f32>, String> {
+    let payload = framing::escape(payload.to_vec());
+    let frames = if payload.len() <= crate::MAX_LENGTH_VARIABLE {
+        vec![payload]
+    } else {
+        let id = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.subsec_nanos() as u8);
+        chunk::split(&payload, id)?
+    };
+    let mut samples = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        if i > 0 {
+            samples.resize(samples.len() + sample_rate as usize / 10, 0.0);
+        }
+        let encoded = crate::encode(frame, protocol, volume, Some(sample_rate)).map_err(|e| e.to_string())?;
+        samples.extend(encoded.iter().map(|&s| s as f32 / 32768.0));
+    }
+    Ok(samples)
+}
+
+/// Streaming decoder for live audio: feed it captured samples as they arrive and it returns each
+/// message once all of its chunks have been heard. Clones are the same receiver, so a capture
+/// thread and the rest of an app can share it; pushes from different threads take turns.
+#[derive(Clone)]
+pub struct Receiver {
+    decoder: SharedDecoder,
+    reassembler: Arc<Mutex<Reassembler>>,
+}
+
+impl Receiver {
+    /// A receiver for mono samples at `sample_rate` (the capture device's own rate is fine).
+    pub fn new(sample_rate: u32) -> Result<Receiver, ParamsError> {
+        let instance = ParametersBuilder::new()
+            .operating_mode(OperatingMode::Rx)
+            .input_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32)
+            .sample_rate(sample_rate)
+            .init()?;
+        Ok(Receiver { decoder: instance.into(), reassembler: Arc::default() })
+    }
+
+    /// Feeds the next mono samples (-1.0..1.0) and returns the messages they completed: each one
+    /// whole, or why it was dropped (a corrupted chunk, a failed integrity check).
+    pub fn push(&self, samples: &[f32]) -> Vec<Result<Vec<u8>, String>> {
+        let frame = self.decoder.parameters().samplesPerFrame.max(1) as usize;
+        let mut messages = Vec::new();
+        // A frame at a time, so two transmissions in one long buffer are both heard
+        for piece in samples.chunks(frame) {
+            let bytes: Vec<u8> = piece.iter().flat_map(|s| s.to_le_bytes()).collect();
+            if let Some(message) = self.decoder.receive(&bytes).and_then(|payload| self.unframe(payload)) {
+                messages.push(message);
+            }
+        }
+        messages
+    }
+
+    fn unframe(&self, payload: Vec<u8>) -> Option<Result<Vec<u8>, String>> {
+        // Polls and acknowledgements of a reliable transfer are not messages
+        if Control::parse(&payload).is_some() {
+            return None;
+        }
+        match self.reassembler.lock().unwrap_or_else(|e| e.into_inner()).push(payload) {
+            Ok(Received::Message(bytes)) => Some(integrity::unpack(&bytes).unwrap_or(Ok(bytes)).map(framing::unescape)),
+            Ok(Received::Partial { .. } | Received::Duplicate { .. }) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_messages_round_trip_in_small_buffers() {
+        let payload: Vec<u8> = (0..400).map(|i| b'a' + (i % 26) as u8).collect();
+        let samples = encode(&payload, crate::parse_protocol("audible:fastest").unwrap(), 50, 48000).unwrap();
+        let receiver = Receiver::new(48000).unwrap();
+        let heard: Vec<_> = samples.chunks(480).flat_map(|buffer| receiver.push(buffer)).collect();
+        assert_eq!(heard, [Ok(payload)]);
+    }
+
+    #[test]
+    fn integrity_envelopes_are_checked() {
+        let packed = integrity::pack(b"checked").unwrap();
+        let receiver = Receiver::new(48000).unwrap();
+        assert_eq!(receiver.unframe(packed), Some(Ok(b"checked".to_vec())));
+        let mut corrupt = integrity::pack(b"checked").unwrap();
+        corrupt.push(0);
+        assert!(matches!(receiver.unframe(corrupt), Some(Err(_))));
+        assert_eq!(receiver.unframe(Control::Poll { id: 1, count: 2 }.to_bytes()), None);
+    }
+
+    #[test]
+    fn payloads_starting_with_frame_markers_round_trip() {
+        let receiver = Receiver::new(48000).unwrap();
+        for first in framing::RESERVED {
+            let payload = vec![first, b'A', b'B', b'C'];
+            let samples = encode(&payload, crate::parse_protocol("audible:fastest").unwrap(), 50, 48000).unwrap();
+            assert_eq!(receiver.push(&samples), [Ok(payload)]);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Minimal RIFF/WAVE reading and writing: mono output in any ggwave sample format, and input
+//! downmixed to mono for decoding.
+
+use crate::ggwave_consts;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct WavData {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    pub format_tag: u16, // 1 = PCM, 3 = IEEE float; WAVE_FORMAT_EXTENSIBLE files report their sub-format
+    pub data: Vec<u8>,
+}
+
+fn read_le_u16(buf: &[u8]) -> u16 { u16::from_le_bytes([buf[0], buf[1]]) }
+fn read_le_u32(buf: &[u8]) -> u32 { u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) }
+
+pub fn read_wav(path: &Path) -> Result<WavData, String> {
+    read_wav_from(std::io::BufReader::new(File::open(path).map_err(|e| format!("open: {}", e))?))
+}
+
+/// Like [`read_wav`], for a WAV arriving on a stream such as stdin. Recorders writing to a pipe
+/// can't fill in the data length, so a data chunk that ends early is taken as it is.
+pub fn read_wav_from(mut f: impl Read) -> Result<WavData, String> {
+    let mut header = [0u8; 12];
+    f.read_exact(&mut header).map_err(|e| format!("read header: {}", e))?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err("Not a RIFF/WAVE file".into());
+    }
+    let mut fmt_chunk_found = false;
+    let mut data_chunk_found = false;
+    let mut format_tag = 1u16;
+    let mut channels = 1u16;
+    let mut sample_rate = 44100u32;
+    let mut bits_per_sample = 16u16;
+    let mut data = Vec::new();
+
+    loop {
+        let mut chunk_hdr = [0u8; 8];
+        if f.read_exact(&mut chunk_hdr).is_err() { break; }
+        let id = &chunk_hdr[0..4];
+        let len = read_le_u32(&chunk_hdr[4..8]) as usize;
+        if id == b"data" {
+            (&mut f).take(len as u64).read_to_end(&mut data).map_err(|e| format!("read data: {}", e))?;
+            data_chunk_found = true;
+            if fmt_chunk_found || data.len() < len { break; }
+            if len % 2 == 1 { let mut pad = [0u8; 1]; let _ = f.read_exact(&mut pad); }
+            continue;
+        }
+        let mut chunk = vec![0u8; len];
+        f.read_exact(&mut chunk).map_err(|e| format!("read chunk: {}", e))?;
+        if len % 2 == 1 { let mut pad = [0u8; 1]; let _ = f.read_exact(&mut pad); }
+        if id == b"fmt " {
+            if len < 16 { return Err("fmt chunk too small".into()); }
+            format_tag = read_le_u16(&chunk[0..2]);
+            channels = read_le_u16(&chunk[2..4]);
+            sample_rate = read_le_u32(&chunk[4..8]);
+            bits_per_sample = read_le_u16(&chunk[14..16]);
+            // WAVE_FORMAT_EXTENSIBLE: the real tag is the first two bytes of the sub-format GUID
+            if format_tag == 0xFFFE {
+                if len < 40 { return Err("extensible fmt chunk too small".into()); }
+                format_tag = read_le_u16(&chunk[24..26]);
+            }
+            fmt_chunk_found = true;
+        }
+        if fmt_chunk_found && data_chunk_found { break; }
+    }
+    if !fmt_chunk_found || !data_chunk_found {
+        return Err("Missing fmt or data chunk".into());
+    }
+    Ok(WavData { sample_rate, channels, bits_per_sample, format_tag, data })
+}
+
+pub fn downmix_to_mono(w: &WavData) -> Result<(i32, Vec<u8>), String> {
+    use ggwave_consts::*;
+    if w.channels == 0 {
+        return Err("WAV has no channels".into());
+    }
+    // ggwave has no 24- or 32-bit integer format, so those come out as f32
+    if w.format_tag == 1 && matches!(w.bits_per_sample, 24 | 32) {
+        let width = w.bits_per_sample as usize / 8;
+        let channels = w.channels as usize;
+        let out = w
+            .data
+            .chunks_exact(width * channels)
+            .flat_map(|frame| {
+                let sum: f32 = frame.chunks_exact(width).map(pcm_to_f32).sum();
+                (sum / channels as f32).to_le_bytes()
+            })
+            .collect();
+        return Ok((GGWAVE_SAMPLE_FORMAT_F32, out));
+    }
+    if w.channels == 1 {
+        let fmt = match (w.format_tag, w.bits_per_sample) {
+            (1, 8) => GGWAVE_SAMPLE_FORMAT_U8,
+            (1, 16) => GGWAVE_SAMPLE_FORMAT_I16,
+            (3, 32) => GGWAVE_SAMPLE_FORMAT_F32,
+            _ => return Err(format!("Unsupported WAV format tag {} bits {}", w.format_tag, w.bits_per_sample)),
+        };
+        return Ok((fmt, w.data.clone()));
+    }
+    match (w.format_tag, w.bits_per_sample) {
+        (1, 16) => {
+            let frame_count = w.data.len() / (2 * w.channels as usize);
+            let mut out = Vec::with_capacity(frame_count * 2);
+            for i in 0..frame_count {
+                let mut acc: i32 = 0;
+                for ch in 0..w.channels as usize {
+                    let idx = (i * w.channels as usize + ch) * 2;
+                    let s = i16::from_le_bytes([w.data[idx], w.data[idx+1]]) as i32;
+                    acc += s;
+                }
+                let avg = (acc / (w.channels as i32)).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+                out.extend_from_slice(&avg.to_le_bytes());
+            }
+            Ok((GGWAVE_SAMPLE_FORMAT_I16, out))
+        }
+        (1, 8) => {
+            let frame_count = w.data.len() / w.channels as usize;
+            let mut out = Vec::with_capacity(frame_count);
+            for i in 0..frame_count {
+                let mut acc: i32 = 0;
+                for ch in 0..w.channels as usize {
+                    let idx = i * w.channels as usize + ch;
+                    let s = w.data[idx] as i32;
+                    acc += s;
+                }
+                let avg = (acc / (w.channels as i32)).clamp(0, 255) as u8;
+                out.push(avg);
+            }
+            Ok((GGWAVE_SAMPLE_FORMAT_U8, out))
+        }
+        (3, 32) => {
+            let frame_count = w.data.len() / (4 * w.channels as usize);
+            let mut out = Vec::with_capacity(frame_count * 4);
+            for i in 0..frame_count {
+                let mut acc: f32 = 0.0;
+                for ch in 0..w.channels as usize {
+                    let idx = (i * w.channels as usize + ch) * 4;
+                    let s = f32::from_le_bytes([w.data[idx], w.data[idx+1], w.data[idx+2], w.data[idx+3]]);
+                    acc += s;
+                }
+                let avg = acc / (w.channels as f32);
+                out.extend_from_slice(&avg.to_le_bytes());
+            }
+            Ok((GGWAVE_SAMPLE_FORMAT_F32, out))
+        }
+        _ => Err(format!("Unsupported multi-channel WAV format tag {} bits {}", w.format_tag, w.bits_per_sample)),
+    }
+}
+
+// Little-endian signed PCM of 1-4 bytes, scaled to [-1, 1)
+fn pcm_to_f32(sample: &[u8]) -> f32 {
+    let mut bytes = [0u8; 4];
+    bytes[4 - sample.len()..].copy_from_slice(sample);
+    i32::from_le_bytes(bytes) as f32 / 2_147_483_648.0
+}
+
+pub fn to_mono_f32(w: &WavData) -> Result<Vec<f32>, String> {
+    use ggwave_consts::*;
+    let (fmt, bytes) = downmix_to_mono(w)?;
+    Ok(match fmt {
+        GGWAVE_SAMPLE_FORMAT_U8 => bytes.iter().map(|&b| (b as f32 - 128.0) / 128.0).collect(),
+        GGWAVE_SAMPLE_FORMAT_I16 => bytes.chunks_exact(2).map(|c| i16::from_le_bytes([c[0], c[1]]) as f32 / 32768.0).collect(),
+        _ => bytes.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect(),
+    })
+}
+
+
+pub fn write_wav(path: &Path, sample_rate: u32, sample_format: i32, data: &[u8]) -> std::io::Result<()> {
+    write_wav_to(BufWriter::new(File::create(path)?), sample_rate, sample_format, data)
+}
+
+/// Like [`write_wav`], to any writer (e.g. a `Vec<u8>` for a WAV held in memory).
+pub fn write_wav_to(mut writer: impl Write, sample_rate: u32, sample_format: i32, data: &[u8]) -> std::io::Result<()> {
+    let num_channels: u16 = 1;
+    let bits_per_sample: u16 = match sample_format {
+        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16 => 16,
+        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_U8 => 8,
+        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32 => 32,
+        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_I8 => 8,
+        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_U16 => 16,
+        _ => 16,
+    };
+    let byte_rate: u32 = sample_rate * num_channels as u32 * (bits_per_sample as u32 / 8);
+    let block_align: u16 = num_channels * (bits_per_sample / 8);
+    let data_len = data.len() as u32;
+    let riff_chunk_size = 36 + data_len;
+
+    // RIFF header
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&riff_chunk_size.to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    // fmt subchunk
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?; // Subchunk1Size for PCM
+    writer.write_all(&1u16.to_le_bytes())?; // AudioFormat PCM
+    writer.write_all(&num_channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&bits_per_sample.to_le_bytes())?;
+
+    // data subchunk
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    writer.write_all(data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// A 16-bit mono WAV written as samples arrive. The header is brought up to date after every
+/// [`WavWriter::append`], so a recording cut short (Ctrl+C, a crash) still opens.
+pub struct WavWriter {
+    file: File,
+    data_len: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, sample_rate: u32) -> std::io::Result<Self> {
+        let mut file = File::create(path)?;
+        write_wav_to(&mut file, sample_rate, ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16, &[])?;
+        Ok(Self { file, data_len: 0 })
+    }
+
+    /// Appends `samples` (-1.0..=1.0, clipped beyond that).
+    pub fn append(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        let data: Vec<u8> = samples.iter().flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes()).collect();
+        self.data_len = self.data_len.checked_add(data.len() as u32).ok_or_else(|| std::io::Error::other("WAV files end at 4 GiB"))?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&data)?;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(36 + self.data_len).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_len.to_le_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writer_keeps_header_valid() {
+        let path = std::env::temp_dir().join(format!("gibberlink-wav-{}.wav", std::process::id()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.append(&[0.0, 0.5]).unwrap();
+        writer.append(&[-1.0, 2.0]).unwrap();
+        let wav = read_wav(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((wav.sample_rate, wav.channels, wav.bits_per_sample), (8000, 1, 16));
+        let samples: Vec<i16> = wav.data.chunks_exact(2).map(|c| i16::from_le_bytes([c[0], c[1]])).collect();
+        assert_eq!(samples, [0, 16383, -32767, 32767]);
+    }
+}
@@ -0,0 +1,64 @@
+package com.gibberlink;
+
+import android.content.Context;
+
+import java.io.IOException;
+
+/**
+ * gibberlink over JNI: encode messages to ggwave audio and decode them back with the same chunking
+ * and integrity checks gibberlink-tx uses, backed by libgibberlink.so (gibberlink-ffi built with
+ * the {@code android} feature).
+ *
+ * <p>Either bring your own audio (samples are mono floats, -1.0..1.0, at any rate; {@link #encode}
+ * and {@link #push}) or let the library capture and play through Oboe ({@link #listen} and
+ * {@link #transmit}, after {@link #init}). Protocols are "audible|ultrasound|dt|mt" plus
+ * ":normal|fast|fastest"; null means "audible:fast" and any other name is rejected. Volume is 0-100.
+ */
+public final class Gibberlink {
+    static {
+        System.loadLibrary("gibberlink");
+    }
+
+    private Gibberlink() {}
+
+    /** Receives messages from {@link #push} or {@link #listen}. */
+    public interface MessageListener {
+        /** A whole message, once all of its chunks have been heard. */
+        void onMessage(byte[] message);
+
+        /** Why a message was dropped (a corrupted chunk, a failed integrity check). */
+        void onError(String reason);
+    }
+
+    /** Hands the native audio layer the application context; call once before transmit or listen. */
+    public static native void init(Context context);
+
+    /** Mono samples for {@code payload} at {@code sampleRate}, chunked if it is too long for one transmission. */
+    public static native float[] encode(byte[] payload, String protocol, int volume, int sampleRate);
+
+    /** A receiver for mono samples at {@code sampleRate}; free it with {@link #freeReceiver}. */
+    public static native long newReceiver(int sampleRate);
+
+    /**
+     * Feeds captured samples and calls {@code listener} on this thread for every message they
+     * complete. Safe from several threads at once; their pushes take turns.
+     */
+    public static native void push(long receiver, float[] samples, MessageListener listener);
+
+    public static native void freeReceiver(long receiver);
+
+    /**
+     * Plays {@code payload} on the output device called {@code device} (null for the default one)
+     * and returns once it has been heard. Blocks; keep it off the main thread.
+     */
+    public static native void transmit(byte[] payload, String protocol, int volume, String device) throws IOException;
+
+    /**
+     * Captures the input device called {@code device} (null for the default one; needs the
+     * RECORD_AUDIO permission) and calls {@code listener} from a capture thread with every message
+     * heard. Returns a handle for {@link #stopListening}.
+     */
+    public static native long listen(String device, MessageListener listener) throws IOException;
+
+    public static native void stopListening(long handle);
+}
@@ -0,0 +1,24 @@
+#!/bin/sh
+# Builds target/Gibberlink.xcframework (device and simulator slices of libgibberlink.a, plus the
+# header and module map) for an iOS app. Needs macOS with Xcode and rustup.
+set -e
+cd "$(dirname "$0")"
+
+for target in aarch64-apple-ios aarch64-apple-ios-sim x86_64-apple-ios; do
+    rustup target add "$target"
+    cargo build --release --target "$target" "$@"
+done
+
+# One simulator library for both architectures
+mkdir -p target/ios-simulator
+lipo -create \
+    target/aarch64-apple-ios-sim/release/libgibberlink.a \
+    target/x86_64-apple-ios/release/libgibberlink.a \
+    -output target/ios-simulator/libgibberlink.a
+
+rm -rf target/Gibberlink.xcframework
+xcodebuild -create-xcframework \
+    -library target/aarch64-apple-ios/release/libgibberlink.a -headers include \
+    -library target/ios-simulator/libgibberlink.a -headers include \
+    -output target/Gibberlink.xcframework
+echo "Built target/Gibberlink.xcframework"
@@ -0,0 +1,55 @@
+/*
+ * gibberlink: encode messages to ggwave audio and decode them back, in-process.
+ *
+ * Samples are mono 32-bit floats (-1.0..1.0) at the caller's sample rate, as an AVAudioEngine
+ * tap or a Core Audio render callback delivers them; the app does its own audio I/O. Messages
+ * longer than one transmission are chunked on the way out and reassembled on the way in, the same
+ * framing gibberlink-tx uses, so apps and desktop instances understand each other.
+ *
+ * Functions returning int return -1 on failure; gibberlink_last_error() says why.
+ */
+#ifndef GIBBERLINK_H
+#define GIBBERLINK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct GibberlinkReceiver GibberlinkReceiver;
+
+/* Called with each message a receiver completes; `message` is only valid during the call. */
+typedef void (*GibberlinkMessageCallback)(const uint8_t *message, size_t len, void *context);
+
+/* Why the last failing call on this thread failed; valid until the next one fails. */
+const char *gibberlink_last_error(void);
+
+/* Encodes `len` bytes at `payload` with `protocol` ("audible|ultrasound|dt|mt" plus
+ * ":normal|fast|fastest"; NULL for "audible:fast") at `volume` (0-100), as samples at
+ * `sample_rate`. On success stores them in `*samples` and their number in `*count` and returns 0;
+ * free them with gibberlink_samples_free(). An unknown protocol name fails. */
+int gibberlink_encode(const uint8_t *payload, size_t len, const char *protocol, int volume,
+                      uint32_t sample_rate, float **samples, size_t *count);
+
+/* Frees samples returned by gibberlink_encode(). */
+void gibberlink_samples_free(float *samples, size_t count);
+
+/* A receiver for samples at `sample_rate`, or NULL if the rate is unsupported. */
+GibberlinkReceiver *gibberlink_receiver_new(uint32_t sample_rate);
+
+/* Feeds `count` captured samples and calls `on_message` with `context` for every message they
+ * complete. Returns how many were delivered, or -1 if one was dropped for failing its checks. A
+ * receiver may be fed from several threads; their pushes take turns. */
+int gibberlink_receiver_push(GibberlinkReceiver *receiver, const float *samples, size_t count,
+                             GibberlinkMessageCallback on_message, void *context);
+
+/* Frees a receiver; NULL is ignored. */
+void gibberlink_receiver_free(GibberlinkReceiver *receiver);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,215 @@
+//! JNI entry points for Android (the `android` feature), the native half of
+//! `android/com/gibberlink/Gibberlink.java`. Besides encode and decode over samples the app
+//! captures itself, an app can let the library do its audio: [`listen`] and [`transmit`] go through
+//! `gibberlink_core::audio`, which cpal backs with Oboe (AAudio, or OpenSL ES on older devices).
+//! Failures are thrown as Java exceptions.
+
+use gibberlink_core::audio::{self, Listening};
+use gibberlink_core::message::{self, Receiver};
+use jni::objects::{JByteArray, JClass, JFloatArray, JObject, JString, JValue};
+use jni::sys::{jfloatArray, jint, jlong};
+use jni::JNIEnv;
+
+// Throws `class` with `message`, unless a failed JNI call already left an exception pending
+fn throw(env: &mut JNIEnv, class: &str, message: impl AsRef<str>) {
+    if !env.exception_check().unwrap_or(true) {
+        let _ = env.throw_new(class, message);
+    }
+}
+
+fn string_arg(env: &mut JNIEnv, value: &JString) -> Result<Option<String>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    env.get_string(value).map(|s| Some(s.into())).map_err(|e| e.to_string())
+}
+
+// Calls `listener.onMessage(byte[])`, or `listener.onError(String)` for a dropped message
+fn deliver(env: &mut JNIEnv, listener: &JObject, message: Result<Vec<u8>, String>) -> jni::errors::Result<()> {
+    match message {
+        Ok(bytes) => {
+            let array = env.byte_array_from_slice(&bytes)?;
+            env.call_method(listener, "onMessage", "([B)V", &[JValue::Object(&array)])?;
+        }
+        Err(e) => {
+            let reason = env.new_string(e)?;
+            env.call_method(listener, "onError", "(Ljava/lang/String;)V", &[JValue::Object(&reason)])?;
+        }
+    }
+    Ok(())
+}
+
+/// `static native void init(Context context)`: hands cpal the application context it needs to
+/// open audio streams. Call once before `transmit` or `listen`.
+#[no_mangle]
+pub extern "system" fn Java_com_gibberlink_Gibberlink_init(mut env: JNIEnv, _class: JClass, context: JObject) {
+    #[cfg(target_os = "android")]
+    {
+        static CONTEXT: std::sync::OnceLock<jni::objects::GlobalRef> = std::sync::OnceLock::new();
+        let vm = match env.get_java_vm() {
+            Ok(vm) => vm,
+            Err(e) => return throw(&mut env, "java/lang/IllegalStateException", e.to_string()),
+        };
+        let context = match env.new_global_ref(context) {
+            Ok(context) => context,
+            Err(e) => return throw(&mut env, "java/lang/IllegalStateException", e.to_string()),
+        };
+        // The context is kept for the life of the process, as ndk-context expects
+        if CONTEXT.set(context).is_ok() {
+            let context = CONTEXT.get().map_or(std::ptr::null_mut(), |c| c.as_obj().as_raw());
+            unsafe { ndk_context::initialize_android_context(vm.get_java_vm_pointer().cast(), context.cast()) };
+        }
+    }
+    #[cfg(not(target_os = "android"))]
+    let _ = (&mut env, context);
+}
+
+/// `static native float[] encode(byte[] payload, String protocol, int volume, int sampleRate)`:
+/// mono samples (-1.0..1.0) for `payload`, chunked if it is too long for one transmission.
+/// `protocol` may be null for "audible:fast".
+#[no_mangle]
+pub extern "system" fn Java_com_gibberlink_Gibberlink_encode(
+    mut env: JNIEnv,
+    _class: JClass,
+    payload: JByteArray,
+    protocol: JString,
+    volume: jint,
+    sample_rate: jint,
+) -> jfloatArray {
+    let encoded = (|| {
+        let payload = env.convert_byte_array(&payload).map_err(|e| e.to_string())?;
+        let protocol = string_arg(&mut env, &protocol)?.unwrap_or_else(|| "audible:fast".into());
+        let rate = u32::try_from(sample_rate).map_err(|_| format!("invalid sample rate {}", sample_rate))?;
+        let protocol = gibberlink_core::parse_protocol(&protocol).map_err(|e| e.to_string())?;
+        let samples = message::encode(&payload, protocol, volume.clamp(0, 100), rate)?;
+        let array = env.new_float_array(samples.len() as jint).map_err(|e| e.to_string())?;
+        env.set_float_array_region(&array, 0, &samples).map_err(|e| e.to_string())?;
+        Ok::<_, String>(array)
+    })();
+    match encoded {
+        Ok(array) => array.into_raw(),
+        Err(e) => {
+            throw(&mut env, "java/lang/IllegalArgumentException", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// `static native long newReceiver(int sampleRate)`: a receiver handle for mono samples at
+/// `sampleRate`; free it with `freeReceiver`.
+#[no_mangle]
+pub extern "system" fn Java_com_gibberlink_Gibberlink_newReceiver(mut env: JNIEnv, _class: JClass, sample_rate: jint) -> jlong {
+    match u32::try_from(sample_rate).map_err(|_| format!("invalid sample rate {}", sample_rate)).and_then(|rate| Receiver::new(rate).map_err(|e| e.to_string())) {
+        Ok(receiver) => Box::into_raw(Box::new(receiver)) as jlong,
+        Err(e) => {
+            throw(&mut env, "java/lang/IllegalArgumentException", e);
+            0
+        }
+    }
+}
+
+/// `static native void push(long receiver, float[] samples, MessageListener listener)`: feeds
+/// captured samples and calls `listener` for every message they complete, on the calling thread.
+///
+/// # Safety
+///
+/// Called from Java only, with a handle from `newReceiver` that is not freed meanwhile. Pushes
+/// from several threads are safe; they take turns.
+#[no_mangle]
+pub unsafe extern "system" fn Java_com_gibberlink_Gibberlink_push(mut env: JNIEnv, _class: JClass, receiver: jlong, samples: JFloatArray, listener: JObject) {
+    let Some(receiver) = (receiver as *const Receiver).as_ref() else {
+        return throw(&mut env, "java/lang/IllegalStateException", "receiver has been freed");
+    };
+    let buffer = env.get_array_length(&samples).and_then(|len| {
+        let mut buffer = vec![0.0; len as usize];
+        env.get_float_array_region(&samples, 0, &mut buffer).map(|_| buffer)
+    });
+    let Ok(buffer) = buffer else {
+        return throw(&mut env, "java/lang/IllegalArgumentException", "unreadable samples");
+    };
+    for message in receiver.push(&buffer) {
+        // An exception thrown by the listener is left for the caller
+        if deliver(&mut env, &listener, message).is_err() {
+            return;
+        }
+    }
+}
+
+/// `static native void freeReceiver(long receiver)`; 0 is ignored.
+///
+/// # Safety
+///
+/// Called from Java only, with a handle from `newReceiver` that is not freed before.
+#[no_mangle]
+pub unsafe extern "system" fn Java_com_gibberlink_Gibberlink_freeReceiver(_env: JNIEnv, _class: JClass, receiver: jlong) {
+    if receiver != 0 {
+        drop(Box::from_raw(receiver as *mut Receiver));
+    }
+}
+
+/// `static native void transmit(byte[] payload, String protocol, int volume, String device) throws
+/// IOException`: plays `payload` on the output device called `device` (null for the default one)
+/// and returns once it has been heard. Blocks, so keep it off the main thread.
+#[no_mangle]
+pub extern "system" fn Java_com_gibberlink_Gibberlink_transmit(
+    mut env: JNIEnv,
+    _class: JClass,
+    payload: JByteArray,
+    protocol: JString,
+    volume: jint,
+    device: JString,
+) {
+    let sent = (|| {
+        let payload = env.convert_byte_array(&payload).map_err(|e| e.to_string())?;
+        let protocol = string_arg(&mut env, &protocol)?.unwrap_or_else(|| "audible:fast".into());
+        let device = string_arg(&mut env, &device)?;
+        let protocol = gibberlink_core::parse_protocol(&protocol).map_err(|e| e.to_string())?;
+        audio::transmit(&payload, protocol, volume.clamp(0, 100), device.as_deref())
+    })();
+    if let Err(e) = sent {
+        throw(&mut env, "java/io/IOException", e);
+    }
+}
+
+/// `static native long listen(String device, MessageListener listener) throws IOException`:
+/// captures the input device called `device` (null for the default one, which needs the
+/// RECORD_AUDIO permission) and calls `listener` from the capture thread with every message heard.
+/// Returns a handle for `stopListening`.
+#[no_mangle]
+pub extern "system" fn Java_com_gibberlink_Gibberlink_listen(mut env: JNIEnv, _class: JClass, device: JString, listener: JObject) -> jlong {
+    let started = (|| {
+        let device = string_arg(&mut env, &device)?;
+        let vm = env.get_java_vm().map_err(|e| e.to_string())?;
+        let listener = env.new_global_ref(listener).map_err(|e| e.to_string())?;
+        audio::listen(device.as_deref(), move |message| {
+            // Attached once; later calls on the capture thread reuse the attachment
+            let Ok(mut env) = vm.attach_current_thread_as_daemon() else {
+                return;
+            };
+            // An exception from the listener must not stay pending on a thread Java never returns to
+            if deliver(&mut env, listener.as_obj(), message).is_err() && env.exception_check().unwrap_or(false) {
+                let _ = env.exception_describe();
+                let _ = env.exception_clear();
+            }
+        })
+    })();
+    match started {
+        Ok(listening) => Box::into_raw(Box::new(listening)) as jlong,
+        Err(e) => {
+            throw(&mut env, "java/io/IOException", e);
+            0
+        }
+    }
+}
+
+/// `static native void stopListening(long handle)`: stops capture; 0 is ignored.
+///
+/// # Safety
+///
+/// Called from Java only, with a handle from `listen` that is not stopped before.
+#[no_mangle]
+pub unsafe extern "system" fn Java_com_gibberlink_Gibberlink_stopListening(_env: JNIEnv, _class: JClass, handle: jlong) {
+    if handle != 0 {
+        let _ = Box::from_raw(handle as *mut Listening).stop();
+    }
+}
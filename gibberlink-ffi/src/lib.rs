@@ -0,0 +1,183 @@
+//! C ABI over `gibberlink-core` for apps that can't spawn the CLI (iOS above all): encode a
+//! message to samples, and decode captured samples back into messages. The app keeps its own audio
+//! I/O; samples are mono 32-bit floats at whatever rate it runs at, the format an `AVAudioEngine`
+//! tap or a Core Audio render callback works in. Declared for C and Swift in
+//! `include/gibberlink.h`.
+
+#[cfg(feature = "android")]
+mod android;
+
+use gibberlink_core::message::{self, Receiver};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+thread_local! {
+    // Why the last call on this thread failed, for gibberlink_last_error()
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn fail(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Why the last failing call on this thread failed. The string stays valid until the next failing
+/// call on the same thread.
+#[no_mangle]
+pub extern "C" fn gibberlink_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Encodes `len` bytes at `payload` with `protocol` (e.g. "audible:fast"; NULL for that default)
+/// at `volume` (0-100), as mono samples at `sample_rate`. On success stores the samples in
+/// `*samples` and their number in `*count` and returns 0; free them with gibberlink_samples_free().
+///
+/// # Safety
+///
+/// `payload` must point to `len` readable bytes, `protocol` must be NULL or a NUL-terminated
+/// string, and `samples` and `count` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn gibberlink_encode(
+    payload: *const u8,
+    len: usize,
+    protocol: *const c_char,
+    volume: c_int,
+    sample_rate: u32,
+    samples: *mut *mut f32,
+    count: *mut usize,
+) -> c_int {
+    if (payload.is_null() && len > 0) || samples.is_null() || count.is_null() {
+        fail("null argument");
+        return -1;
+    }
+    let payload = if len == 0 { &[][..] } else { std::slice::from_raw_parts(payload, len) };
+    let protocol = if protocol.is_null() { "audible:fast".into() } else { CStr::from_ptr(protocol).to_string_lossy() };
+    let encoded = gibberlink_core::parse_protocol(&protocol).map_err(|e| e.to_string()).and_then(|protocol| message::encode(payload, protocol, volume.clamp(0, 100), sample_rate));
+    match encoded {
+        Ok(encoded) => {
+            let encoded = encoded.into_boxed_slice();
+            *count = encoded.len();
+            *samples = Box::into_raw(encoded) as *mut f32;
+            0
+        }
+        Err(e) => {
+            fail(e);
+            -1
+        }
+    }
+}
+
+/// Frees samples returned by gibberlink_encode().
+///
+/// # Safety
+///
+/// `samples` and `count` must be exactly what gibberlink_encode() stored, and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn gibberlink_samples_free(samples: *mut f32, count: usize) {
+    if !samples.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(samples, count)));
+    }
+}
+
+/// Called with each message a receiver completes; `message` is only valid during the call.
+pub type GibberlinkMessageCallback = extern "C" fn(message: *const u8, len: usize, context: *mut c_void);
+
+/// A receiver for mono samples at `sample_rate`, or NULL if ggwave rejects the rate. Free it with
+/// gibberlink_receiver_free().
+#[no_mangle]
+pub extern "C" fn gibberlink_receiver_new(sample_rate: u32) -> *mut Receiver {
+    match Receiver::new(sample_rate) {
+        Ok(receiver) => Box::into_raw(Box::new(receiver)),
+        Err(e) => {
+            fail(e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Feeds `count` captured samples to `receiver` and calls `on_message` (with `context`) for every
+/// message they complete. Returns how many messages were delivered, or -1 if one was dropped for
+/// failing its checks (any others are still delivered).
+///
+/// # Safety
+///
+/// `receiver` must come from gibberlink_receiver_new() and not be freed meanwhile; pushes from
+/// several threads are safe and take turns. `samples` must point to `count` readable floats.
+#[no_mangle]
+pub unsafe extern "C" fn gibberlink_receiver_push(
+    receiver: *mut Receiver,
+    samples: *const f32,
+    count: usize,
+    on_message: GibberlinkMessageCallback,
+    context: *mut c_void,
+) -> c_int {
+    let Some(receiver) = receiver.as_ref() else {
+        fail("null receiver");
+        return -1;
+    };
+    let samples = if count == 0 || samples.is_null() { &[][..] } else { std::slice::from_raw_parts(samples, count) };
+    let mut delivered = 0;
+    let mut dropped = false;
+    for message in receiver.push(samples) {
+        match message {
+            Ok(message) => {
+                on_message(message.as_ptr(), message.len(), context);
+                delivered += 1;
+            }
+            Err(e) => {
+                fail(e);
+                dropped = true;
+            }
+        }
+    }
+    if dropped { -1 } else { delivered }
+}
+
+/// Frees a receiver; NULL is ignored.
+///
+/// # Safety
+///
+/// `receiver` must come from gibberlink_receiver_new() and not be freed before.
+#[no_mangle]
+pub unsafe extern "C" fn gibberlink_receiver_free(receiver: *mut Receiver) {
+    if !receiver.is_null() {
+        drop(Box::from_raw(receiver));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn collect(message: *const u8, len: usize, context: *mut c_void) {
+        let heard = unsafe { &mut *(context as *mut Vec<Vec<u8>>) };
+        heard.push(unsafe { std::slice::from_raw_parts(message, len) }.to_vec());
+    }
+
+    #[test]
+    fn messages_round_trip_through_the_c_abi() {
+        let payload = b"hello from the other side".repeat(8);
+        let (mut samples, mut count) = (std::ptr::null_mut(), 0);
+        let protocol = CString::new("audible:fastest").unwrap();
+        assert_eq!(unsafe { gibberlink_encode(payload.as_ptr(), payload.len(), protocol.as_ptr(), 50, 44100, &mut samples, &mut count) }, 0);
+
+        let receiver = gibberlink_receiver_new(44100);
+        let mut heard: Vec<Vec<u8>> = Vec::new();
+        let context = &mut heard as *mut _ as *mut c_void;
+        let buffers = unsafe { std::slice::from_raw_parts(samples, count) }.chunks(512);
+        let delivered: c_int = buffers.map(|buffer| unsafe { gibberlink_receiver_push(receiver, buffer.as_ptr(), buffer.len(), collect, context) }).sum();
+        unsafe {
+            gibberlink_receiver_free(receiver);
+            gibberlink_samples_free(samples, count);
+        }
+        assert_eq!(delivered, 1);
+        assert_eq!(heard, [payload]);
+    }
+
+    #[test]
+    fn failures_are_explained() {
+        assert_eq!(unsafe { gibberlink_encode(std::ptr::null(), 3, std::ptr::null(), 50, 48000, std::ptr::null_mut(), std::ptr::null_mut()) }, -1);
+        let error = unsafe { CStr::from_ptr(gibberlink_last_error()) };
+        assert_eq!(error.to_str(), Ok("null argument"));
+    }
+}
@@ -0,0 +1,49 @@
+/// <reference types="node" />
+import { EventEmitter } from 'events'
+
+export interface EncodeOptions {
+  /** "audible|ultrasound|dt|mt" plus ":normal|fast|fastest" (default "audible:fast"); other names are errors */
+  protocol?: string
+  /** 0-100 (default 50) */
+  volume?: number
+  /** Samples per second of the encoded audio (default 48000; transmit() uses the device's own) */
+  sampleRate?: number
+  /** Output device for transmit(); a distinctive part of its name is enough */
+  device?: string
+}
+
+/** Mono samples (-1.0..1.0) for `payload`, chunked if it is too long for one transmission. */
+export function encode(payload: Buffer, options?: EncodeOptions): Float32Array
+
+/** Every message in a whole recording of mono samples; messages that fail their checks are left out. */
+export function decode(samples: Float32Array, sampleRate: number): Buffer[]
+
+/** Plays `payload` on the output device; resolves once it has been heard. */
+export function transmit(payload: Buffer, options?: EncodeOptions): Promise<void>
+
+/** Decodes samples the app captures itself. */
+export class Decoder extends EventEmitter {
+  constructor(sampleRate: number)
+  /** Emits 'message' and 'dropped' for the messages these samples complete, before returning. */
+  push(samples: Float32Array): void
+  on(event: 'message', listener: (message: Buffer) => void): this
+  on(event: 'dropped', listener: (err: Error) => void): this
+}
+
+export interface ListenOptions {
+  /** Input device; a distinctive part of its name is enough (default: the system default) */
+  device?: string
+}
+
+/** Live capture from the microphone; keeps the process alive until stopped. */
+export class Receiver extends EventEmitter {
+  constructor(options?: ListenOptions)
+  /** The device being captured, its rate and channel count. */
+  readonly description: string
+  stop(): void
+  on(event: 'message', listener: (message: Buffer) => void): this
+  on(event: 'dropped', listener: (err: Error) => void): this
+}
+
+/** Starts capturing; the same as `new Receiver(options)`. */
+export function listen(options?: ListenOptions): Receiver
@@ -0,0 +1,53 @@
+'use strict'
+
+// EventEmitter wrappers over the native addon (src/lib.rs), built to gibberlink.node by `npm run build`
+const { EventEmitter } = require('events')
+const native = require('./gibberlink.node')
+
+// Emits 'message' (a Buffer) for every message in the samples pushed to it, and 'dropped' (an
+// Error) for one that failed its checks
+class Decoder extends EventEmitter {
+  constructor (sampleRate) {
+    super()
+    this._native = new native.Decoder(sampleRate)
+  }
+
+  push (samples) {
+    this._native.push(samples, (err, message) => {
+      if (err) this.emit('dropped', err)
+      else this.emit('message', message)
+    })
+  }
+}
+
+// Live capture: emits 'message' and 'dropped' like a Decoder until stop() is called
+class Receiver extends EventEmitter {
+  constructor (options = {}) {
+    super()
+    this._listener = native.listen(options.device ?? null, (err, message) => {
+      if (err) this.emit('dropped', err)
+      else this.emit('message', message)
+    })
+  }
+
+  get description () {
+    return this._listener.description
+  }
+
+  stop () {
+    this._listener.stop()
+  }
+}
+
+function listen (options) {
+  return new Receiver(options)
+}
+
+module.exports = {
+  encode: native.encode,
+  decode: native.decode,
+  transmit: native.transmit,
+  Decoder,
+  Receiver,
+  listen
+}
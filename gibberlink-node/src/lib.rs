@@ -0,0 +1,148 @@
+//! Node.js bindings over `gibberlink-core` (napi-rs), so Electron apps and Node services can embed
+//! gibberlink instead of spawning the CLI. This is the native half; `index.js` wraps [`Decoder`]
+//! and [`listen`] in EventEmitters and `index.d.ts` types the whole package.
+
+use gibberlink_core::audio::{self, Listening};
+use gibberlink_core::message::{self, Receiver};
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsFunction, JsUnknown};
+use napi_derive::napi;
+
+/// How to encode or play a message; every field is optional.
+#[napi(object)]
+pub struct EncodeOptions {
+    /// "audible|ultrasound|dt|mt" plus ":normal|fast|fastest" (default "audible:fast"); other names are errors
+    pub protocol: Option<String>,
+    /// 0-100 (default 50)
+    pub volume: Option<i32>,
+    /// Samples per second of the encoded audio (default 48000; `transmit` uses the device's own)
+    pub sample_rate: Option<u32>,
+    /// Output device for `transmit`; a distinctive part of its name is enough (default: the system default)
+    pub device: Option<String>,
+}
+
+// The protocol id, or why its name was rejected, and the volume
+fn protocol_and_volume(options: &Option<EncodeOptions>) -> (std::result::Result<i32, String>, i32) {
+    let protocol = options.as_ref().and_then(|o| o.protocol.as_deref()).unwrap_or("audible:fast");
+    let volume = options.as_ref().and_then(|o| o.volume).unwrap_or(50).clamp(0, 100);
+    (gibberlink_core::parse_protocol(protocol).map_err(|e| e.to_string()), volume)
+}
+
+/// Encodes `payload` to mono samples (-1.0..1.0), chunked if it is too long for one transmission.
+#[napi]
+pub fn encode(payload: Buffer, options: Option<EncodeOptions>) -> Result<Float32Array> {
+    let (protocol, volume) = protocol_and_volume(&options);
+    let protocol = protocol.map_err(Error::from_reason)?;
+    let rate = options.as_ref().and_then(|o| o.sample_rate).unwrap_or(48000);
+    message::encode(&payload, protocol, volume, rate).map(Float32Array::new).map_err(Error::from_reason)
+}
+
+/// Every message in a whole recording of mono samples at `sample_rate`. Messages that fail their
+/// checks are left out; a [`Decoder`] reports those too.
+#[napi]
+pub fn decode(samples: Float32Array, sample_rate: u32) -> Result<Vec<Buffer>> {
+    let receiver = Receiver::new(sample_rate).map_err(|e| Error::from_reason(e.to_string()))?;
+    Ok(receiver.push(&samples).into_iter().filter_map(|message| message.ok().map(Buffer::from)).collect())
+}
+
+// Calls a Node-style `callback(err, message)`
+fn deliver(env: &Env, callback: &JsFunction, message: std::result::Result<Vec<u8>, String>) -> Result<()> {
+    let args: [JsUnknown; 2] = match message {
+        Ok(bytes) => [env.get_null()?.into_unknown(), env.create_buffer_with_data(bytes)?.into_raw().into_unknown()],
+        Err(e) => [env.create_error(Error::from_reason(e))?.into_unknown(), env.get_undefined()?.into_unknown()],
+    };
+    callback.call(None, &args).map(|_| ())
+}
+
+/// Streaming decoder for samples the app captures itself.
+#[napi]
+pub struct Decoder {
+    receiver: Receiver,
+}
+
+#[napi]
+impl Decoder {
+    /// A decoder for mono samples at `sample_rate`.
+    #[napi(constructor)]
+    pub fn new(sample_rate: u32) -> Result<Decoder> {
+        let receiver = Receiver::new(sample_rate).map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(Decoder { receiver })
+    }
+
+    /// Feeds the next samples and calls `callback(err, message)` for every message they complete
+    /// (`err` when one was dropped), before returning.
+    #[napi]
+    pub fn push(&self, env: Env, samples: Float32Array, callback: JsFunction) -> Result<()> {
+        for message in self.receiver.push(&samples) {
+            deliver(&env, &callback, message)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Transmit {
+    payload: Vec<u8>,
+    protocol: std::result::Result<i32, String>,
+    volume: i32,
+    device: Option<String>,
+}
+
+impl Task for Transmit {
+    type Output = ();
+    type JsValue = ();
+
+    fn compute(&mut self) -> Result<()> {
+        // An unknown protocol rejects the promise rather than throwing
+        let protocol = self.protocol.clone().map_err(Error::from_reason)?;
+        audio::transmit(&self.payload, protocol, self.volume, self.device.as_deref()).map_err(Error::from_reason)
+    }
+
+    fn resolve(&mut self, _env: Env, _output: ()) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Plays `payload` on the output device (on a worker thread); resolves once it has been heard.
+#[napi(ts_return_type = "Promise<void>")]
+pub fn transmit(payload: Buffer, options: Option<EncodeOptions>) -> AsyncTask<Transmit> {
+    let (protocol, volume) = protocol_and_volume(&options);
+    let device = options.and_then(|o| o.device);
+    AsyncTask::new(Transmit { payload: payload.to_vec(), protocol, volume, device })
+}
+
+/// A running capture from [`listen`]; it keeps the process alive until stopped.
+#[napi]
+pub struct Listener {
+    listening: Option<Listening>,
+    description: String,
+}
+
+#[napi]
+impl Listener {
+    /// The device being captured, its rate and channel count.
+    #[napi(getter)]
+    pub fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    /// Stops capture; later calls do nothing.
+    #[napi]
+    pub fn stop(&mut self) -> Result<()> {
+        self.listening.take().map_or(Ok(()), |listening| listening.stop().map_err(Error::from_reason))
+    }
+}
+
+/// Captures the input device called `device` (or the default one) and calls `callback(err,
+/// message)` on the JavaScript thread with every message heard, or why one was dropped.
+#[napi(ts_args_type = "device: string | null | undefined, callback: (err: Error | null, message?: Buffer) => void")]
+pub fn listen(device: Option<String>, callback: JsFunction) -> Result<Listener> {
+    let callback: ThreadsafeFunction<Vec<u8>, ErrorStrategy::CalleeHandled> =
+        callback.create_threadsafe_function(0, |ctx: ThreadSafeCallContext<Vec<u8>>| Ok(vec![ctx.env.create_buffer_with_data(ctx.value)?.into_raw()]))?;
+    let listening = audio::listen(device.as_deref(), move |message| {
+        callback.call(message.map_err(Error::from_reason), ThreadsafeFunctionCallMode::NonBlocking);
+    })
+    .map_err(Error::from_reason)?;
+    let description = listening.description().to_owned();
+    Ok(Listener { listening: Some(listening), description })
+}
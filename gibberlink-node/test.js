@@ -0,0 +1,21 @@
+'use strict'
+
+// Round trips through the built addon: `npm run build:debug && npm test`
+const assert = require('assert')
+const gibberlink = require('./index.js')
+
+const payload = Buffer.from('hello from node, long enough to need more than one chunk. '.repeat(4))
+const samples = gibberlink.encode(payload, { protocol: 'audible:fastest', sampleRate: 48000 })
+assert.ok(samples instanceof Float32Array && samples.length > 0)
+assert.deepStrictEqual(gibberlink.decode(samples, 48000), [payload])
+
+// Streaming, in buffers the size a capture callback delivers
+const decoder = new gibberlink.Decoder(48000)
+const heard = []
+decoder.on('message', (message) => heard.push(message))
+for (let i = 0; i < samples.length; i += 480) decoder.push(samples.subarray(i, i + 480))
+assert.deepStrictEqual(heard, [payload])
+
+assert.throws(() => new gibberlink.Decoder(0))
+assert.throws(() => gibberlink.encode(payload, { protocol: 'audibel' }), /unknown protocol/)
+console.log('ok')
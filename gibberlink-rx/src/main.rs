@@ -0,0 +1,149 @@
+// Receive-only counterpart to gibberlink-tx for headless receivers (a Raspberry Pi, a container):
+// decodes the microphone and writes each message to stdout and/or POSTs it to a webhook. Nothing
+// here encodes or plays, so the binary carries only ggwave, capture and the message receiver.
+
+use base64::Engine;
+use clap::Parser;
+use gibberlink_core::audio;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Receive gibberlink messages from the microphone and print them or POST them to a webhook")]
+struct Args {
+    /// Capture from this input device (a distinctive part of its name is enough) [default: the system default]
+    #[arg(long, value_name = "NAME")]
+    input_device: Option<String>,
+
+    /// Print each message as a JSON line, `{"type":"decoded","kind":"text|json|binary","text":...,"base64":...}`, as `gibberlink-tx --listen --json` does
+    #[arg(long)]
+    json: bool,
+
+    /// Also POST each message's JSON line to this URL (plain http://)
+    #[arg(long, value_name = "URL")]
+    webhook: Option<String>,
+
+    /// Don't print messages, only send them to --webhook
+    #[arg(long, requires = "webhook")]
+    quiet: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    let webhook = match args.webhook.as_deref().map(Webhook::parse).transpose() {
+        Ok(webhook) => webhook,
+        Err(e) => {
+            eprintln!("Invalid --webhook: {}", e);
+            std::process::exit(1);
+        }
+    };
+    gibberlink_core::disable_logging();
+    let (json, quiet) = (args.json, args.quiet);
+    let started = audio::listen(args.input_device.as_deref(), move |message| {
+        let bytes = match message {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                return;
+            }
+        };
+        let event = decoded_event(&bytes);
+        if !quiet {
+            if json {
+                println!("{}", event);
+            } else {
+                match std::str::from_utf8(&bytes) {
+                    Ok(text) => println!("{}", text),
+                    Err(_) => println!("0x{}", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+                }
+            }
+        }
+        if let Some(webhook) = &webhook {
+            if let Err(e) = webhook.post(&event.to_string()) {
+                eprintln!("Webhook failed: {}", e);
+            }
+        }
+    });
+    let result = started.and_then(|listening| {
+        eprintln!("Listening on {}; Ctrl+C to stop", listening.description());
+        listening.wait()
+    });
+    if let Err(e) = result {
+        eprintln!("Listen failed: {}", e);
+        std::process::exit(6);
+    }
+}
+
+// The same object as gibberlink-tx's `decoded` event
+fn decoded_event(bytes: &[u8]) -> serde_json::Value {
+    let text = std::str::from_utf8(bytes).ok();
+    let kind = match text {
+        Some(s) if s.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) => "binary",
+        Some(s) if matches!(s.trim_start().chars().next(), Some('{' | '[')) && serde_json::from_str::<serde_json::Value>(s).is_ok() => "json",
+        Some(_) => "text",
+        None => "binary",
+    };
+    serde_json::json!({
+        "type": "decoded",
+        "kind": kind,
+        "text": text,
+        "base64": base64::engine::general_purpose::STANDARD.encode(bytes),
+    })
+}
+
+// --webhook, split up front so a bad URL fails at startup rather than on the first message
+struct Webhook {
+    host: String,
+    addr: String,
+    path: String,
+}
+
+impl Webhook {
+    fn parse(url: &str) -> Result<Webhook, String> {
+        let rest = url.strip_prefix("http://").ok_or("only http:// URLs are supported")?;
+        let (host, path) = rest.split_once('/').map_or((rest, "/".to_owned()), |(host, path)| (host, format!("/{}", path)));
+        if host.is_empty() {
+            return Err("missing host".into());
+        }
+        // Bracketed IPv6 hosts keep their colons
+        let addr = if host.rsplit_once(':').is_some_and(|(_, port)| !port.contains(']')) { host.to_owned() } else { format!("{}:80", host) };
+        Ok(Webhook { host: host.to_owned(), addr, path })
+    }
+
+    fn post(&self, body: &str) -> Result<(), String> {
+        let mut stream = TcpStream::connect(&self.addr).map_err(|e| format!("connect {}: {}", self.addr, e))?;
+        stream.set_read_timeout(Some(Duration::from_secs(10))).map_err(|e| e.to_string())?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        )
+        .map_err(|e| format!("send: {}", e))?;
+        let mut status = [0; 12];
+        stream.read_exact(&mut status).map_err(|e| format!("response: {}", e))?;
+        match std::str::from_utf8(&status[9..]) {
+            Ok(code) if code.starts_with('2') => Ok(()),
+            Ok(code) => Err(format!("HTTP {}", code)),
+            Err(_) => Err("malformed response".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhook_urls() {
+        let hook = Webhook::parse("http://pi.local:8080/hooks/rx").unwrap();
+        assert_eq!((hook.addr.as_str(), hook.path.as_str()), ("pi.local:8080", "/hooks/rx"));
+        let hook = Webhook::parse("http://[::1]").unwrap();
+        assert_eq!((hook.addr.as_str(), hook.path.as_str()), ("[::1]:80", "/"));
+        assert!(Webhook::parse("https://example.com/").is_err());
+        assert!(Webhook::parse("http:///path").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+// LLM agent mode (the `agent` feature): what the microphone hears goes to an OpenAI-compatible
+// chat-completions endpoint as the user's turn, and the model's reply is transmitted back.
+
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// A conversation with one model: the system prompt plus the last few exchanges for context.
+pub struct Agent {
+    client: ureq::Agent,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    system: String,
+    // (heard, replied) pairs, oldest first
+    history: VecDeque<(String, String)>,
+    history_len: usize,
+}
+
+impl Agent {
+    pub fn new(url: &str, model: &str, system: String, api_key: Option<String>, history_len: usize, timeout: Duration) -> Agent {
+        // Error statuses carry the API's explanation in the body, so they are read rather than raised
+        let client = ureq::Agent::config_builder().timeout_global(Some(timeout)).http_status_as_error(false).build().into();
+        Agent { client, url: url.to_owned(), model: model.to_owned(), api_key, system, history: VecDeque::new(), history_len }
+    }
+
+    /// Asks the model to answer `heard`. The exchange joins the history only once it has a reply.
+    pub fn reply(&mut self, heard: &str) -> Result<String, String> {
+        let mut messages = vec![json!({"role": "system", "content": self.system})];
+        for (user, assistant) in &self.history {
+            messages.push(json!({"role": "user", "content": user}));
+            messages.push(json!({"role": "assistant", "content": assistant}));
+        }
+        messages.push(json!({"role": "user", "content": heard}));
+        let mut request = self.client.post(&self.url);
+        if let Some(key) = &self.api_key {
+            request = request.header("Authorization", format!("Bearer {}", key));
+        }
+        let mut response = request.send_json(json!({"model": self.model, "messages": messages})).map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body = response.body_mut().read_to_string().map_err(|e| format!("read response: {}", e))?;
+        let parsed: Result<Value, _> = serde_json::from_str(&body);
+        if !status.is_success() {
+            let reason = parsed.ok().and_then(|v| v["error"]["message"].as_str().map(str::to_owned)).unwrap_or(body);
+            return Err(format!("HTTP {}: {}", status.as_u16(), reason.trim()));
+        }
+        let parsed = parsed.map_err(|e| format!("response is not JSON: {}", e))?;
+        let reply = parsed["choices"][0]["message"]["content"].as_str().ok_or("response has no choices[0].message.content")?.trim().to_owned();
+        self.history.push_back((heard.to_owned(), reply.clone()));
+        while self.history.len() > self.history_len {
+            self.history.pop_front();
+        }
+        Ok(reply)
+    }
+}
+
+/// The longest start of `text` that `fits`, cut after a word when there is one to cut at. `fits`
+/// must hold for every prefix of a prefix that holds, as payload sizes do.
+pub fn truncate(text: &str, fits: impl Fn(&str) -> bool) -> &str {
+    if fits(text) {
+        return text;
+    }
+    let ends: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    // Binary search for the last char boundary that still fits (ends[0] == 0 always does)
+    let (mut lo, mut hi) = (0, ends.len());
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if fits(&text[..ends[mid]]) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    let cut = &text[..ends[lo]];
+    if text[cut.len()..].starts_with(char::is_whitespace) {
+        return cut.trim_end();
+    }
+    match cut.rfind(char::is_whitespace) {
+        Some(space) if space > 0 => cut[..space].trim_end(),
+        _ => cut,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_at_word_boundaries() {
+        let fits = |s: &str| s.len() <= 12;
+        assert_eq!(truncate("short", fits), "short");
+        assert_eq!(truncate("hello there, how are you", fits), "hello there,");
+        assert_eq!(truncate("hello there you", fits), "hello there");
+        assert_eq!(truncate("hello therefore", fits), "hello");
+        assert_eq!(truncate("unbreakablewordhere", fits), "unbreakablew");
+        // Never splits a character
+        assert_eq!(truncate("ééééééééé", fits), "éééééé");
+    }
+}
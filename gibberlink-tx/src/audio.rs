@@ -0,0 +1,618 @@
+// In-process audio I/O through cpal (the `audio` feature), so live use doesn't depend on WAV files
+// or external players.
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use gibberlink_core::wav::WavWriter;
+use gibberlink_core::{ggwave_consts, OperatingMode, ParametersBuilder};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+// --buffer-frames, for every stream this process opens; unset keeps the host's default
+static BUFFER_FRAMES: OnceLock<u32> = OnceLock::new();
+
+/// Asks every capture and playback stream for a device buffer of `frames` frames (clamped to what
+/// the device supports). ALSA's default period on a Raspberry Pi can be short enough for a busy
+/// Pi to under- or overrun.
+pub fn set_buffer_frames(frames: u32) {
+    let _ = BUFFER_FRAMES.set(frames);
+}
+
+// --output-latency-ms: the delay after the device that its driver doesn't report
+static OUTPUT_LATENCY: OnceLock<Duration> = OnceLock::new();
+
+/// Starts scheduled playback ([`play_at`]) `latency` early on top of the latency the host reports,
+/// for what comes after the device: Bluetooth links, USB DACs, external mixers.
+pub fn set_output_latency(latency: Duration) {
+    let _ = OUTPUT_LATENCY.set(latency);
+}
+
+// --record-to, and the recording once the first capture has opened it; later captures append to it
+static RECORD_TO: OnceLock<PathBuf> = OnceLock::new();
+static RECORDING: Mutex<Option<WavWriter>> = Mutex::new(None);
+
+/// Also writes everything captured from now on to a WAV at `path` (16-bit mono at the device
+/// rate), so a session can be decoded again offline with other settings.
+pub fn record_to(path: PathBuf) {
+    let _ = RECORD_TO.set(path);
+}
+
+fn record(sample_rate: u32, samples: &[f32]) -> Result<(), String> {
+    let Some(path) = RECORD_TO.get() else { return Ok(()) };
+    let failed = |e: std::io::Error| format!("record to {}: {}", path.display(), e);
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    if recording.is_none() {
+        *recording = Some(WavWriter::create(path, sample_rate).map_err(failed)?);
+    }
+    recording.as_mut().expect("opened above").append(samples).map_err(failed)
+}
+
+/// How much of the latest capture --dump-failures keeps: enough for a long chunked message.
+pub const RECENT_SECONDS: u32 = 30;
+static KEEP_RECENT: AtomicBool = AtomicBool::new(false);
+// The device rate and the last RECENT_SECONDS of samples
+static RECENT: Mutex<(u32, VecDeque<f32>)> = Mutex::new((0, VecDeque::new()));
+
+/// Keeps the last [`RECENT_SECONDS`] of everything captured from now on, for [`save_recent`].
+/// Decoding needs no such look-back (ggwave keeps its own history of the stream); this is for
+/// post-mortems.
+pub fn keep_recent() {
+    KEEP_RECENT.store(true, Ordering::Relaxed);
+}
+
+fn remember(sample_rate: u32, samples: &[f32]) {
+    if !KEEP_RECENT.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    let (rate, kept) = &mut *recent;
+    *rate = sample_rate;
+    kept.extend(samples);
+    let excess = kept.len().saturating_sub((sample_rate * RECENT_SECONDS) as usize);
+    kept.drain(..excess);
+}
+
+/// Writes what [`keep_recent`] has kept to a WAV at `path`.
+pub fn save_recent(path: &Path) -> Result<(), String> {
+    let recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    let (rate, kept) = &*recent;
+    let (front, back) = kept.as_slices();
+    WavWriter::create(path, *rate)
+        .and_then(|mut w| w.append(front).and_then(|_| w.append(back)))
+        .map_err(|e| e.to_string())
+}
+
+// When the capture stream last delivered audio, for `serve`'s /healthz
+static LAST_CAPTURE: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// How long ago the input device last delivered audio; `None` before capture starts. A device that
+/// stops delivering (unplugged, or a wedged driver) leaves this growing.
+#[cfg(all(feature = "serve", feature = "decode"))]
+pub fn since_capture() -> Option<Duration> {
+    LAST_CAPTURE.lock().unwrap_or_else(|e| e.into_inner()).map(|at| at.elapsed())
+}
+
+// --monitor-of: the output device whose sound captures listen to instead of an input
+static MONITOR_OF: OnceLock<String> = OnceLock::new();
+
+/// Makes every capture from now on hear what the output device `output` plays (`default` for the
+/// default one) rather than an input: through its PulseAudio/PipeWire monitor source on Linux, or
+/// WASAPI loopback on Windows, so what another app plays can be decoded. macOS has no monitor
+/// sources; sound routed through a virtual device such as BlackHole is captured with
+/// `--input-device` instead.
+pub fn monitor_of(output: &str) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        let source = match output {
+            "default" => "@DEFAULT_MONITOR@".to_owned(),
+            _ => format!("{}.monitor", pick_name(pulse_sinks()?, output)?),
+        };
+        // Read by libpulse (and PipeWire's pulse server) when the ALSA pulse device opens its stream
+        std::env::set_var("PULSE_SOURCE", source);
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    return Err(format!(
+        "--monitor-of {}: this system has no monitor sources; route the sound through a virtual device such as BlackHole and capture that with --input-device",
+        output
+    ));
+    #[allow(unreachable_code)]
+    {
+        let _ = MONITOR_OF.set(output.to_owned());
+        Ok(())
+    }
+}
+
+// The input device and config capture opens for --monitor-of
+#[cfg(target_os = "linux")]
+fn monitor_device(host: &cpal::Host, _output: &str) -> Result<(cpal::Device, cpal::SupportedStreamConfig), String> {
+    let mut devices = host.input_devices().map_err(|e| format!("input devices: {}", e))?;
+    let device = devices
+        .find(|d| d.name().is_ok_and(|name| name == "pulse"))
+        .ok_or("--monitor-of needs ALSA's pulse device (alsa-plugins-pulseaudio, or pipewire-alsa with pipewire-pulse)")?;
+    let config = device.default_input_config().map_err(|e| format!("input config: {}", e))?;
+    Ok((device, config))
+}
+
+// WASAPI records what an output device plays when an input stream is built on it
+#[cfg(target_os = "windows")]
+fn monitor_device(host: &cpal::Host, output: &str) -> Result<(cpal::Device, cpal::SupportedStreamConfig), String> {
+    let device = match output {
+        "default" => host.default_output_device().ok_or("no output device available")?,
+        name => pick_device(host.output_devices().map_err(|e| format!("output devices: {}", e))?, name)?,
+    };
+    let config = device.default_output_config().map_err(|e| format!("output config: {}", e))?;
+    Ok((device, config))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn monitor_device(_host: &cpal::Host, output: &str) -> Result<(cpal::Device, cpal::SupportedStreamConfig), String> {
+    Err(format!("--monitor-of {}: no monitor sources on this system", output))
+}
+
+/// The outputs [`monitor_of`] can listen to, for --list-input-devices: PulseAudio/PipeWire sinks
+/// on Linux (none if `pactl` is missing), every output device on Windows.
+pub fn monitors() -> Vec<String> {
+    #[cfg(target_os = "linux")]
+    return pulse_sinks().unwrap_or_default();
+    #[cfg(target_os = "windows")]
+    return cpal::default_host().output_devices().map(|d| d.filter_map(|d| d.name().ok()).collect()).unwrap_or_default();
+    #[allow(unreachable_code)]
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+fn pulse_sinks() -> Result<Vec<String>, String> {
+    let output = std::process::Command::new("pactl")
+        .args(["list", "short", "sinks"])
+        .output()
+        .map_err(|e| format!("pactl (needed to find monitor sources): {}", e))?;
+    if !output.status.success() {
+        return Err(format!("pactl list short sinks: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(sink_names(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// `pactl list short sinks` prints one tab-separated line per sink: index, name, driver, ...
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn sink_names(listing: &str) -> Vec<String> {
+    listing.lines().filter_map(|line| line.split('\t').nth(1)).map(str::to_owned).collect()
+}
+
+// Names of the virtual cables and loopback drivers people route app audio through
+const VIRTUAL_DEVICES: [&str; 7] = ["blackhole", "soundflower", "loopback", "vb-audio", "cable output", "voicemeeter", "monitor of"];
+
+fn is_virtual(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    VIRTUAL_DEVICES.iter().any(|v| lower.contains(v))
+}
+
+// --levels: how often, and where to
+const LEVEL_INTERVAL: Duration = Duration::from_millis(100);
+type LevelSink = Box<dyn Fn(f32, f32) + Send + Sync>;
+static ON_LEVEL: OnceLock<LevelSink> = OnceLock::new();
+
+/// Calls `on_level` with the RMS and peak level of the input, in dBFS, for every
+/// [`LEVEL_INTERVAL`] of audio captured from now on, for input meters.
+pub fn report_levels(on_level: impl Fn(f32, f32) + Send + Sync + 'static) {
+    let _ = ON_LEVEL.set(Box::new(on_level));
+}
+
+// Sum of squares, peak and sample count since the last report
+#[derive(Default)]
+struct Meter {
+    squares: f64,
+    peak: f32,
+    samples: usize,
+}
+
+impl Meter {
+    fn measure(&mut self, sample_rate: u32, samples: &[f32]) {
+        let Some(on_level) = ON_LEVEL.get() else { return };
+        for &s in samples {
+            self.squares += (s as f64) * (s as f64);
+            self.peak = self.peak.max(s.abs());
+        }
+        self.samples += samples.len();
+        if self.samples as f64 >= sample_rate as f64 * LEVEL_INTERVAL.as_secs_f64() {
+            let dbfs = |x: f64| (20.0 * x.max(1e-6).log10()) as f32;
+            on_level(dbfs((self.squares / self.samples as f64).sqrt()), dbfs(self.peak as f64));
+            *self = Meter::default();
+        }
+    }
+}
+
+fn stream_config(supported: &cpal::SupportedStreamConfig) -> cpal::StreamConfig {
+    let mut config = supported.config();
+    config.buffer_size = match (BUFFER_FRAMES.get(), supported.buffer_size()) {
+        (Some(&frames), cpal::SupportedBufferSize::Range { min, max }) => cpal::BufferSize::Fixed(frames.clamp(*min, *max)),
+        (Some(&frames), cpal::SupportedBufferSize::Unknown) => cpal::BufferSize::Fixed(frames),
+        (None, _) => cpal::BufferSize::Default,
+    };
+    config
+}
+
+/// Captures the input device called `device` (or the default one) and calls `on_payload` for every
+/// ggwave transmission heard, with the receiver settings (rx protocols, fixed payload length) in
+/// `params`. Runs until interrupted.
+pub fn listen(device: Option<&str>, params: ParametersBuilder, mut on_payload: impl FnMut(Vec<u8>)) -> Result<(), String> {
+    capture(device, None, params, |payload| {
+        on_payload(payload);
+        None::<()>
+    })
+    .map(|_| ())
+}
+
+/// Listens for up to `timeout`, stopping at the first payload `on_payload` maps to `Some`.
+pub fn listen_for<T>(
+    device: Option<&str>,
+    timeout: Duration,
+    params: ParametersBuilder,
+    on_payload: impl FnMut(Vec<u8>) -> Option<T>,
+) -> Result<Option<T>, String> {
+    capture(device, Some(timeout), params, on_payload)
+}
+
+/// Captures `duration` of raw mono audio from the input device called `device` (or the default
+/// one, or the --monitor-of output); returns the samples and their rate.
+#[cfg_attr(not(feature = "spectrogram"), allow(dead_code))]
+pub fn sample(device: Option<&str>, duration: Duration) -> Result<(Vec<f32>, u32), String> {
+    let Input { stream, rx, config, .. } = open_input(device)?;
+    stream.play().map_err(|e| format!("start capture: {}", e))?;
+    let wanted = (duration.as_secs_f64() * config.sample_rate.0 as f64) as usize;
+    let mut samples = Vec::with_capacity(wanted);
+    let mut meter = Meter::default();
+    while samples.len() < wanted {
+        let chunk = rx.recv_timeout(Duration::from_secs(5)).map_err(|_| "capture stream stalled".to_string())?;
+        meter.measure(config.sample_rate.0, &chunk);
+        samples.extend_from_slice(&chunk);
+    }
+    samples.truncate(wanted);
+    Ok((samples, config.sample_rate.0))
+}
+
+// An input stream, not started yet, and where its mono buffers arrive
+struct Input {
+    stream: cpal::Stream,
+    rx: Receiver<Vec<f32>>,
+    config: cpal::StreamConfig,
+    name: String,
+}
+
+fn open_input(device: Option<&str>) -> Result<Input, String> {
+    let host = cpal::default_host();
+    let (device, supported) = match (MONITOR_OF.get(), device) {
+        (Some(output), _) => monitor_device(&host, output)?,
+        (None, device) => {
+            let device = match device {
+                Some(name) => pick_device(host.input_devices().map_err(|e| format!("input devices: {}", e))?, name)?,
+                None => host.default_input_device().ok_or("no input device available")?,
+            };
+            let supported = device.default_input_config().map_err(|e| format!("input config: {}", e))?;
+            (device, supported)
+        }
+    };
+    let config = stream_config(&supported);
+    let (tx, rx) = channel();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_input::<f32>(&device, &config, tx),
+        cpal::SampleFormat::I16 => build_input::<i16>(&device, &config, tx),
+        cpal::SampleFormat::U16 => build_input::<u16>(&device, &config, tx),
+        cpal::SampleFormat::I32 => build_input::<i32>(&device, &config, tx),
+        other => return Err(format!("unsupported input sample format {}", other)),
+    }
+    .map_err(|e| format!("open input stream: {}", e))?;
+    let name = device.name().unwrap_or_else(|_| "default input".into());
+    Ok(Input { stream, rx, config, name })
+}
+
+fn capture<T>(
+    device: Option<&str>,
+    timeout: Option<Duration>,
+    params: ParametersBuilder,
+    mut on_payload: impl FnMut(Vec<u8>) -> Option<T>,
+) -> Result<Option<T>, String> {
+    let Input { stream, rx, config, name } = open_input(device)?;
+
+    // Decode at the device rate; ggwave handles rates other than 48 kHz
+    let mut instance = params
+        .operating_mode(OperatingMode::Rx)
+        .input_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32)
+        .sample_rate(config.sample_rate.0)
+        .init()
+        .map_err(|e| format!("ggwave init failed: {}", e))?;
+    stream.play().map_err(|e| format!("start capture: {}", e))?;
+    let deadline = timeout.map(|t| Instant::now() + t);
+    if deadline.is_none() {
+        eprintln!(
+            "Listening on {} ({} Hz, {} ch); Ctrl+C to stop",
+            name,
+            config.sample_rate.0,
+            config.channels
+        );
+    }
+
+    let mut meter = Meter::default();
+    loop {
+        let chunk = match deadline {
+            Some(deadline) => match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(chunk) => chunk,
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match rx.recv() {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            },
+        };
+        record(config.sample_rate.0, &chunk)?;
+        remember(config.sample_rate.0, &chunk);
+        meter.measure(config.sample_rate.0, &chunk);
+        *LAST_CAPTURE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+        let bytes: Vec<u8> = chunk.iter().flat_map(|s: &f32| s.to_le_bytes()).collect();
+        if let Some(done) = instance.receive(&bytes).and_then(&mut on_payload) {
+            return Ok(Some(done));
+        }
+    }
+    Err("capture stream closed".into())
+}
+
+// Downmixes each callback buffer to mono f32 and hands it to the decoding thread
+fn build_input<T>(device: &cpal::Device, config: &cpal::StreamConfig, tx: Sender<Vec<f32>>) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let mono = data
+                .chunks(channels)
+                .map(|frame| frame.iter().map(|&s| s.to_sample::<f32>()).sum::<f32>() / channels as f32)
+                .collect();
+            let _ = tx.send(mono);
+        },
+        |e| eprintln!("Capture error: {}", e),
+        None,
+    )
+}
+
+/// Plays mono `samples` on the output device called `device` (or the default one) and returns once
+/// they have been heard.
+pub fn play(samples: &[f32], sample_rate: u32, device: Option<&str>) -> Result<(), String> {
+    play_at(samples, sample_rate, device, None)
+}
+
+/// Like [`play`], but the first sample leaves the speaker at the wall-clock time `start`, so hosts
+/// whose clocks are synchronized (NTP, PTP) sound together. Silence is played until then, counted
+/// from the latency the host reports for the stream plus [`set_output_latency`]. Fails without
+/// playing anything if `start` has passed by the time the stream runs.
+pub fn play_at(samples: &[f32], sample_rate: u32, device: Option<&str>, start: Option<SystemTime>) -> Result<(), String> {
+    let host = cpal::default_host();
+    let device = match device {
+        Some(name) => pick_device(host.output_devices().map_err(|e| format!("output devices: {}", e))?, name)?,
+        None => host.default_output_device().ok_or("no output device available")?,
+    };
+    let supported = output_config(&device, sample_rate)?;
+    let config = stream_config(&supported);
+    let samples = resample(samples, sample_rate, config.sample_rate.0);
+    let wait = start.and_then(|start| start.duration_since(SystemTime::now()).ok()).unwrap_or_default();
+    let duration = wait + Duration::from_secs_f64(samples.len() as f64 / config.sample_rate.0 as f64);
+    let (done_tx, done_rx) = channel();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_output::<f32>(&device, &config, samples, start, done_tx),
+        cpal::SampleFormat::I16 => build_output::<i16>(&device, &config, samples, start, done_tx),
+        cpal::SampleFormat::U16 => build_output::<u16>(&device, &config, samples, start, done_tx),
+        cpal::SampleFormat::I32 => build_output::<i32>(&device, &config, samples, start, done_tx),
+        other => return Err(format!("unsupported output sample format {}", other)),
+    }
+    .map_err(|e| format!("open output stream: {}", e))?;
+    stream.play().map_err(|e| format!("start playback: {}", e))?;
+    // The callback reports when it runs out of samples; then give the device buffer time to drain
+    done_rx.recv_timeout(duration + Duration::from_secs(5)).map_err(|_| "output stream stalled".to_string())??;
+    std::thread::sleep(Duration::from_millis(250));
+    Ok(())
+}
+
+// Frames of silence before a stream whose next buffer is heard `heard_in` from now reaches `start`,
+// or how late it already is
+fn lead_frames(start: SystemTime, now: SystemTime, heard_in: Duration, sample_rate: u32) -> Result<usize, Duration> {
+    match start.duration_since(now + heard_in + OUTPUT_LATENCY.get().copied().unwrap_or_default()) {
+        Ok(wait) => Ok((wait.as_secs_f64() * sample_rate as f64).round() as usize),
+        Err(e) => Err(e.duration()),
+    }
+}
+
+pub struct DeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    /// A virtual cable or loopback device (BlackHole, VB-Cable, a monitor source, ...)
+    pub is_virtual: bool,
+    /// Supported sample rate ranges in Hz, merged across sample formats and channel counts
+    pub sample_rates: Vec<(u32, u32)>,
+}
+
+/// Every output device the host reports, for --list-output-devices.
+pub fn output_devices() -> Result<Vec<DeviceInfo>, String> {
+    let host = cpal::default_host();
+    let default = host.default_output_device().and_then(|d| d.name().ok());
+    let devices = host.output_devices().map_err(|e| format!("output devices: {}", e))?;
+    Ok(devices
+        .filter_map(|d| {
+            let ranges = d.supported_output_configs().map(|c| c.map(|c| (c.min_sample_rate().0, c.max_sample_rate().0)).collect());
+            device_info(d.name().ok()?, default.as_deref(), ranges.unwrap_or_default())
+        })
+        .collect())
+}
+
+/// Every input device the host reports, for --list-input-devices.
+pub fn input_devices() -> Result<Vec<DeviceInfo>, String> {
+    let host = cpal::default_host();
+    let default = host.default_input_device().and_then(|d| d.name().ok());
+    let devices = host.input_devices().map_err(|e| format!("input devices: {}", e))?;
+    Ok(devices
+        .filter_map(|d| {
+            let ranges = d.supported_input_configs().map(|c| c.map(|c| (c.min_sample_rate().0, c.max_sample_rate().0)).collect());
+            device_info(d.name().ok()?, default.as_deref(), ranges.unwrap_or_default())
+        })
+        .collect())
+}
+
+fn device_info(name: String, default: Option<&str>, mut ranges: Vec<(u32, u32)>) -> Option<DeviceInfo> {
+    ranges.sort_unstable();
+    let mut sample_rates: Vec<(u32, u32)> = Vec::new();
+    for (min, max) in ranges {
+        match sample_rates.last_mut() {
+            Some(last) if min <= last.1 => last.1 = last.1.max(max),
+            _ => sample_rates.push((min, max)),
+        }
+    }
+    Some(DeviceInfo { is_default: default == Some(name.as_str()), is_virtual: is_virtual(&name), name, sample_rates })
+}
+
+// An exact name, else the one device whose name contains `wanted` ignoring case; device names
+// are long, so a distinctive part of one is enough
+fn pick_device(devices: impl Iterator<Item = cpal::Device>, wanted: &str) -> Result<cpal::Device, String> {
+    let mut named: Vec<(String, cpal::Device)> = devices.filter_map(|d| Some((d.name().ok()?, d))).collect();
+    let names: Vec<String> = named.iter().map(|(name, _)| name.clone()).collect();
+    let picked = pick_name(names, wanted)?;
+    let i = named.iter().position(|(name, _)| *name == picked).expect("picked from these names");
+    Ok(named.swap_remove(i).1)
+}
+
+fn pick_name(mut names: Vec<String>, wanted: &str) -> Result<String, String> {
+    if let Some(i) = names.iter().position(|name| name == wanted) {
+        return Ok(names.swap_remove(i));
+    }
+    let lower = wanted.to_lowercase();
+    names.retain(|name| name.to_lowercase().contains(&lower));
+    match names.len() {
+        1 => Ok(names.remove(0)),
+        0 => Err(format!("no audio device matches {:?}", wanted)),
+        _ => Err(format!("{:?} matches several devices: {}", wanted, names.join(", "))),
+    }
+}
+
+// Prefers a config at the waveform's own rate so nothing needs resampling
+fn output_config(device: &cpal::Device, sample_rate: u32) -> Result<cpal::SupportedStreamConfig, String> {
+    let exact = device
+        .supported_output_configs()
+        .map_err(|e| format!("output configs: {}", e))?
+        .filter(|c| (c.min_sample_rate().0..=c.max_sample_rate().0).contains(&sample_rate))
+        .max_by_key(|c| c.sample_format() == cpal::SampleFormat::F32)
+        .map(|c| c.with_sample_rate(cpal::SampleRate(sample_rate)));
+    match exact {
+        Some(c) => Ok(c),
+        None => device.default_output_config().map_err(|e| format!("output config: {}", e)),
+    }
+}
+
+// Linear interpolation; only used when the device can't run at the waveform's rate
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let step = from as f64 / to as f64;
+    let len = (samples.len() as f64 / step) as usize;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let j = pos as usize;
+            let a = samples[j];
+            let b = samples.get(j + 1).copied().unwrap_or(a);
+            a + (b - a) * (pos - j as f64) as f32
+        })
+        .collect()
+}
+
+// Writes the mono samples to every channel, then silence; `done` fires once the last sample is queued
+fn build_output<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: Vec<f32>,
+    start: Option<SystemTime>,
+    done: Sender<Result<(), String>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let (channels, sample_rate) = (config.channels as usize, config.sample_rate.0);
+    let mut samples = samples.into_iter();
+    let mut finished = false;
+    // Silence still to play before the samples; worked out on the first callback, once the host
+    // says how far behind the callback the speaker is
+    let mut lead = start.is_none().then_some(0);
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+            let remaining = match (lead.as_mut(), start) {
+                (Some(remaining), _) => remaining,
+                (None, start) => {
+                    let timestamp = info.timestamp();
+                    let heard_in = timestamp.playback.duration_since(&timestamp.callback).unwrap_or_default();
+                    match lead_frames(start.expect("no lead only when scheduled"), SystemTime::now(), heard_in, sample_rate) {
+                        Ok(frames) => lead.insert(frames),
+                        Err(late) => {
+                            finished = true;
+                            samples = Vec::new().into_iter();
+                            let _ = done.send(Err(format!("start time passed {} ms before the device was ready", late.as_millis())));
+                            lead.insert(0)
+                        }
+                    }
+                }
+            };
+            for frame in data.chunks_mut(channels) {
+                let sample = if *remaining > 0 {
+                    *remaining -= 1;
+                    0.0
+                } else {
+                    samples.next().unwrap_or(0.0)
+                };
+                frame.fill(T::from_sample(sample));
+            }
+            if samples.len() == 0 && !finished {
+                finished = true;
+                let _ = done.send(Ok(()));
+            }
+        },
+        |e| eprintln!("Playback error: {}", e),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitor_sources_are_found_by_sink_name() {
+        let listing = "0\talsa_output.pci-0000_00_1f.3.analog-stereo\tmodule-alsa-card.c\ts16le 2ch 48000Hz\tSUSPENDED\n\
+                       1\tbluez_output.AA_BB.1\tmodule-bluez5-device.c\ts16le 2ch 48000Hz\tRUNNING\n";
+        let sinks = sink_names(listing);
+        assert_eq!(sinks, ["alsa_output.pci-0000_00_1f.3.analog-stereo", "bluez_output.AA_BB.1"]);
+        assert_eq!(pick_name(sinks.clone(), "bluez").unwrap(), "bluez_output.AA_BB.1");
+        assert!(pick_name(sinks.clone(), "output").unwrap_err().contains("several"));
+        assert!(pick_name(sinks, "hdmi").is_err());
+    }
+
+    #[test]
+    fn scheduled_playback_waits_out_the_reported_latency() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let start = now + Duration::from_millis(500);
+        // 100 ms in the device pipeline leaves 400 ms of silence to play first
+        assert_eq!(lead_frames(start, now, Duration::from_millis(100), 48000), Ok(19200));
+        assert_eq!(lead_frames(start, now, Duration::from_millis(600), 48000), Err(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn virtual_cables_are_marked() {
+        assert!(is_virtual("BlackHole 2ch"));
+        assert!(is_virtual("CABLE Output (VB-Audio Virtual Cable)"));
+        assert!(is_virtual("Monitor of Built-in Audio Analog Stereo"));
+        assert!(!is_virtual("MacBook Pro Microphone"));
+    }
+}
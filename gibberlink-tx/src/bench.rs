@@ -0,0 +1,102 @@
+// Robustness benchmark behind `bench`: random payloads through a modem, mixed with noise at a
+// range of signal-to-noise ratios, decoded back and compared.
+
+use crate::modem::Modem;
+use crate::NoiseKind;
+
+/// Success counts for one modem setting, one entry per SNR.
+pub struct Row {
+    pub bytes_per_s: f64,
+    pub successes: Vec<usize>,
+}
+
+pub struct Config<'a> {
+    pub snrs_db: &'a [f32],
+    pub noise: NoiseKind,
+    pub trials: u32,
+    pub payload_len: usize,
+    pub volume: i32,
+    // DTMF only carries keypad symbols
+    pub alphabet: &'a [u8],
+}
+
+pub fn run(modem: &dyn Modem, config: &Config, rng: &mut Rng) -> Result<Row, String> {
+    let mut row = Row { bytes_per_s: 0.0, successes: vec![0; config.snrs_db.len()] };
+    for _ in 0..config.trials {
+        let payload: Vec<u8> = (0..config.payload_len).map(|_| config.alphabet[rng.below(config.alphabet.len())]).collect();
+        let waveform = modem.modulate(&payload, config.volume).map_err(|e| e.to_string())?;
+        row.bytes_per_s = payload.len() as f64 * waveform.sample_rate as f64 / waveform.samples.len() as f64;
+        // Room noise before and after the transmission as well, as a live capture would have
+        let pad = waveform.sample_rate as usize / 4;
+        let mut clean = vec![0.0f32; pad];
+        clean.extend(waveform.samples.iter().map(|&s| s as f32 / 32768.0));
+        clean.resize(clean.len() + pad, 0.0);
+        for (snr, successes) in config.snrs_db.iter().zip(&mut row.successes) {
+            let noisy = add_noise(&clean, *snr, config.noise, rng);
+            if let Ok(messages) = modem.demodulate_all(&noisy, waveform.sample_rate) {
+                *successes += messages.contains(&payload) as usize;
+            }
+        }
+    }
+    Ok(row)
+}
+
+// Scales the noise against the power of the audible part of the signal, not the silent padding
+fn add_noise(signal: &[f32], snr_db: f32, kind: NoiseKind, rng: &mut Rng) -> Vec<f32> {
+    let active: Vec<f32> = signal.iter().copied().filter(|s| s.abs() > 1e-4).collect();
+    let signal_power = active.iter().map(|s| s * s).sum::<f32>() / active.len().max(1) as f32;
+    let mut noise: Vec<f32> = match kind {
+        NoiseKind::White => (0..signal.len()).map(|_| rng.gaussian()).collect(),
+        NoiseKind::Pink => {
+            // Paul Kellet's economy filter: -3 dB/octave within 0.05 dB above 9 Hz
+            let (mut b0, mut b1, mut b2) = (0.0, 0.0, 0.0);
+            (0..signal.len())
+                .map(|_| {
+                    let white = rng.gaussian();
+                    b0 = 0.99765 * b0 + white * 0.0990460;
+                    b1 = 0.96300 * b1 + white * 0.2965164;
+                    b2 = 0.57000 * b2 + white * 1.0526913;
+                    b0 + b1 + b2 + white * 0.1848
+                })
+                .collect()
+        }
+    };
+    let noise_power = noise.iter().map(|n| n * n).sum::<f32>() / noise.len().max(1) as f32;
+    let scale = (signal_power / 10f32.powf(snr_db / 10.0) / noise_power.max(f32::MIN_POSITIVE)).sqrt();
+    for (n, s) in noise.iter_mut().zip(signal) {
+        *n = s + *n * scale;
+    }
+    noise
+}
+
+/// xorshift64*: plenty for test payloads and noise, and no extra dependency.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Rng(nanos | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    // Uniform in (0, 1]
+    fn unit(&mut self) -> f32 {
+        ((self.next_u64() >> 40) + 1) as f32 / (1u64 << 24) as f32
+    }
+
+    // Standard normal, by Box-Muller
+    fn gaussian(&mut self) -> f32 {
+        let (u1, u2) = (self.unit(), self.unit());
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+    }
+}
@@ -0,0 +1,84 @@
+// Recordings in formats other than PCM WAV (MP3, M4A/AAC, FLAC, Ogg Vorbis, ...) through symphonia
+// (the `codecs` feature), downmixed to mono for decoding.
+
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatReader;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::probe::Hint;
+
+/// Decodes the first audio track of the file at `path`, or of `bytes` when it was read from stdin,
+/// to mono samples and their rate.
+pub fn decode(path: &Path, bytes: Option<Vec<u8>>) -> Result<(Vec<f32>, u32), String> {
+    let mut hint = Hint::new();
+    // Containers such as M4A keep their index at the end, so stdin is buffered rather than streamed
+    let source: Box<dyn MediaSource> = match bytes {
+        Some(bytes) => Box::new(std::io::Cursor::new(bytes)),
+        None => {
+            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+                hint.with_extension(ext);
+            }
+            Box::new(std::fs::File::open(path).map_err(|e| format!("open: {}", e))?)
+        }
+    };
+    let mut samples = Vec::new();
+    let sample_rate = decode_each(source, &hint, |chunk, _| samples.extend_from_slice(chunk))?;
+    Ok((samples, sample_rate))
+}
+
+/// Decodes the first audio track of `source` packet by packet, handing each packet's mono samples
+/// and their rate to `on_samples` as it goes, so endless sources (live streams) work too. Returns
+/// the rate once the source ends.
+pub fn decode_each(source: Box<dyn MediaSource>, hint: &Hint, mut on_samples: impl FnMut(&[f32], u32)) -> Result<u32, String> {
+    let stream = MediaSourceStream::new(source, Default::default());
+    let mut format = symphonia::default::get_probe()
+        .format(hint, stream, &Default::default(), &Default::default())
+        .map_err(|e| format!("unrecognised audio format: {}", e))?
+        .format;
+    let mut track = first_track(format.as_ref())?;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            // A chained Ogg stream (the next song on a radio station) starts over with new tracks
+            Err(Error::ResetRequired) => {
+                track = first_track(format.as_ref())?;
+                continue;
+            }
+            Err(e) => return Err(format!("read: {}", e)),
+        };
+        if packet.track_id() != track.id {
+            continue;
+        }
+        let decoded = match track.decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A damaged packet only costs its own samples
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("decode: {}", e)),
+        };
+        let spec = *decoded.spec();
+        let rate = *track.sample_rate.get_or_insert(spec.rate);
+        let channels = spec.channels.count().max(1);
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+        let mono: Vec<f32> = buf.samples().chunks(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect();
+        on_samples(&mono, rate);
+    }
+    track.sample_rate.ok_or_else(|| "unknown sample rate".into())
+}
+
+struct Track {
+    id: u32,
+    sample_rate: Option<u32>,
+    decoder: Box<dyn Decoder>,
+}
+
+fn first_track(format: &dyn FormatReader) -> Result<Track, String> {
+    let track = format.tracks().iter().find(|t| t.codec_params.codec != CODEC_TYPE_NULL).ok_or("no audio track")?;
+    let decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("unsupported codec: {}", e))?;
+    Ok(Track { id: track.id, sample_rate: track.codec_params.sample_rate, decoder })
+}
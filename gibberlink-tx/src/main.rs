@@ -1,93 +1,252 @@
-use clap::Parser;
-use std::ffi::c_int;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::PathBuf;
+// Without `decode` the rx half of the modems is compiled but unused
+#![cfg_attr(not(feature = "decode"), allow(dead_code))]
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug)]
-struct GgwaveParameters {
-    payloadLength: c_int,
-    sampleRateInp: f32,
-    sampleRateOut: f32,
-    sampleRate: f32,
-    samplesPerFrame: c_int,
-    soundMarkerThreshold: f32,
-    sampleFormatInp: c_int,
-    sampleFormatOut: c_int,
-    operatingMode: c_int,
-}
-
-#[allow(non_camel_case_types)]
-type ggwave_Instance = c_int;
-
-// Enums from ggwave.h
-#[allow(non_camel_case_types)]
-mod ggwave_consts {
-    pub const GGWAVE_SAMPLE_FORMAT_UNDEFINED: i32 = 0;
-    pub const GGWAVE_SAMPLE_FORMAT_U8: i32 = 1;
-    pub const GGWAVE_SAMPLE_FORMAT_I8: i32 = 2;
-    pub const GGWAVE_SAMPLE_FORMAT_U16: i32 = 3;
-    pub const GGWAVE_SAMPLE_FORMAT_I16: i32 = 4;
-    pub const GGWAVE_SAMPLE_FORMAT_F32: i32 = 5;
-
-    pub const GGWAVE_PROTOCOL_AUDIBLE_NORMAL: i32 = 0;
-    pub const GGWAVE_PROTOCOL_AUDIBLE_FAST: i32 = 1;
-    pub const GGWAVE_PROTOCOL_AUDIBLE_FASTEST: i32 = 2;
-    pub const GGWAVE_PROTOCOL_ULTRASOUND_NORMAL: i32 = 3;
-    pub const GGWAVE_PROTOCOL_ULTRASOUND_FAST: i32 = 4;
-    pub const GGWAVE_PROTOCOL_ULTRASOUND_FASTEST: i32 = 5;
-    pub const GGWAVE_PROTOCOL_DT_NORMAL: i32 = 6;
-    pub const GGWAVE_PROTOCOL_DT_FAST: i32 = 7;
-    pub const GGWAVE_PROTOCOL_DT_FASTEST: i32 = 8;
-    pub const GGWAVE_PROTOCOL_MT_NORMAL: i32 = 9;
-    pub const GGWAVE_PROTOCOL_MT_FAST: i32 = 10;
-    pub const GGWAVE_PROTOCOL_MT_FASTEST: i32 = 11;
-
-    pub const GGWAVE_OPERATING_MODE_RX: i32 = 1 << 1;
-    pub const GGWAVE_OPERATING_MODE_TX: i32 = 1 << 2;
-    pub const GGWAVE_OPERATING_MODE_RX_AND_TX: i32 = GGWAVE_OPERATING_MODE_RX | GGWAVE_OPERATING_MODE_TX;
-}
-
-#[link(name = "ggwave")]
-extern "C" {
-    fn ggwave_getDefaultParameters() -> GgwaveParameters;
-    fn ggwave_setLogFile(fptr: *mut core::ffi::c_void);
-    fn ggwave_init(parameters: GgwaveParameters) -> ggwave_Instance;
-    fn ggwave_free(instance: ggwave_Instance);
-    fn ggwave_encode(
-        instance: ggwave_Instance,
-        payloadBuffer: *const core::ffi::c_void,
-        payloadSize: c_int,
-        protocolId: c_int,
-        volume: c_int,
-        waveformBuffer: *mut core::ffi::c_void,
-        query: c_int,
-    ) -> c_int;
-    fn ggwave_ndecode(
-        instance: ggwave_Instance,
-        waveformBuffer: *const core::ffi::c_void,
-        waveformSize: c_int,
-        payloadBuffer: *mut core::ffi::c_void,
-        payloadSize: c_int,
-    ) -> c_int;
-}
+#[cfg(all(feature = "agent", feature = "audio", feature = "decode"))]
+mod agent;
+#[cfg(feature = "audio")]
+mod audio;
+#[cfg(feature = "decode")]
+mod bench;
+#[cfg(feature = "codecs")]
+mod codecs;
+mod modem;
+#[cfg(feature = "spectrogram")]
+mod spectrogram;
+#[cfg(all(feature = "serve", feature = "audio", feature = "decode"))]
+mod serve;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(all(feature = "tls", feature = "serve", feature = "audio", feature = "decode"))]
+mod tls;
+#[cfg(all(feature = "watch", feature = "audio"))]
+mod watch;
+
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use gibberlink_core::wav::write_wav;
+#[cfg(all(feature = "serve", feature = "audio", feature = "decode"))]
+use gibberlink_core::wav::write_wav_to;
+#[cfg(any(feature = "decode", feature = "audio"))]
+use gibberlink_core::wav::{read_wav, to_mono_f32};
+#[cfg(feature = "decode")]
+use gibberlink_core::wav::read_wav_from;
+use gibberlink_core::{chunk, file, framing, integrity};
+#[cfg(all(feature = "audio", feature = "decode"))]
+use gibberlink_core::chat;
+#[cfg(any(feature = "decode", feature = "audio"))]
+use gibberlink_core::chunk::Control;
+#[cfg(feature = "decode")]
+use gibberlink_core::chunk::{Reassembler, Received};
+use gibberlink_core::{ggwave_consts, parse_protocol, protocol_max_hz, OperatingMode, ParametersBuilder, SharedDecoder};
+use modem::{Modem, ModemError, Waveform};
+use std::io::{Read, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
 
+// One struct per group of options, flattened into each subcommand that takes them and into the flat
+// flag set (still accepted on its own for existing scripts), which main() works from
 #[derive(Parser, Debug)]
-#[command(name = "gibberlink-tx", about = "Text → Gibberlink (ggwave) audio generator and player")]
+#[command(name = "gibberlink-tx", about = "Text → Gibberlink (ggwave) audio generator and player", args_conflicts_with_subcommands = true)]
+#[command(group(clap::ArgGroup::new("daemon").args(["ws", "http", "mqtt", "serial", "udp"]).multiple(true)
+    .conflicts_with_all(["text", "file", "input_format", "batch", "watch", "reliable", "decode_wav", "decode_raw", "listen", "chat", "bench"])))]
+#[command(group(clap::ArgGroup::new("rate").args(["raw_rate", "sample_rate"])))]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Text to encode. If omitted, reads from stdin.
     #[arg(short, long)]
     text: Option<String>,
 
-    /// Output WAV file path
-    #[arg(short, long, default_value = "gibberlink.wav")]
-    out: PathBuf,
+    /// Decode payload from WAV file (`-` for stdin), or a live http(s):// or rtsp:// stream (needs `stream`), and print as text
+    #[arg(long, value_name = "WAV")]
+    decode_wav: Option<PathBuf>,
+
+    /// Decode raw mono PCM (see --raw-format and --raw-rate) from this file, or `-` for stdin
+    #[arg(long, value_name = "PATH", conflicts_with = "decode_wav")]
+    decode_raw: Option<PathBuf>,
+
+    /// Decode from the default microphone continuously, printing each message as it arrives (ggwave only)
+    #[arg(long, conflicts_with_all = ["decode_wav", "decode_raw"])]
+    listen: bool,
+
+    /// With --listen, answer --reliable senders with acknowledgements (transmits on the default output device)
+    #[arg(long, requires = "listen")]
+    ack: bool,
+
+    /// Full-duplex chat: send each line typed on stdin while printing received lines with a timestamp and the sender's peer ID (ggwave only)
+    #[arg(long, conflicts_with_all = ["decode_wav", "decode_raw", "listen", "text", "file"])]
+    chat: bool,
+
+    /// With --chat, the name other peers see for this end (at most 16 bytes; default: random)
+    #[arg(long, value_name = "NAME", requires = "chat")]
+    peer_id: Option<String>,
+
+    /// Measure how often each protocol survives noise: encode random payloads, add noise at each --snr, decode, and print success rates
+    #[arg(long, conflicts_with_all = ["decode_wav", "decode_raw", "listen", "chat"])]
+    bench: bool,
+
+    /// Record the room for --seconds, report the noise in each protocol family's band and recommend the quietest (needs `audio` and `spectrogram`)
+    #[arg(long, conflicts_with_all = ["decode_wav", "decode_raw", "listen", "chat", "bench"])]
+    spectrum: bool,
+
+    /// With --spectrum, how long to record
+    #[arg(long, value_name = "N", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=3600))]
+    seconds: u32,
+
+    /// Watch this directory and transmit each .txt/.json file dropped into it, then move it to --done-dir (needs `watch` and `audio`)
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["text", "file", "input_format", "batch", "reliable", "decode_wav", "decode_raw", "listen", "chat", "bench"])]
+    watch: Option<PathBuf>,
+
+    /// With --watch, where sent files are moved [default: DIR/done]
+    #[arg(long, value_name = "DIR", requires = "watch")]
+    done_dir: Option<PathBuf>,
+
+    // The program `bridge` runs and its arguments (subcommand only)
+    #[arg(skip)]
+    bridge: Vec<String>,
+
+    // The model `agent` talks to (subcommand only)
+    #[arg(skip)]
+    agent: Option<AgentOptions>,
+
+    #[command(flatten)]
+    common: CommonOptions,
+
+    #[command(flatten)]
+    receiver: ReceiverOptions,
+
+    #[command(flatten)]
+    signal: SignalOptions,
+
+    #[command(flatten)]
+    shaping: ShapingOptions,
+
+    #[command(flatten)]
+    playback: PlaybackOptions,
+
+    #[command(flatten)]
+    capture: CaptureOptions,
+
+    #[command(flatten)]
+    sealing: SealOptions,
+
+    #[command(flatten)]
+    steps: TextOptions,
+
+    #[command(flatten)]
+    recording: RecordingOptions,
+
+    #[command(flatten)]
+    received: ReceivedOptions,
+
+    #[command(flatten)]
+    output: OutputOptions,
+
+    #[command(flatten)]
+    encode: EncodeOptions,
+
+    #[command(flatten)]
+    decode: DecodeOptions,
+
+    #[command(flatten)]
+    benchmark: BenchOptions,
+
+    #[command(flatten)]
+    serve: ServeOptions,
+}
+
+// Options every subcommand takes
+#[derive(clap::Args, Debug)]
+struct CommonOptions {
+    /// Apply a named protocol/volume preset from the presets file (explicit flags still win)
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// Presets file [default: <config dir>/gibberlink/presets.toml, shared with the UI]
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Modulation backend: ggwave, afsk (Bell 202, 1200 baud 8N1), minimodem (see --baud) or dtmf (payload must be 0-9, A-D, *, #)
+    #[arg(long, value_enum, default_value = "ggwave")]
+    modem: ModemKind,
+
+    /// minimodem baud mode: a rate such as 1200 or 300 (at most half the sample rate), or `rtty` (45.45 baud Baudot)
+    #[arg(long, default_value = "1200", value_parser = parse_baud)]
+    baud: String,
+
+    /// Override the mark (1) tone frequency for --modem minimodem, like minimodem -M
+    #[arg(long, value_name = "HZ")]
+    mark_hz: Option<f32>,
+
+    /// Override the space (0) tone frequency for --modem minimodem, like minimodem -S
+    #[arg(long, value_name = "HZ")]
+    space_hz: Option<f32>,
 
     /// Protocol: audible|ultrasound|dt|mt (normal|fast|fastest)
-    #[arg(long, default_value = "audible:fast")] 
+    #[arg(long, default_value = "audible:fast")]
     protocol: String,
 
+    /// ggwave fixed-length mode: every transmission carries exactly N bytes (2..=64; one holds the payload length, so payloads up to N-1 bytes) and no ggwave length header, which is much more robust for short frames. Sender and receiver must use the same N
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(i32).range(2..=gibberlink_core::MAX_LENGTH_FIXED as i64))]
+    payload_length: Option<i32>,
+
+    /// ggwave audio frame size in samples (1..=1024, default 1024). Tone spacing follows from it, so sender and receiver must match; smaller frames cut latency at the cost of robustness
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(i32).range(1..=gibberlink_core::MAX_SAMPLES_PER_FRAME as i64))]
+    samples_per_frame: Option<i32>,
+
+    /// Payload format on the air. cbor/msgpack/protobuf take JSON text and re-emit JSON on decode
+    #[arg(long, value_enum, default_value = "text")]
+    payload_format: PayloadFormat,
+
+    /// Compiled descriptor set (`protoc --descriptor_set_out`) for --payload-format protobuf
+    #[arg(long, value_name = "FILE")]
+    proto_descriptor: Option<PathBuf>,
+
+    /// Fully-qualified protobuf message type, e.g. `telemetry.Reading`
+    #[arg(long, value_name = "TYPE")]
+    proto_message: Option<String>,
+
+    /// zstd dictionary shared by both ends; payloads are compressed with it on encode and expanded on decode
+    #[arg(long, value_name = "FILE")]
+    dict: Option<PathBuf>,
+
+    /// Encrypt payloads with AES-256-GCM under a key derived from this passphrase (and decrypt on decode)
+    #[arg(long, value_name = "PASSPHRASE", conflicts_with = "key_file")]
+    key: Option<String>,
+
+    /// Like --key, but read a 32-byte key (raw, or 64 hex digits) from FILE
+    #[arg(long, value_name = "FILE")]
+    key_file: Option<PathBuf>,
+
+    /// Rhai script defining on_transmit(text) and/or on_receive(text); return a string to replace the payload or () to drop it
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+
+    /// Print results as JSON lines on stdout: an `encoded` summary, or a `decoded` event per message (`warning` for failures) plus a `decode_summary`
+    #[arg(long)]
+    json: bool,
+
+    /// Print extra diagnostics (payload classification, sizes) to stderr
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+// How the ggwave receiver listens
+#[derive(clap::Args, Debug)]
+struct ReceiverOptions {
+    /// Only listen for these ggwave protocols when decoding: families (audible,ultrasound,dt,mt) or family:speed, comma-separated
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    rx_protocols: Vec<String>,
+
+    /// ggwave receive threshold for the start/end markers (default 3.0): raise it to ignore noisy rooms, lower it to catch weak signals
+    #[arg(long, value_name = "X", value_parser = parse_marker_threshold)]
+    marker_threshold: Option<f32>,
+}
+
+// The generated signal
+#[derive(clap::Args, Debug)]
+struct SignalOptions {
     /// Volume [0..100]
     #[arg(long, default_value_t = 25)]
     volume: i32,
@@ -95,371 +254,3844 @@ struct Args {
     /// Sample rate for output
     #[arg(long)]
     sample_rate: Option<u32>,
+}
+
+// Level and band limits for what goes on the air
+#[derive(clap::Args, Debug)]
+struct ShapingOptions {
+    /// Refuse to transmit if the chosen protocol/tones go above this frequency (pets, hearing aids)
+    #[arg(long, value_name = "HZ")]
+    max_freq_hz: Option<f32>,
+
+    /// Scale the encoded audio so its loudest sample sits at this level, in dBFS (e.g. -1); overrides the loudness --volume gives
+    #[arg(long, value_name = "DB", value_parser = parse_dbfs, allow_negative_numbers = true)]
+    peak_dbfs: Option<f32>,
+
+    /// Soft-limit the encoded audio so it never goes above this level in dBFS, easing peaks from 6 dB below it (after --peak-dbfs)
+    #[arg(long, value_name = "DB", value_parser = parse_dbfs, allow_negative_numbers = true)]
+    limit_dbfs: Option<f32>,
+}
+
+// The output device
+#[derive(clap::Args, Debug)]
+struct PlaybackOptions {
+    /// Play on the output device with this name (or a unique part of it) instead of the system default; see --list-output-devices (needs `audio`)
+    #[arg(long, value_name = "NAME")]
+    device: Option<String>,
+
+    /// Ask the sound device for a buffer of N frames (clamped to what it supports) instead of the host default; larger values stop a busy Raspberry Pi from dropping audio, at the cost of latency (needs `audio`)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(64..=65536))]
+    buffer_frames: Option<u32>,
+
+    /// With `serve`, start submissions scheduled with `at` this many ms early, for delay after the sound device its driver doesn't report (Bluetooth, USB DACs, mixers); measure it once per device (needs `audio`)
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u32).range(..=10000))]
+    output_latency_ms: Option<u32>,
+
+    /// List the output devices --device can pick, then exit (needs `audio`)
+    #[arg(long)]
+    list_output_devices: bool,
+
+    /// Play a 1 s, 1 kHz tone at --volume on the output device, then exit, to check the speaker before a session (needs `audio`)
+    #[arg(long)]
+    test_tone: bool,
+
+    /// Retry failed playback N more times, waiting 1 s, 2 s, 4 s, ... in between
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    retries: u32,
+}
+
+// The input device
+#[derive(clap::Args, Debug)]
+struct CaptureOptions {
+    /// Capture from the input device with this name (or a unique part of it) instead of the system default; see --list-input-devices (needs `audio`)
+    #[arg(long, value_name = "NAME")]
+    input_device: Option<String>,
+
+    /// Capture what the output device with this name (or `default`) plays instead of an input, to decode audio from other apps: its PulseAudio/PipeWire monitor source on Linux, WASAPI loopback on Windows; on macOS capture a virtual device such as BlackHole with --input-device (needs `audio`)
+    #[arg(long, value_name = "OUTPUT", conflicts_with = "input_device")]
+    monitor_of: Option<String>,
+
+    /// List the input devices --input-device can pick, with their sample rates, and the outputs --monitor-of can, then exit (needs `audio`)
+    #[arg(long)]
+    list_input_devices: bool,
+
+    /// Also write everything captured to this WAV (16-bit mono at the device rate) while decoding, to analyze failed transmissions offline with other settings (needs `audio`)
+    #[arg(long, value_name = "WAV")]
+    record_to: Option<PathBuf>,
+
+    /// When a message heard live fails to decode, save the last 30 s of audio to a WAV in this directory for a post-mortem (needs `audio`)
+    #[arg(long, value_name = "DIR")]
+    dump_failures: Option<PathBuf>,
+
+    /// While capturing, also print `{"type":"level","rms_dbfs":...,"peak_dbfs":...}` ten times a second, for input meters (needs `audio`)
+    #[arg(long, requires = "json")]
+    levels: bool,
+}
+
+// What wraps each outgoing message
+#[derive(clap::Args, Debug)]
+struct SealOptions {
+    /// Compress the payload before encoding (only if that makes it shorter); receivers detect and expand it without any flag
+    #[arg(long, value_enum, value_name = "METHOD", conflicts_with = "dict")]
+    compress: Option<Compression>,
+
+    /// Wrap the payload in a length + CRC-32 envelope; decoders check it and reject messages that arrive corrupted
+    #[arg(long)]
+    crc: bool,
+
+    /// Add Reed-Solomon parity chunks, N-K for every K data chunks, so receivers can rebuild chunks lost entirely: `rs(N,K)`, e.g. `rs(6,4)` (ggwave only)
+    #[arg(long, value_name = "CODE", value_parser = parse_rs_code)]
+    fec: Option<RsCode>,
+}
+
+// The text steps before encoding
+#[derive(clap::Args, Debug)]
+struct TextOptions {
+    /// Expand {hostname}, {env:VAR}, {now}, {now:%H:%M} and {unix} in the text at transmit time ({{ and }} for literal braces)
+    #[arg(long)]
+    template: bool,
+
+    /// Unicode normalization applied to text before encoding
+    #[arg(long, value_enum)]
+    normalize: Option<Normalization>,
+
+    /// Transliterate text to plain ASCII before encoding (e.g. "Grüße" -> "Grusse")
+    #[arg(long)]
+    ascii: bool,
+
+    /// Require the text to be well-formed JSON and minify it before encoding
+    #[arg(long)]
+    json_payload: bool,
+
+    /// Validate the JSON payload against this JSON Schema file (implies --json-payload)
+    #[arg(long, value_name = "FILE")]
+    json_schema: Option<PathBuf>,
+}
+
+// Raw PCM and spectrograms of a recording
+#[derive(clap::Args, Debug)]
+struct RecordingOptions {
+    /// Sample encoding of raw PCM for --out - and --decode-raw (mono)
+    #[arg(long, value_enum, default_value = "s16le")]
+    raw_format: RawFormat,
+
+    /// Sample rate of raw PCM for --out - and --decode-raw [default: 48000]; with --out - it sets the generated rate
+    #[arg(long, value_name = "HZ")]
+    raw_rate: Option<u32>,
+
+    /// Also render a spectrogram PNG of the generated signal (or, when decoding, of the recording) to check which band the tones use
+    #[arg(long, value_name = "PNG")]
+    spectrogram: Option<PathBuf>,
+}
+
+// What happens to a decoded message
+#[derive(clap::Args, Debug)]
+struct ReceivedOptions {
+    /// With --decode-wav, unwrap payloads that look like base64/base45 text and show the inner content
+    #[arg(long)]
+    unwrap: bool,
+
+    /// With --decode-wav or --listen, save received files (see --file) into DIR instead of just reporting them
+    #[arg(long, value_name = "DIR")]
+    save_dir: Option<PathBuf>,
+}
+
+// Where decoded bytes go besides the terminal
+#[derive(clap::Args, Debug)]
+struct OutputOptions {
+    /// With --decode-wav, write the raw decoded bytes to this file (`-` for stdout) instead of printing them
+    #[arg(long, value_name = "PATH")]
+    out_bin: Option<PathBuf>,
+
+    /// With --decode-wav, also hand each decoded message to this sink plugin command as a JSON line on stdin (repeatable)
+    #[arg(long, value_name = "CMD")]
+    sink: Vec<String>,
+}
+
+// Options only `tx` takes
+#[derive(clap::Args, Debug)]
+struct EncodeOptions {
+    /// Send this file instead of text; its name, size and SHA-256 travel with it
+    #[arg(long, value_name = "PATH", conflicts_with = "text")]
+    file: Option<PathBuf>,
+
+    /// How --text or stdin is read: text, or binary bytes sent as-is given as hex, base64 or raw (stdin read byte for byte)
+    #[arg(long, value_enum, default_value = "text", conflicts_with = "file")]
+    input_format: InputFormat,
+
+    /// Output WAV file path, or `-` for raw PCM on stdout (see --raw-format)
+    #[arg(short, long, default_value = "gibberlink.wav")]
+    out: PathBuf,
+
+    /// Encode each line of this file (`-` for stdin) as its own message, written to --out-dir and/or --concat instead of played
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["text", "file", "input_format", "reliable", "no_wav"])]
+    batch: Option<PathBuf>,
+
+    /// With --batch, write one WAV per line, named after the line number (0001.wav, 0002.wav, ...)
+    #[arg(long, value_name = "DIR", requires = "batch")]
+    out_dir: Option<PathBuf>,
+
+    /// With --batch, also write every message into this one WAV, separated by --gap of silence
+    #[arg(long, value_name = "WAV", requires = "batch")]
+    concat: Option<PathBuf>,
+
+    /// Silence between messages in --concat, in seconds
+    #[arg(long, value_name = "SECS", default_value_t = 1.0, requires = "concat")]
+    gap: f32,
+
+    /// Also render the payload as a QR code PNG at this path
+    #[arg(long, value_name = "PNG")]
+    qr: Option<PathBuf>,
 
     /// Play after generating
     #[arg(long, default_value_t = true)]
     play: bool,
 
-    /// Decode payload from WAV file and print as text
-    #[arg(long, value_name = "WAV")]
-    decode_wav: Option<PathBuf>,
+    /// Play straight from memory without writing --out (needs the `audio` feature)
+    #[arg(long)]
+    no_wav: bool,
+
+    /// Send over the air and wait for the receiver's acknowledgement, resending lost chunks (needs `audio`; the receiver runs --listen --ack)
+    #[arg(long, conflicts_with_all = ["no_wav", "fec"])]
+    reliable: bool,
+
+    /// Keep WAVs whose playback still failed in DIR and play them (oldest first) before the next transmission
+    #[arg(long, value_name = "DIR")]
+    retry_queue: Option<PathBuf>,
+
+    /// Train a zstd dictionary from a file of sample messages (one per line) and write it to --dict
+    #[arg(long, value_name = "SAMPLES", requires = "dict")]
+    train_dict: Option<PathBuf>,
 }
 
-fn parse_protocol(s: &str) -> i32 {
-    use ggwave_consts::*;
-    let (family, speed) = if let Some((a, b)) = s.split_once(':') { (a, b) } else { (s, "normal") };
-    match (family.to_ascii_lowercase().as_str(), speed.to_ascii_lowercase().as_str()) {
-        ("audible", "normal") => GGWAVE_PROTOCOL_AUDIBLE_NORMAL,
-        ("audible", "fast") => GGWAVE_PROTOCOL_AUDIBLE_FAST,
-        ("audible", "fastest") => GGWAVE_PROTOCOL_AUDIBLE_FASTEST,
-        ("ultrasound", "normal") => GGWAVE_PROTOCOL_ULTRASOUND_NORMAL,
-        ("ultrasound", "fast") => GGWAVE_PROTOCOL_ULTRASOUND_FAST,
-        ("ultrasound", "fastest") => GGWAVE_PROTOCOL_ULTRASOUND_FASTEST,
-        ("dt", "normal") => GGWAVE_PROTOCOL_DT_NORMAL,
-        ("dt", "fast") => GGWAVE_PROTOCOL_DT_FAST,
-        ("dt", "fastest") => GGWAVE_PROTOCOL_DT_FASTEST,
-        ("mt", "normal") => GGWAVE_PROTOCOL_MT_NORMAL,
-        ("mt", "fast") => GGWAVE_PROTOCOL_MT_FAST,
-        ("mt", "fastest") => GGWAVE_PROTOCOL_MT_FASTEST,
-        _ => GGWAVE_PROTOCOL_AUDIBLE_FAST,
-    }
-}
-
-fn write_wav(path: &PathBuf, sample_rate: u32, sample_format: i32, data: &[u8]) -> std::io::Result<()> {
-    let mut writer = BufWriter::new(File::create(path)?);
-    let num_channels: u16 = 1;
-    let bits_per_sample: u16 = match sample_format {
-        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16 => 16,
-        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_U8 => 8,
-        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32 => 32,
-        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_I8 => 8,
-        x if x == ggwave_consts::GGWAVE_SAMPLE_FORMAT_U16 => 16,
-        _ => 16,
-    };
-    let byte_rate: u32 = sample_rate * num_channels as u32 * (bits_per_sample as u32 / 8);
-    let block_align: u16 = num_channels * (bits_per_sample / 8);
-    let data_len = data.len() as u32;
-    let riff_chunk_size = 36 + data_len;
-
-    // RIFF header
-    writer.write_all(b"RIFF")?;
-    writer.write_all(&riff_chunk_size.to_le_bytes())?;
-    writer.write_all(b"WAVE")?;
-
-    // fmt subchunk
-    writer.write_all(b"fmt ")?;
-    writer.write_all(&16u32.to_le_bytes())?; // Subchunk1Size for PCM
-    writer.write_all(&1u16.to_le_bytes())?; // AudioFormat PCM
-    writer.write_all(&num_channels.to_le_bytes())?;
-    writer.write_all(&sample_rate.to_le_bytes())?;
-    writer.write_all(&byte_rate.to_le_bytes())?;
-    writer.write_all(&block_align.to_le_bytes())?;
-    writer.write_all(&bits_per_sample.to_le_bytes())?;
-
-    // data subchunk
-    writer.write_all(b"data")?;
-    writer.write_all(&data_len.to_le_bytes())?;
-    writer.write_all(data)?;
-    writer.flush()?;
-    Ok(())
+// Options only `rx` takes
+#[derive(clap::Args, Debug)]
+struct DecodeOptions {
+    /// With --decode-wav, find the protocol (and sample rate) the recording was made with and report it, instead of assuming the file's header and listening for everything (ggwave only)
+    #[arg(long, conflicts_with_all = ["rx_protocols", "payload_length", "samples_per_frame"])]
+    detect: bool,
+
+    /// With --decode-wav, stop after N messages (default: every message in the file)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_messages: Option<u64>,
+
+    /// With --decode-wav, print a summary (messages, failures by stage, frames per protocol, estimated SNR, audio processed, wall time) to stderr
+    #[arg(long)]
+    stats: bool,
 }
 
-#[derive(Debug)]
-struct WavData {
-    sample_rate: u32,
-    channels: u16,
-    bits_per_sample: u16,
-    format_tag: u16, // 1 = PCM, 3 = IEEE float
-    data: Vec<u8>,
-}
-
-fn read_le_u16(buf: &[u8]) -> u16 { u16::from_le_bytes([buf[0], buf[1]]) }
-fn read_le_u32(buf: &[u8]) -> u32 { u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) }
-
-fn read_wav(path: &std::path::Path) -> Result<WavData, String> {
-    let mut f = BufReader::new(File::open(path).map_err(|e| format!("open: {}", e))?);
-    let mut header = [0u8; 12];
-    f.read_exact(&mut header).map_err(|e| format!("read header: {}", e))?;
-    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
-        return Err("Not a RIFF/WAVE file".into());
-    }
-    let mut fmt_chunk_found = false;
-    let mut data_chunk_found = false;
-    let mut format_tag = 1u16;
-    let mut channels = 1u16;
-    let mut sample_rate = 44100u32;
-    let mut bits_per_sample = 16u16;
-    let mut data = Vec::new();
+// Options only `bench` takes
+#[derive(clap::Args, Debug)]
+struct BenchOptions {
+    /// With --bench, signal-to-noise ratios to test, in dB
+    #[arg(long, value_name = "DB", value_delimiter = ',', default_value = "30,20,10,5,0", allow_negative_numbers = true)]
+    snr: Vec<f32>,
 
-    loop {
-        let mut chunk_hdr = [0u8; 8];
-        if f.read_exact(&mut chunk_hdr).is_err() { break; }
-        let id = &chunk_hdr[0..4];
-        let len = read_le_u32(&chunk_hdr[4..8]) as usize;
-        let mut chunk = vec![0u8; len];
-        f.read_exact(&mut chunk).map_err(|e| format!("read chunk: {}", e))?;
-        if len % 2 == 1 { let mut pad = [0u8; 1]; let _ = f.read_exact(&mut pad); }
-        if id == b"fmt " {
-            if len < 16 { return Err("fmt chunk too small".into()); }
-            format_tag = read_le_u16(&chunk[0..2]);
-            channels = read_le_u16(&chunk[2..4]);
-            sample_rate = read_le_u32(&chunk[4..8]);
-            bits_per_sample = read_le_u16(&chunk[14..16]);
-            fmt_chunk_found = true;
-        } else if id == b"data" {
-            data = chunk;
-            data_chunk_found = true;
-        }
-        if fmt_chunk_found && data_chunk_found { break; }
-    }
-    if !fmt_chunk_found || !data_chunk_found {
-        return Err("Missing fmt or data chunk".into());
-    }
-    Ok(WavData { sample_rate, channels, bits_per_sample, format_tag, data })
-}
-
-fn downmix_to_mono(w: &WavData) -> Result<(i32, Vec<u8>), String> {
-    use ggwave_consts::*;
-    if w.channels == 1 {
-        let fmt = match (w.format_tag, w.bits_per_sample) {
-            (1, 8) => GGWAVE_SAMPLE_FORMAT_U8,
-            (1, 16) => GGWAVE_SAMPLE_FORMAT_I16,
-            (3, 32) => GGWAVE_SAMPLE_FORMAT_F32,
-            _ => return Err(format!("Unsupported WAV format tag {} bits {}", w.format_tag, w.bits_per_sample)),
-        };
-        return Ok((fmt, w.data.clone()));
-    }
-    match (w.format_tag, w.bits_per_sample) {
-        (1, 16) => {
-            let frame_count = w.data.len() / (2 * w.channels as usize);
-            let mut out = Vec::with_capacity(frame_count * 2);
-            for i in 0..frame_count {
-                let mut acc: i32 = 0;
-                for ch in 0..w.channels as usize {
-                    let idx = (i * w.channels as usize + ch) * 2;
-                    let s = i16::from_le_bytes([w.data[idx], w.data[idx+1]]) as i32;
-                    acc += s;
-                }
-                let avg = (acc / (w.channels as i32)).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-                out.extend_from_slice(&avg.to_le_bytes());
-            }
-            Ok((GGWAVE_SAMPLE_FORMAT_I16, out))
-        }
-        (1, 8) => {
-            let frame_count = w.data.len() / (1 * w.channels as usize);
-            let mut out = Vec::with_capacity(frame_count);
-            for i in 0..frame_count {
-                let mut acc: i32 = 0;
-                for ch in 0..w.channels as usize {
-                    let idx = i * w.channels as usize + ch;
-                    let s = w.data[idx] as i32;
-                    acc += s;
-                }
-                let avg = (acc / (w.channels as i32)).clamp(0, 255) as u8;
-                out.push(avg);
-            }
-            Ok((GGWAVE_SAMPLE_FORMAT_U8, out))
-        }
-        (3, 32) => {
-            let frame_count = w.data.len() / (4 * w.channels as usize);
-            let mut out = Vec::with_capacity(frame_count * 4);
-            for i in 0..frame_count {
-                let mut acc: f32 = 0.0;
-                for ch in 0..w.channels as usize {
-                    let idx = (i * w.channels as usize + ch) * 4;
-                    let s = f32::from_le_bytes([w.data[idx], w.data[idx+1], w.data[idx+2], w.data[idx+3]]);
-                    acc += s;
-                }
-                let avg = acc / (w.channels as f32);
-                out.extend_from_slice(&avg.to_le_bytes());
-            }
-            Ok((GGWAVE_SAMPLE_FORMAT_F32, out))
-        }
-        _ => Err(format!("Unsupported multi-channel WAV format tag {} bits {}", w.format_tag, w.bits_per_sample)),
-    }
+    /// With --bench, the kind of noise mixed in
+    #[arg(long, value_enum, default_value = "white")]
+    noise: NoiseKind,
+
+    /// With --bench, random payloads per protocol
+    #[arg(long, value_name = "N", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    trials: u32,
+
+    /// With --bench, length of each random payload in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 16, value_parser = clap::value_parser!(u64).range(1..))]
+    bench_bytes: u64,
 }
 
-fn decode_wav_with_ggwave(path: &std::path::Path) -> Result<Vec<u8>, String> {
-    let wav = read_wav(path)?;
-    let (sample_format_inp, mono_bytes) = downmix_to_mono(&wav)?;
-    unsafe {
-        let mut params = ggwave_getDefaultParameters();
-        params.operatingMode = ggwave_consts::GGWAVE_OPERATING_MODE_RX;
-        params.sampleFormatInp = sample_format_inp;
-        params.sampleRateInp = wav.sample_rate as f32;
-        params.sampleRate = wav.sample_rate as f32;
-
-        let instance = ggwave_init(params);
-        if instance < 0 { return Err("ggwave init failed".into()); }
-
-        let mut cap = 256usize;
-        let decoded = loop {
-            let mut out = vec![0u8; cap];
-            let n = ggwave_ndecode(
-                instance,
-                mono_bytes.as_ptr() as *const _,
-                mono_bytes.len() as c_int,
-                out.as_mut_ptr() as *mut _,
-                out.len() as c_int,
-            );
-            if n == -2 { cap *= 2; if cap > 65536 { break Err("Decoded payload too large".into()); } continue; }
-            if n <= 0 { break Err("No payload decoded".into()); }
-            out.truncate(n as usize);
-            break Ok(out);
-        };
-        ggwave_free(instance);
-        decoded
+// Options only `serve` takes
+#[derive(clap::Args, Debug)]
+struct ServeOptions {
+    /// Run as a sound modem daemon: serve WebSocket clients on this address (e.g. 127.0.0.1:9000), transmitting what they send and pushing every decoded message to them as JSON (needs `serve` and `audio`)
+    #[arg(long, value_name = "ADDR")]
+    ws: Option<String>,
+
+    /// Run as a sound modem daemon with an HTTP API on this address: POST /encode returns the WAV, POST /tx plays it, GET /rx/stream streams decoded messages as JSON lines and GET /events as server-sent events (needs `serve` and `audio`; may be combined with --ws)
+    #[arg(long, value_name = "ADDR")]
+    http: Option<String>,
+
+    /// Require --ws and --http clients to present the token in this file (or the GIBBERLINK_TOKEN environment variable), as `Authorization: Bearer TOKEN` or a `?token=TOKEN` query parameter
+    #[arg(long, value_name = "FILE")]
+    token_file: Option<PathBuf>,
+
+    /// Submissions each client (an address, or the MQTT, serial or UDP bridge's sender) may make per minute, in bursts of up to --rate-burst; more get a 429 or a `warning` event saying when to retry. 0 for no limit
+    #[arg(long, value_name = "N", default_value_t = 30)]
+    rate_limit: u32,
+
+    /// With --rate-limit, submissions a client may make back to back
+    #[arg(long, value_name = "N", default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    rate_burst: u32,
+
+    /// Serve --ws and --http over TLS (wss:// and https://) with this PEM certificate chain, leaf first (needs `tls`)
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// With --tls-cert, the certificate's PEM private key
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Serve --ws and --http over TLS with a certificate generated at startup for these DNS names or IP addresses, comma-separated; its SHA-256 fingerprint is printed for clients to check (needs `tls`)
+    #[arg(long, value_name = "NAMES", value_delimiter = ',', conflicts_with = "tls_cert")]
+    tls_self_signed: Vec<String>,
+
+    /// Run as a sound modem daemon bridged to the MQTT broker at HOST[:PORT] (port 1883 by default): messages published on --mqtt-tx-topic are played, decoded messages are published on --mqtt-rx-topic as JSON (needs `serve` and `audio`; may be combined with --ws and --http)
+    #[arg(long, value_name = "BROKER")]
+    mqtt: Option<String>,
+
+    /// With --mqtt, the topic whose messages are transmitted
+    #[arg(long, value_name = "TOPIC", default_value = "gibberlink/tx", requires = "mqtt")]
+    mqtt_tx_topic: String,
+
+    /// With --mqtt, the topic decoded messages are published on
+    #[arg(long, value_name = "TOPIC", default_value = "gibberlink/rx", requires = "mqtt")]
+    mqtt_rx_topic: String,
+
+    /// Run as a sound modem daemon bridged to this serial port (or `pty` for a new pseudo-terminal on Unix): bytes written to it at --serial-baud are transmitted once the writer pauses, received payloads are written back as-is (needs `serve` and `audio`; may be combined with --ws, --http and --mqtt)
+    #[arg(long, value_name = "PORT")]
+    serial: Option<String>,
+
+    /// With --serial, the port's baud rate
+    #[arg(long, value_name = "RATE", default_value_t = 9600, requires = "serial")]
+    serial_baud: u32,
+
+    /// Run as a sound modem daemon bridged to UDP on this address (a multicast group address joins the group): datagrams are transmitted, received payloads are sent to --udp-peer (needs `serve` and `audio`; may be combined with the other bridges)
+    #[arg(long, value_name = "ADDR")]
+    udp: Option<String>,
+
+    /// With --udp, where received payloads are sent [default: the group for a multicast --udp, else the sender of the last datagram]
+    #[arg(long, value_name = "ADDR", requires = "udp")]
+    udp_peer: Option<String>,
+
+    /// With --udp, only transmit datagrams from these hosts, comma-separated; needed unless --udp is a loopback address
+    #[arg(long, value_name = "IPS", value_delimiter = ',', requires = "udp")]
+    udp_from: Vec<std::net::IpAddr>,
+}
+
+impl ServeOptions {
+    // Whether any bridge was asked for, i.e. this is the `serve` daemon
+    fn any(&self) -> bool {
+        self.ws.is_some() || self.http.is_some() || self.mqtt.is_some() || self.serial.is_some() || self.udp.is_some()
     }
 }
 
-#[cfg(target_os = "windows")]
-fn play_wav_blocking(path: &std::path::Path) -> Result<(), String> {
-    use std::ffi::OsStr;
-    use std::os::windows::ffi::OsStrExt;
-    use std::ptr::null_mut;
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Encode text (or a file) to audio, write it and play it
+    Tx(TxCommand),
+    /// Decode every message in a recording (WAV, compressed audio, or raw PCM with --raw)
+    Rx(RxCommand),
+    /// Measure decode success rates under noise for each protocol
+    Bench(BenchCommand),
+    /// Decode from the microphone until interrupted (audio feature)
+    Listen(ListenCommand),
+    /// Measure the room's noise in each protocol family's band and recommend the quietest (audio and spectrogram features)
+    Spectrum(SpectrumCommand),
+    /// Transmit every .txt/.json file dropped into a directory, then move it to a done folder (watch and audio features)
+    Watch(WatchCommand),
+    /// Sound modem daemon: transmit payloads from network clients and send them every decoded message (serve and audio features)
+    Serve(ServeCommand),
+    /// Full-duplex chat: send lines typed on stdin, print lines heard on the microphone (audio feature)
+    Chat(ChatCommand),
+    /// Run a program as an acoustic service: decoded messages go to its stdin, each line it prints is transmitted (audio feature)
+    Bridge(BridgeCommand),
+    /// Answer what the microphone hears with an LLM: each turn goes to an OpenAI-compatible chat endpoint and the reply is transmitted (agent and audio features)
+    Agent(AgentCommand),
+}
+
+#[derive(clap::Args, Debug)]
+#[command(group(clap::ArgGroup::new("rate").args(["raw_rate", "sample_rate"])))]
+struct TxCommand {
+    /// Text to encode. If omitted, reads from stdin.
+    text: Option<String>,
 
-    const SND_SYNC: u32 = 0x0000;
-    const SND_FILENAME: u32 = 0x00020000;
+    #[command(flatten)]
+    common: CommonOptions,
 
-    #[link(name = "winmm")]
-    extern "system" {
-        fn PlaySoundW(pszSound: *const u16, hmod: *mut core::ffi::c_void, fdwSound: u32) -> i32;
-    }
+    #[command(flatten)]
+    encode: EncodeOptions,
 
-    let widestr: Vec<u16> = OsStr::new(path)
-        .encode_wide()
-        .chain(std::iter::once(0))
-        .collect();
+    #[command(flatten)]
+    signal: SignalOptions,
 
-    let ok = unsafe { PlaySoundW(widestr.as_ptr(), null_mut(), SND_SYNC | SND_FILENAME) };
-    if ok == 0 { Err("PlaySoundW failed".into()) } else { Ok(()) }
+    #[command(flatten)]
+    shaping: ShapingOptions,
+
+    #[command(flatten)]
+    recording: RecordingOptions,
+
+    #[command(flatten)]
+    playback: PlaybackOptions,
+
+    #[command(flatten)]
+    capture: CaptureOptions,
+
+    #[command(flatten)]
+    sealing: SealOptions,
+
+    #[command(flatten)]
+    steps: TextOptions,
 }
 
-#[cfg(not(target_os = "windows"))]
-fn play_wav_blocking(path: &std::path::Path) -> Result<(), String> {
-    // Fallback: try to spawn `ffplay` or `aplay` if available
-    let candidates = [
-        ("ffplay", &["-nodisp", "-autoexit"] as &[&str]),
-        ("aplay", &[] as &[&str]),
-        ("afplay", &[] as &[&str]),
-        ("paplay", &[] as &[&str]),
-    ];
-    for (cmd, args) in candidates {
-        if std::process::Command::new(cmd)
-            .args(args)
-            .arg(path)
-            .spawn()
-            .map(|mut c| c.wait().map(|s| s.success()).unwrap_or(false))
-            .unwrap_or(false)
-        {
-            return Ok(());
-        }
-    }
-    Err("No audio player found".into())
+#[derive(clap::Args, Debug)]
+struct RxCommand {
+    /// Recording to decode: WAV, or MP3/M4A/FLAC/Ogg with the codecs feature (`-` for stdin); or an Icecast/HTTP(S) or RTSP stream URL to decode live until it ends (stream feature; RTSP needs ffmpeg)
+    #[arg(value_name = "INPUT", required_unless_present = "decode_raw")]
+    decode_wav: Option<PathBuf>,
+
+    /// Decode raw mono PCM (see --raw-format and --raw-rate) from this file, or `-` for stdin
+    #[arg(long = "raw", value_name = "PATH", conflicts_with = "decode_wav")]
+    decode_raw: Option<PathBuf>,
+
+    #[command(flatten)]
+    common: CommonOptions,
+
+    #[command(flatten)]
+    decode: DecodeOptions,
+
+    #[command(flatten)]
+    receiver: ReceiverOptions,
+
+    #[command(flatten)]
+    recording: RecordingOptions,
+
+    #[command(flatten)]
+    received: ReceivedOptions,
+
+    #[command(flatten)]
+    output: OutputOptions,
 }
 
-fn main() {
-    let args = Args::parse();
-    unsafe { ggwave_setLogFile(std::ptr::null_mut()); }
+#[derive(clap::Args, Debug)]
+struct BenchCommand {
+    #[command(flatten)]
+    common: CommonOptions,
 
-    // Decode mode
-    if let Some(wav) = args.decode_wav.as_ref() {
-        match decode_wav_with_ggwave(wav.as_path()) {
-            Ok(bytes) => {
-                match String::from_utf8(bytes.clone()) {
-                    Ok(s) => { println!("{}", s); }
-                    Err(_) => {
-                        print!("0x");
-                        for b in bytes { print!("{:02x}", b); }
-                        println!();
-                    }
-                }
-                return;
-            }
-            Err(e) => {
-                eprintln!("Decode failed: {}", e);
-                std::process::exit(6);
-            }
-        }
-    }
+    #[command(flatten)]
+    benchmark: BenchOptions,
 
-    // Read text
-    let text = match args.text {
-        Some(t) => t,
-        None => {
-            let mut buf = String::new();
-            std::io::stdin().read_to_string(&mut buf).expect("failed to read stdin");
-            buf.trim_end().to_owned()
-        }
-    };
-    if text.is_empty() {
-        eprintln!("No text provided");
-        std::process::exit(1);
-    }
+    #[command(flatten)]
+    receiver: ReceiverOptions,
 
-    unsafe {
-        let mut params = ggwave_getDefaultParameters();
-        // TX only, mono 16-bit output
-        params.operatingMode = ggwave_consts::GGWAVE_OPERATING_MODE_TX;
-        params.sampleFormatOut = ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16;
-        if let Some(sr) = args.sample_rate { params.sampleRateOut = sr as f32; params.sampleRate = sr as f32; }
-
-        let instance = ggwave_init(params);
-        if instance < 0 {
-            eprintln!("Failed to init ggwave");
-            std::process::exit(2);
-        }
-
-        let payload = text.as_bytes();
-        let protocol = parse_protocol(&args.protocol);
-        let volume = args.volume.clamp(0, 100);
-
-        // Query size
-        let nbytes = ggwave_encode(
-            instance,
-            payload.as_ptr() as *const _,
-            payload.len() as c_int,
-            protocol,
-            volume,
-            std::ptr::null_mut(),
-            1,
-        );
-        if nbytes <= 0 {
-            ggwave_free(instance);
-            eprintln!("ggwave_encode size query failed");
-            std::process::exit(3);
-        }
+    #[command(flatten)]
+    signal: SignalOptions,
+}
 
-        let mut buf = vec![0u8; nbytes as usize];
-        let nwritten = ggwave_encode(
-            instance,
-            payload.as_ptr() as *const _,
-            payload.len() as c_int,
-            protocol,
-            volume,
-            buf.as_mut_ptr() as *mut _,
-            0,
-        );
-        if nwritten != nbytes {
-            ggwave_free(instance);
-            eprintln!("ggwave_encode wrote {} but expected {}", nwritten, nbytes);
-            std::process::exit(4);
-        }
+#[derive(clap::Args, Debug)]
+struct SpectrumCommand {
+    /// How long to record
+    #[arg(long, value_name = "N", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=3600))]
+    seconds: u32,
 
-        ggwave_free(instance);
+    #[command(flatten)]
+    common: CommonOptions,
 
-        // Write WAV
-        if let Err(e) = write_wav(&args.out, params.sampleRateOut as u32, params.sampleFormatOut, &buf) {
-            eprintln!("Failed to write WAV: {}", e);
-            std::process::exit(5);
-        }
+    #[command(flatten)]
+    capture: CaptureOptions,
+}
 
-        println!("Wrote {} bytes to {}", buf.len(), args.out.display());
+#[derive(clap::Args, Debug)]
+struct ListenCommand {
+    /// Answer --reliable senders with acknowledgements (transmits on the output device)
+    #[arg(long)]
+    ack: bool,
 
-        if args.play {
-            if let Err(e) = play_wav_blocking(&args.out) {
-                eprintln!("Playback failed: {}", e);
-            }
+    #[command(flatten)]
+    common: CommonOptions,
+
+    #[command(flatten)]
+    receiver: ReceiverOptions,
+
+    #[command(flatten)]
+    capture: CaptureOptions,
+
+    #[command(flatten)]
+    received: ReceivedOptions,
+
+    #[command(flatten)]
+    output: OutputOptions,
+
+    #[command(flatten)]
+    signal: SignalOptions,
+
+    #[command(flatten)]
+    shaping: ShapingOptions,
+
+    #[command(flatten)]
+    playback: PlaybackOptions,
+}
+
+#[derive(clap::Args, Debug)]
+struct WatchCommand {
+    /// Directory to watch
+    #[arg(value_name = "DIR")]
+    watch: PathBuf,
+
+    /// Where sent files are moved [default: DIR/done]
+    #[arg(long, value_name = "DIR")]
+    done_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    common: CommonOptions,
+
+    #[command(flatten)]
+    signal: SignalOptions,
+
+    #[command(flatten)]
+    shaping: ShapingOptions,
+
+    #[command(flatten)]
+    playback: PlaybackOptions,
+
+    #[command(flatten)]
+    sealing: SealOptions,
+
+    #[command(flatten)]
+    steps: TextOptions,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(group(clap::ArgGroup::new("bridge").args(["ws", "http", "mqtt", "serial", "udp"]).multiple(true).required(true)))]
+struct ServeCommand {
+    #[command(flatten)]
+    serve: ServeOptions,
+
+    #[command(flatten)]
+    common: CommonOptions,
+
+    #[command(flatten)]
+    signal: SignalOptions,
+
+    #[command(flatten)]
+    shaping: ShapingOptions,
+
+    #[command(flatten)]
+    playback: PlaybackOptions,
+
+    #[command(flatten)]
+    capture: CaptureOptions,
+
+    #[command(flatten)]
+    receiver: ReceiverOptions,
+
+    #[command(flatten)]
+    received: ReceivedOptions,
+
+    #[command(flatten)]
+    sealing: SealOptions,
+
+    #[command(flatten)]
+    steps: TextOptions,
+}
+
+#[derive(clap::Args, Debug)]
+struct ChatCommand {
+    /// The name other peers see for this end (at most 16 bytes; default: random)
+    #[arg(long, value_name = "NAME")]
+    peer_id: Option<String>,
+
+    #[command(flatten)]
+    common: CommonOptions,
+
+    #[command(flatten)]
+    signal: SignalOptions,
+
+    #[command(flatten)]
+    shaping: ShapingOptions,
+
+    #[command(flatten)]
+    playback: PlaybackOptions,
+
+    #[command(flatten)]
+    capture: CaptureOptions,
+
+    #[command(flatten)]
+    receiver: ReceiverOptions,
+
+    #[command(flatten)]
+    received: ReceivedOptions,
+
+    #[command(flatten)]
+    sealing: SealOptions,
+}
+
+#[derive(clap::Args, Debug)]
+struct BridgeCommand {
+    /// Program to run, and its arguments
+    #[arg(value_name = "COMMAND", required = true, last = true)]
+    command: Vec<String>,
+
+    #[command(flatten)]
+    common: CommonOptions,
+
+    #[command(flatten)]
+    signal: SignalOptions,
+
+    #[command(flatten)]
+    shaping: ShapingOptions,
+
+    #[command(flatten)]
+    playback: PlaybackOptions,
+
+    #[command(flatten)]
+    capture: CaptureOptions,
+
+    #[command(flatten)]
+    receiver: ReceiverOptions,
+
+    #[command(flatten)]
+    received: ReceivedOptions,
+
+    #[command(flatten)]
+    sealing: SealOptions,
+
+    #[command(flatten)]
+    steps: TextOptions,
+}
+
+// Options only `agent` takes
+#[derive(clap::Args, Debug, Clone)]
+struct AgentOptions {
+    /// OpenAI-compatible chat-completions endpoint (a local server such as Ollama or llama.cpp works too)
+    #[arg(long, value_name = "URL", default_value = "https://api.openai.com/v1/chat/completions")]
+    url: String,
+
+    /// Model to ask
+    #[arg(long, value_name = "NAME", required = true)]
+    model: String,
+
+    /// System prompt; a note of how long replies may be is added to it
+    #[arg(long, value_name = "PROMPT", default_value = "You are an AI agent talking to another agent over sound. Answer briefly, in plain text.")]
+    system: String,
+
+    /// Environment variable holding the API key, sent as a bearer token if set
+    #[arg(long, value_name = "VAR", default_value = "OPENAI_API_KEY")]
+    api_key_env: String,
+
+    /// Longest reply, in ggwave transmissions: 1 keeps it to a single unchunked one; longer replies are cut at a word
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..))]
+    reply_frames: u8,
+
+    /// Earlier exchanges sent along as context
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    history: usize,
+
+    /// Seconds without a new message before the agent takes its turn, so it doesn't answer a peer that is still sending
+    #[arg(long, value_name = "SECS", default_value_t = 1.5)]
+    turn_gap: f32,
+
+    /// Seconds to wait for the model's reply
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    timeout: u64,
+}
+
+#[derive(clap::Args, Debug)]
+struct AgentCommand {
+    #[command(flatten)]
+    agent: AgentOptions,
+
+    #[command(flatten)]
+    common: CommonOptions,
+
+    #[command(flatten)]
+    signal: SignalOptions,
+
+    #[command(flatten)]
+    shaping: ShapingOptions,
+
+    #[command(flatten)]
+    playback: PlaybackOptions,
+
+    #[command(flatten)]
+    capture: CaptureOptions,
+
+    #[command(flatten)]
+    receiver: ReceiverOptions,
+
+    #[command(flatten)]
+    received: ReceivedOptions,
+
+    #[command(flatten)]
+    sealing: SealOptions,
+
+    #[command(flatten)]
+    steps: TextOptions,
+}
+
+impl Command {
+    // The flat options this subcommand stands for; groups it doesn't take keep their defaults
+    fn into_args(self) -> Args {
+        let mut args = Args::parse_from(["gibberlink-tx"]);
+        match self {
+            Command::Tx(c) => {
+                args.text = c.text;
+                (args.common, args.encode, args.signal, args.shaping) = (c.common, c.encode, c.signal, c.shaping);
+                (args.recording, args.playback, args.capture, args.sealing, args.steps) = (c.recording, c.playback, c.capture, c.sealing, c.steps);
+            }
+            Command::Rx(c) => {
+                (args.decode_wav, args.decode_raw) = (c.decode_wav, c.decode_raw);
+                (args.common, args.decode, args.receiver) = (c.common, c.decode, c.receiver);
+                (args.recording, args.received, args.output) = (c.recording, c.received, c.output);
+            }
+            Command::Bench(c) => {
+                args.bench = true;
+                (args.common, args.benchmark, args.receiver, args.signal) = (c.common, c.benchmark, c.receiver, c.signal);
+            }
+            Command::Spectrum(c) => {
+                (args.spectrum, args.seconds) = (true, c.seconds);
+                (args.common, args.capture) = (c.common, c.capture);
+            }
+            Command::Listen(c) => {
+                (args.listen, args.ack) = (true, c.ack);
+                (args.common, args.receiver, args.capture, args.received, args.output) = (c.common, c.receiver, c.capture, c.received, c.output);
+                (args.signal, args.shaping, args.playback) = (c.signal, c.shaping, c.playback);
+            }
+            Command::Watch(c) => {
+                (args.watch, args.done_dir) = (Some(c.watch), c.done_dir);
+                (args.common, args.signal, args.shaping, args.playback, args.sealing, args.steps) = (c.common, c.signal, c.shaping, c.playback, c.sealing, c.steps);
+            }
+            Command::Serve(c) => {
+                (args.serve, args.common, args.signal, args.shaping, args.playback) = (c.serve, c.common, c.signal, c.shaping, c.playback);
+                (args.capture, args.receiver, args.received, args.sealing, args.steps) = (c.capture, c.receiver, c.received, c.sealing, c.steps);
+            }
+            Command::Chat(c) => {
+                (args.chat, args.peer_id) = (true, c.peer_id);
+                (args.common, args.signal, args.shaping, args.playback) = (c.common, c.signal, c.shaping, c.playback);
+                (args.capture, args.receiver, args.received, args.sealing) = (c.capture, c.receiver, c.received, c.sealing);
+            }
+            Command::Bridge(c) => {
+                args.bridge = c.command;
+                (args.common, args.signal, args.shaping, args.playback, args.capture) = (c.common, c.signal, c.shaping, c.playback, c.capture);
+                (args.receiver, args.received, args.sealing, args.steps) = (c.receiver, c.received, c.sealing, c.steps);
+            }
+            Command::Agent(c) => {
+                args.agent = Some(c.agent);
+                (args.common, args.signal, args.shaping, args.playback, args.capture) = (c.common, c.signal, c.shaping, c.playback, c.capture);
+                (args.receiver, args.received, args.sealing, args.steps) = (c.receiver, c.received, c.sealing, c.steps);
+            }
+        }
+        args
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Config {
+    max_freq_hz: Option<f32>,
+    #[serde(default)]
+    presets: std::collections::BTreeMap<String, Preset>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Preset {
+    protocol: Option<String>,
+    volume: Option<i32>,
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("gibberlink").join("presets.toml"))
+}
+
+fn load_config(path: &std::path::Path) -> Result<Config, String> {
+    let raw = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    toml::from_str(&raw).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Settings from the presets file; explicit flags always win. The file is optional unless
+// --preset/--config asks for it.
+fn apply_config(args: &mut Args, matches: &clap::ArgMatches) -> Result<(), String> {
+    let required = args.common.preset.is_some() || args.common.config.is_some();
+    let path = match args.common.config.clone().or_else(default_config_path) {
+        Some(path) if required || path.exists() => path,
+        None if required => return Err("no config directory on this platform".into()),
+        _ => return Ok(()),
+    };
+    let config = load_config(&path)?;
+    if args.shaping.max_freq_hz.is_none() {
+        args.shaping.max_freq_hz = config.max_freq_hz;
+    }
+    let Some(name) = args.common.preset.as_deref() else { return Ok(()) };
+    let preset = config.presets.get(name).ok_or_else(|| format!("preset {:?} not found in {}", name, path.display()))?;
+    let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    if let (Some(protocol), false) = (&preset.protocol, from_cli("protocol")) {
+        args.common.protocol = protocol.clone();
+    }
+    if let (Some(volume), false) = (preset.volume, from_cli("volume")) {
+        args.signal.volume = volume;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ModemKind {
+    Ggwave,
+    Afsk,
+    Minimodem,
+    Dtmf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Normalization {
+    Nfc,
+    Nfkc,
+}
+
+#[cfg(not(feature = "unicode"))]
+fn normalize_text(text: &str, _normalization: Option<Normalization>, _ascii: bool) -> String {
+    text.to_owned()
+}
+
+#[cfg(feature = "unicode")]
+fn normalize_text(text: &str, normalization: Option<Normalization>, ascii: bool) -> String {
+    use unicode_normalization::UnicodeNormalization;
+    let text = match normalization {
+        Some(Normalization::Nfc) => text.nfc().collect(),
+        Some(Normalization::Nfkc) => text.nfkc().collect(),
+        None => text.to_owned(),
+    };
+    if ascii { deunicode::deunicode(&text) } else { text }
+}
+
+// --fec rs(N,K): N chunks sent for every K carrying data
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(feature = "fec"), allow(dead_code))]
+struct RsCode {
+    n: usize,
+    k: usize,
+}
+
+// `rs(N,K)`, or just `N,K` to spare the shell quoting
+fn parse_rs_code(s: &str) -> Result<RsCode, String> {
+    let s = s.trim();
+    let inner = s.strip_prefix("rs(").and_then(|r| r.strip_suffix(')')).unwrap_or(s);
+    let (n, k) = inner.split_once(',').ok_or("expected rs(N,K), e.g. rs(6,4)")?;
+    let n: usize = n.trim().parse().map_err(|_| format!("invalid N {:?}", n.trim()))?;
+    let k: usize = k.trim().parse().map_err(|_| format!("invalid K {:?}", k.trim()))?;
+    if k == 0 || n <= k || n > u8::MAX as usize {
+        return Err("rs(N,K) needs 0 < K < N <= 255".into());
+    }
+    Ok(RsCode { n, k })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    Text,
+    Hex,
+    Base64,
+    Raw,
+}
+
+// Binary payloads given as hex (whitespace and a 0x prefix allowed), base64 or raw bytes
+fn parse_binary_input(input: &[u8], format: InputFormat) -> Result<Vec<u8>, String> {
+    let bytes = match format {
+        InputFormat::Text | InputFormat::Raw => input.to_vec(),
+        InputFormat::Hex | InputFormat::Base64 => {
+            let text = std::str::from_utf8(input).map_err(|_| "input is not ASCII".to_string())?;
+            let compact: String = text.split_whitespace().collect();
+            if format == InputFormat::Base64 {
+                base64_decode(&compact).ok_or("invalid base64")?
+            } else {
+                let digits = compact.strip_prefix("0x").or_else(|| compact.strip_prefix("0X")).unwrap_or(&compact);
+                if !digits.len().is_multiple_of(2) {
+                    return Err("odd number of hex digits".into());
+                }
+                (0..digits.len())
+                    .step_by(2)
+                    .map(|i| digits.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or("invalid hex digit")?
+            }
+        }
+    };
+    if bytes.is_empty() {
+        return Err("empty payload".into());
+    }
+    Ok(bytes)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Compression {
+    Zstd,
+    Deflate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum NoiseKind {
+    White,
+    Pink,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum RawFormat {
+    S16le,
+    F32le,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum PayloadFormat {
+    Text,
+    Cbor,
+    Msgpack,
+    Protobuf,
+}
+
+// Builds without a format/feature keep the flag but reject it at startup; see missing_feature()
+fn without(feature: &str) -> String {
+    format!("this build of gibberlink-tx was compiled without the `{}` feature", feature)
+}
+
+#[cfg(feature = "protobuf")]
+type ProtoMessage = prost_reflect::MessageDescriptor;
+#[cfg(not(feature = "protobuf"))]
+type ProtoMessage = std::convert::Infallible;
+
+#[cfg(not(feature = "protobuf"))]
+fn load_proto_message(_descriptor: Option<&std::path::Path>, _message: Option<&str>) -> Result<ProtoMessage, String> {
+    Err(without("protobuf"))
+}
+
+#[cfg(feature = "protobuf")]
+fn load_proto_message(descriptor: Option<&std::path::Path>, message: Option<&str>) -> Result<ProtoMessage, String> {
+    let (Some(descriptor), Some(message)) = (descriptor, message) else {
+        return Err("protobuf needs --proto-descriptor and --proto-message".into());
+    };
+    let bytes = std::fs::read(descriptor).map_err(|e| format!("read descriptor: {}", e))?;
+    let pool = prost_reflect::DescriptorPool::decode(bytes.as_slice()).map_err(|e| format!("descriptor: {}", e))?;
+    pool.get_message_by_name(message).ok_or_else(|| format!("message type {} not found in descriptor", message))
+}
+
+fn encode_payload(text: &str, format: PayloadFormat, proto: Option<&ProtoMessage>) -> Result<Vec<u8>, String> {
+    if format == PayloadFormat::Text {
+        return Ok(text.as_bytes().to_vec());
+    }
+    #[cfg(not(feature = "protobuf"))]
+    let _ = proto;
+    #[cfg(feature = "protobuf")]
+    if let (PayloadFormat::Protobuf, Some(desc)) = (format, proto) {
+        use prost_reflect::prost::Message;
+        let mut de = serde_json::Deserializer::from_str(text);
+        let msg = prost_reflect::DynamicMessage::deserialize(desc.clone(), &mut de).map_err(|e| format!("protobuf: {}", e))?;
+        de.end().map_err(|e| format!("invalid JSON: {}", e))?;
+        return Ok(msg.encode_to_vec());
+    }
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+    #[cfg(not(any(feature = "cbor", feature = "msgpack")))]
+    let _ = value;
+    match format {
+        #[cfg(feature = "cbor")]
+        PayloadFormat::Cbor => {
+            let mut out = Vec::new();
+            ciborium::into_writer(&value, &mut out).map_err(|e| format!("cbor: {}", e))?;
+            Ok(out)
+        }
+        #[cfg(feature = "msgpack")]
+        PayloadFormat::Msgpack => rmp_serde::to_vec(&value).map_err(|e| format!("msgpack: {}", e)),
+        other => Err(without(&format!("{:?}", other).to_lowercase())),
+    }
+}
+
+#[cfg(not(feature = "template"))]
+fn expand_template(_template: &str) -> Result<String, String> {
+    Err(without("template"))
+}
+
+#[cfg(feature = "template")]
+fn expand_template(template: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err("unmatched '}' in template".into());
+        }
+        let end = tail.find('}').ok_or("unclosed '{' in template")?;
+        out.push_str(&expand_placeholder(&tail[1..end])?);
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(feature = "template")]
+fn expand_placeholder(name: &str) -> Result<String, String> {
+    use chrono::format::{Item, StrftimeItems};
+    let (key, arg) = match name.split_once(':') {
+        Some((k, a)) => (k, Some(a)),
+        None => (name, None),
+    };
+    match (key, arg) {
+        ("hostname", None) => Ok(gethostname::gethostname().to_string_lossy().into_owned()),
+        ("env", Some(var)) => std::env::var(var).map_err(|_| format!("environment variable {} is not set", var)),
+        ("now", fmt) => {
+            let fmt = fmt.unwrap_or("%Y-%m-%dT%H:%M:%S%z");
+            if StrftimeItems::new(fmt).any(|i| matches!(i, Item::Error)) {
+                return Err(format!("invalid time format {:?}", fmt));
+            }
+            Ok(chrono::Local::now().format(fmt).to_string())
+        }
+        ("unix", None) => Ok(chrono::Utc::now().timestamp().to_string()),
+        _ => Err(format!("unknown placeholder {{{}}}", name)),
+    }
+}
+
+#[cfg(not(feature = "script"))]
+enum Script {}
+
+#[cfg(not(feature = "script"))]
+impl Script {
+    fn load(_path: &std::path::Path) -> Result<Self, String> {
+        Err(without("script"))
+    }
+
+    fn call(&self, _hook: &str, _text: &str) -> Result<Option<String>, String> {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "script")]
+struct Script {
+    engine: rhai::Engine,
+    ast: rhai::AST,
+}
+
+#[cfg(feature = "script")]
+impl Script {
+    fn load(path: &std::path::Path) -> Result<Self, String> {
+        let mut engine = rhai::Engine::new();
+        engine.register_fn("expand", |template: &str| -> Result<String, Box<rhai::EvalAltResult>> {
+            expand_template(template).map_err(|e| e.into())
+        });
+        let ast = engine.compile_file(path.to_path_buf()).map_err(|e| e.to_string())?;
+        Ok(Script { engine, ast })
+    }
+
+    // Hooks are optional: a script without the function passes the text through unchanged
+    fn call(&self, hook: &str, text: &str) -> Result<Option<String>, String> {
+        if !self.ast.iter_functions().any(|f| f.name == hook && f.params.len() == 1) {
+            return Ok(Some(text.to_owned()));
+        }
+        let result: rhai::Dynamic = self
+            .engine
+            .call_fn(&mut rhai::Scope::new(), &self.ast, hook, (text.to_owned(),))
+            .map_err(|e| format!("{}: {}", hook, e))?;
+        if result.is_unit() { Ok(None) } else { Ok(Some(result.to_string())) }
+    }
+}
+
+fn prepare_json(text: &str, schema: Option<&std::path::Path>) -> Result<String, String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+    #[cfg(not(feature = "json-schema"))]
+    if schema.is_some() {
+        return Err(without("json-schema"));
+    }
+    #[cfg(feature = "json-schema")]
+    if let Some(path) = schema {
+        let raw = std::fs::read_to_string(path).map_err(|e| format!("read schema: {}", e))?;
+        let schema: serde_json::Value = serde_json::from_str(&raw).map_err(|e| format!("invalid schema: {}", e))?;
+        jsonschema::validate(&schema, &value).map_err(|e| format!("schema violation at {}: {}", e.instance_path, e))?;
+    }
+    Ok(value.to_string())
+}
+
+#[cfg(not(feature = "dict"))]
+fn train_dict(_samples_path: &std::path::Path, _dict_path: &std::path::Path) -> Result<usize, String> {
+    Err(without("dict"))
+}
+
+#[cfg(not(feature = "dict"))]
+fn dict_compress(_data: &[u8], _dict: &[u8]) -> Result<Vec<u8>, String> {
+    Err(without("dict"))
+}
+
+#[cfg(all(feature = "decode", not(feature = "dict")))]
+fn dict_decompress(_data: &[u8], _dict: &[u8]) -> Result<Vec<u8>, String> {
+    Err(without("dict"))
+}
+
+#[cfg(feature = "dict")]
+const DICT_MAX_SIZE: usize = 16 * 1024;
+
+#[cfg(feature = "dict")]
+fn train_dict(samples_path: &std::path::Path, dict_path: &std::path::Path) -> Result<usize, String> {
+    let raw = std::fs::read_to_string(samples_path).map_err(|e| format!("read samples: {}", e))?;
+    let samples: Vec<&[u8]> = raw.lines().filter(|l| !l.is_empty()).map(str::as_bytes).collect();
+    let dict = zstd::dict::from_samples(&samples, DICT_MAX_SIZE).map_err(|e| format!("train: {}", e))?;
+    std::fs::write(dict_path, &dict).map_err(|e| format!("write dict: {}", e))?;
+    Ok(dict.len())
+}
+
+#[cfg(feature = "dict")]
+fn dict_compress(data: &[u8], dict: &[u8]) -> Result<Vec<u8>, String> {
+    use zstd::stream::raw::CParameter;
+    let mut c = zstd::bulk::Compressor::with_dictionary(19, dict).map_err(|e| format!("zstd: {}", e))?;
+    // Every byte costs airtime: drop the optional checksum and dictionary ID from the frame header
+    c.set_parameter(CParameter::ChecksumFlag(false)).map_err(|e| format!("zstd: {}", e))?;
+    c.set_parameter(CParameter::DictIdFlag(false)).map_err(|e| format!("zstd: {}", e))?;
+    c.compress(data).map_err(|e| format!("zstd: {}", e))
+}
+
+#[cfg(all(feature = "decode", feature = "dict"))]
+fn dict_decompress(data: &[u8], dict: &[u8]) -> Result<Vec<u8>, String> {
+    let mut d = zstd::bulk::Decompressor::with_dictionary(dict).map_err(|e| format!("zstd: {}", e))?;
+    d.decompress(data, 65536).map_err(|e| format!("zstd: {}", e))
+}
+
+// Compressed payloads start with one marker byte per method (reserved in gibberlink_core::framing)
+const DEFLATE_MAGIC: u8 = 0x16;
+const ZSTD_MAGIC: u8 = 0x17;
+// Expanded size cap, so a corrupted or hostile payload can't exhaust memory
+#[cfg(all(feature = "decode", feature = "compress"))]
+const DECOMPRESSED_MAX: usize = 1 << 20;
+
+#[cfg(not(feature = "compress"))]
+fn compress(_data: &[u8], _method: Compression) -> Result<Vec<u8>, String> {
+    Err(without("compress"))
+}
+
+#[cfg(feature = "compress")]
+fn compress(data: &[u8], method: Compression) -> Result<Vec<u8>, String> {
+    match method {
+        Compression::Zstd => {
+            use zstd::stream::raw::CParameter;
+            let mut c = zstd::bulk::Compressor::new(19).map_err(|e| format!("zstd: {}", e))?;
+            c.set_parameter(CParameter::ChecksumFlag(false)).map_err(|e| format!("zstd: {}", e))?;
+            let mut out = vec![ZSTD_MAGIC];
+            out.extend(c.compress(data).map_err(|e| format!("zstd: {}", e))?);
+            Ok(out)
+        }
+        Compression::Deflate => {
+            let mut encoder = flate2::write::DeflateEncoder::new(vec![DEFLATE_MAGIC], flate2::Compression::best());
+            encoder.write_all(data).map_err(|e| format!("deflate: {}", e))?;
+            encoder.finish().map_err(|e| format!("deflate: {}", e))
+        }
+    }
+}
+
+// `None` unless the payload carries a compression marker
+#[cfg(all(feature = "decode", not(feature = "compress")))]
+fn decompress(bytes: &[u8]) -> Option<Result<Vec<u8>, String>> {
+    matches!(bytes, [DEFLATE_MAGIC | ZSTD_MAGIC, _, ..]).then(|| Err(format!("message is compressed; {}", without("compress"))))
+}
+
+#[cfg(all(feature = "decode", feature = "compress"))]
+fn decompress(bytes: &[u8]) -> Option<Result<Vec<u8>, String>> {
+    match bytes {
+        [ZSTD_MAGIC, data @ ..] if !data.is_empty() => {
+            Some(zstd::bulk::decompress(data, DECOMPRESSED_MAX).map_err(|e| format!("zstd: {}", e)))
+        }
+        [DEFLATE_MAGIC, data @ ..] if !data.is_empty() => {
+            let mut out = Vec::new();
+            let read = flate2::read::DeflateDecoder::new(data).take(DECOMPRESSED_MAX as u64 + 1).read_to_end(&mut out);
+            Some(match read {
+                Ok(_) if out.len() > DECOMPRESSED_MAX => Err(format!("deflate: expands past {} bytes", DECOMPRESSED_MAX)),
+                Ok(_) => Ok(out),
+                Err(e) => Err(format!("deflate: {}", e)),
+            })
+        }
+        _ => None,
+    }
+}
+
+// Encrypted envelope: 0x1F | version | [16-byte salt] | 12-byte nonce | AES-256-GCM ciphertext and
+// tag. Version 2 (passphrases) carries the salt the key was stretched with; version 1 (--key-file
+// keys) has none. Plaintext payloads starting with 0x1F are escaped (gibberlink_core::framing).
+const ENVELOPE_MAGIC: u8 = 0x1F;
+#[cfg(feature = "crypto")]
+const ENVELOPE_UNSALTED: u8 = 1;
+#[cfg(feature = "crypto")]
+const ENVELOPE_SALTED: u8 = 2;
+#[cfg(feature = "crypto")]
+const SALT_LEN: usize = 16;
+const ENVELOPE_OVERHEAD: usize = 2 + 12 + 16;
+
+#[cfg(not(feature = "crypto"))]
+type Key = [u8; 32];
+
+/// --key is kept as the passphrase, stretched per message with a random salt; --key-file is the key
+#[cfg(feature = "crypto")]
+enum Key {
+    Passphrase(String),
+    Raw([u8; 32]),
+}
+
+#[cfg(feature = "decode")]
+fn is_envelope(bytes: &[u8]) -> bool {
+    bytes.len() >= ENVELOPE_OVERHEAD && bytes[0] == ENVELOPE_MAGIC
+}
+
+#[cfg(not(feature = "crypto"))]
+fn load_key(_passphrase: Option<&str>, _key_file: Option<&std::path::Path>) -> Result<Option<Key>, String> {
+    Ok(None)
+}
+
+#[cfg(not(feature = "crypto"))]
+fn encrypt(_key: &Key, _plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    Err(without("crypto"))
+}
+
+#[cfg(all(feature = "decode", not(feature = "crypto")))]
+fn decrypt(_key: &Key, _envelope: &[u8]) -> Result<Vec<u8>, String> {
+    Err(without("crypto"))
+}
+
+#[cfg(feature = "crypto")]
+const KEY_ROUNDS: u32 = 100_000;
+
+#[cfg(feature = "crypto")]
+fn stretch(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<sha2::Sha256, 32>(passphrase.as_bytes(), salt, KEY_ROUNDS)
+}
+
+#[cfg(feature = "crypto")]
+fn load_key(passphrase: Option<&str>, key_file: Option<&std::path::Path>) -> Result<Option<Key>, String> {
+    if let Some(passphrase) = passphrase {
+        return Ok(Some(Key::Passphrase(passphrase.to_owned())));
+    }
+    let Some(path) = key_file else { return Ok(None) };
+    let raw = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if let Ok(key) = <[u8; 32]>::try_from(raw.as_slice()) {
+        return Ok(Some(Key::Raw(key)));
+    }
+    let hex = std::str::from_utf8(&raw).unwrap_or("").trim();
+    let bytes: Option<Vec<u8>> = (hex.len() == 64)
+        .then(|| (0..64).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect())
+        .flatten();
+    match bytes {
+        Some(bytes) => Ok(Some(Key::Raw(bytes.try_into().expect("64 hex digits are 32 bytes")))),
+        None => Err(format!("{}: expected a 32-byte key or 64 hex digits", path.display())),
+    }
+}
+
+#[cfg(feature = "crypto")]
+fn encrypt(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    use aes_gcm::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload};
+    // The header (with the salt) is authenticated along with the ciphertext
+    let (header, key) = match key {
+        Key::Passphrase(passphrase) => {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            ([&[ENVELOPE_MAGIC, ENVELOPE_SALTED][..], &salt].concat(), stretch(passphrase, &salt))
+        }
+        Key::Raw(key) => (vec![ENVELOPE_MAGIC, ENVELOPE_UNSALTED], *key),
+    };
+    let cipher = aes_gcm::Aes256Gcm::new(&key.into());
+    let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+    let sealed = cipher.encrypt(&nonce, Payload { msg: plaintext, aad: &header }).map_err(|_| "encryption failed".to_string())?;
+    Ok([&header[..], &nonce[..], &sealed[..]].concat())
+}
+
+#[cfg(all(feature = "decode", feature = "crypto"))]
+fn decrypt(key: &Key, envelope: &[u8]) -> Result<Vec<u8>, String> {
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    let (header_len, key) = match (envelope[1], key) {
+        (ENVELOPE_SALTED, Key::Passphrase(passphrase)) if envelope.len() >= ENVELOPE_OVERHEAD + SALT_LEN => {
+            (2 + SALT_LEN, stretch(passphrase, &envelope[2..2 + SALT_LEN]))
+        }
+        (ENVELOPE_SALTED, Key::Passphrase(_)) => return Err("encrypted envelope too short".into()),
+        (ENVELOPE_SALTED, Key::Raw(_)) => return Err("message was encrypted with a passphrase; pass --key instead of --key-file".into()),
+        (ENVELOPE_UNSALTED, Key::Passphrase(_)) => return Err("message was encrypted with a key file; pass --key-file instead of --key".into()),
+        (ENVELOPE_UNSALTED, Key::Raw(key)) => (2, *key),
+        (version, _) => return Err(format!("unsupported encrypted envelope version {}", version)),
+    };
+    let cipher = aes_gcm::Aes256Gcm::new(&key.into());
+    let nonce = aes_gcm::Nonce::from_slice(&envelope[header_len..header_len + 12]);
+    cipher
+        .decrypt(nonce, Payload { msg: &envelope[header_len + 12..], aad: &envelope[..header_len] })
+        .map_err(|_| "decryption failed (wrong key or corrupted message)".to_string())
+}
+
+#[cfg(feature = "decode")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PayloadKind {
+    Text,
+    Json,
+    Binary,
+}
+
+#[cfg(feature = "decode")]
+impl std::fmt::Display for PayloadKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PayloadKind::Text => "UTF-8 text",
+            PayloadKind::Json => "JSON",
+            PayloadKind::Binary => "binary",
+        })
+    }
+}
+
+#[cfg(feature = "decode")]
+fn classify_payload(bytes: &[u8]) -> PayloadKind {
+    let Ok(s) = std::str::from_utf8(bytes) else { return PayloadKind::Binary };
+    if s.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        return PayloadKind::Binary;
+    }
+    let t = s.trim_start();
+    if (t.starts_with('{') || t.starts_with('[')) && serde_json::from_str::<serde_json::Value>(s).is_ok() {
+        PayloadKind::Json
+    } else {
+        PayloadKind::Text
+    }
+}
+
+#[cfg(feature = "decode")]
+const BASE45_ALPHABET: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+#[cfg(feature = "decode")]
+fn base45_decode(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u32> = s.bytes().map(|b| BASE45_ALPHABET.iter().position(|&a| a == b).map(|p| p as u32)).collect::<Option<_>>()?;
+    if digits.len() % 3 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(digits.len() / 3 * 2 + 1);
+    for chunk in digits.chunks(3) {
+        match *chunk {
+            [c, d, e] => {
+                let n = c + d * 45 + e * 45 * 45;
+                if n > 0xffff { return None; }
+                out.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            [c, d] => {
+                let n = c + d * 45;
+                if n > 0xff { return None; }
+                out.push(n as u8);
+            }
+            _ => unreachable!(),
+        }
+    }
+    Some(out)
+}
+
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+    use base64::Engine;
+    [STANDARD, URL_SAFE, STANDARD_NO_PAD, URL_SAFE_NO_PAD].iter().find_map(|e| e.decode(s).ok())
+}
+
+#[cfg(feature = "decode")]
+// Some ggwave frontends wrap binary data as base64/base45 text before sending
+fn unwrap_encoded(bytes: &[u8]) -> Option<(&'static str, Vec<u8>)> {
+    let s = std::str::from_utf8(bytes).ok()?.trim();
+    if s.len() < 4 {
+        return None;
+    }
+    // Base45 is upper-case only; anything with lower case can only be base64
+    let base45 = || base45_decode(s).map(|b| ("base45", b));
+    let base64 = || base64_decode(s).map(|b| ("base64", b));
+    if s.bytes().any(|b| b.is_ascii_lowercase()) {
+        base64()
+    } else {
+        base45().or_else(base64)
+    }
+}
+
+// Events handed to sinks and printed by --json, one JSON object per line; the enum is the schema
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event {
+    #[cfg(feature = "decode")]
+    Decoded { kind: PayloadKind, text: Option<String>, base64: String },
+    #[cfg(feature = "decode")]
+    DecodeSummary {
+        messages: usize,
+        failures: usize,
+        failures_by_stage: std::collections::BTreeMap<String, usize>,
+        modem: String,
+        protocol: Option<String>,
+        frames_by_protocol: std::collections::BTreeMap<String, usize>,
+        snr_db: Option<f32>,
+        sample_rate: u32,
+        duration_s: f64,
+        elapsed_ms: f64,
+    },
+    #[cfg(feature = "decode")]
+    Bench { setting: String, snr_db: f32, trials: u32, successes: usize, bytes_per_s: f64 },
+    #[cfg(feature = "decode")]
+    Chat { time: String, peer: String, text: String },
+    #[cfg(feature = "decode")]
+    Warning { message: String },
+    // A chunked message, from its first chunk to reassembly
+    #[cfg(feature = "decode")]
+    ReceiveBegin { id: u8, count: usize },
+    #[cfg(feature = "decode")]
+    ReceiveProgress { id: u8, received: usize, count: usize },
+    #[cfg(feature = "decode")]
+    ReceiveEnd { id: u8, ok: bool, error: Option<String> },
+    #[cfg(feature = "audio")]
+    Level { rms_dbfs: f32, peak_dbfs: f32 },
+    #[cfg(all(feature = "audio", feature = "spectrogram"))]
+    Spectrum { sample_rate: u32, seconds: u32, bands: Vec<spectrogram::BandNoise>, cleanest: Option<String> },
+    // What `serve` has waiting to transmit, whenever that changes
+    #[cfg(all(feature = "serve", feature = "audio", feature = "decode"))]
+    Queue(serve::QueueStatus),
+    Encoded {
+        base64: String,
+        bytes: usize,
+        frames: usize,
+        modem: String,
+        protocol: Option<String>,
+        sample_rate: u32,
+        duration_s: f64,
+        encode_ms: f64,
+        out: Option<PathBuf>,
+    },
+}
+
+fn encoded_event(args: &Args, payload: &[u8], frames: usize, waveform: &Waveform, encode_time: std::time::Duration, out: Option<PathBuf>) -> Event {
+    use base64::Engine;
+    Event::Encoded {
+        base64: base64::engine::general_purpose::STANDARD.encode(payload),
+        bytes: payload.len(),
+        frames,
+        modem: modem_name(args.common.modem),
+        protocol: (args.common.modem == ModemKind::Ggwave).then(|| args.common.protocol.clone()),
+        sample_rate: waveform.sample_rate,
+        duration_s: waveform.samples.len() as f64 / waveform.sample_rate as f64,
+        encode_ms: encode_time.as_secs_f64() * 1000.0,
+        out,
+    }
+}
+
+fn print_event(event: &Event) {
+    println!("{}", serde_json::to_string(event).expect("events always serialize"));
+}
+
+fn modem_name(modem: ModemKind) -> String {
+    modem.to_possible_value().map(|v| v.get_name().to_owned()).unwrap_or_default()
+}
+
+// Human-readable progress goes to stderr under --json so stdout stays parseable
+fn status(json: bool, message: std::fmt::Arguments) {
+    if json {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+// A message that could not be decoded: a `warning` event under --json, otherwise a line on stderr
+#[cfg(feature = "decode")]
+fn report_failure(json: bool, message: &str) {
+    if json {
+        print_event(&Event::Warning { message: format!("decode failed: {}", message) });
+    } else {
+        eprintln!("Decode failed: {}", message);
+    }
+}
+
+// Chunked-message progress: the receive_* event under --json, otherwise a line on stderr.
+// A failed end is left to report_failure().
+#[cfg(feature = "decode")]
+fn report_progress(json: bool, event: Event) {
+    if json {
+        print_event(&event);
+        return;
+    }
+    match event {
+        Event::ReceiveBegin { id, count } => eprintln!("Message {}: receiving {} chunks", id, count),
+        Event::ReceiveProgress { id, received, count } => eprintln!("Message {}: chunk {}/{}", id, received, count),
+        Event::ReceiveEnd { id, ok: true, .. } => eprintln!("Message {}: complete", id),
+        _ => {}
+    }
+}
+
+#[cfg(feature = "decode")]
+fn decoded_event(bytes: &[u8]) -> Event {
+    use base64::Engine;
+    Event::Decoded {
+        kind: classify_payload(bytes),
+        text: std::str::from_utf8(bytes).ok().map(str::to_owned),
+        base64: base64::engine::general_purpose::STANDARD.encode(bytes),
+    }
+}
+
+#[cfg(feature = "decode")]
+// Sink plugins are plain programs reading JSONL events on stdin, run through the platform shell
+fn run_sink(cmd: &str, event: &Event) -> Result<(), String> {
+    let line = serde_json::to_string(event).map_err(|e| format!("serialize: {}", e))?;
+    let mut child = if cfg!(target_os = "windows") {
+        std::process::Command::new("cmd").args(["/C", cmd]).stdin(std::process::Stdio::piped()).spawn()
+    } else {
+        std::process::Command::new("sh").args(["-c", cmd]).stdin(std::process::Stdio::piped()).spawn()
+    }
+    .map_err(|e| format!("spawn: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", line).map_err(|e| format!("write: {}", e))?;
+    }
+    let status = child.wait().map_err(|e| format!("wait: {}", e))?;
+    if status.success() { Ok(()) } else { Err(format!("exited with {}", status)) }
+}
+
+#[cfg(feature = "decode")]
+fn decode_payload(bytes: &[u8], format: PayloadFormat, proto: Option<&ProtoMessage>) -> Result<String, String> {
+    match format {
+        PayloadFormat::Text => match classify_payload(bytes) {
+            PayloadKind::Text => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            PayloadKind::Json => serde_json::from_slice::<serde_json::Value>(bytes)
+                .and_then(|v| serde_json::to_string_pretty(&v))
+                .map_err(|e| e.to_string()),
+            PayloadKind::Binary => Err("binary payload".into()),
+        },
+        #[cfg(feature = "protobuf")]
+        PayloadFormat::Protobuf => {
+            let desc = proto.ok_or("missing protobuf message type")?.clone();
+            let msg = prost_reflect::DynamicMessage::decode(desc, bytes).map_err(|e| format!("protobuf: {}", e))?;
+            serde_json::to_string(&msg).map_err(|e| format!("protobuf: {}", e))
+        }
+        #[cfg(feature = "cbor")]
+        PayloadFormat::Cbor => ciborium::from_reader::<serde_json::Value, _>(bytes)
+            .map(|v| v.to_string())
+            .map_err(|e| format!("cbor: {}", e)),
+        #[cfg(feature = "msgpack")]
+        PayloadFormat::Msgpack => rmp_serde::from_slice::<serde_json::Value>(bytes)
+            .map(|v| v.to_string())
+            .map_err(|e| format!("msgpack: {}", e)),
+        #[allow(unreachable_patterns)]
+        other => {
+            let _ = proto;
+            Err(without(&format!("{:?}", other).to_lowercase()))
+        }
+    }
+}
+
+#[cfg(not(feature = "qr"))]
+fn write_qr_png(_path: &std::path::Path, _payload: &[u8]) -> Result<(), String> {
+    Err(without("qr"))
+}
+
+#[cfg(feature = "qr")]
+fn write_qr_png(path: &std::path::Path, payload: &[u8]) -> Result<(), String> {
+    const SCALE: usize = 8;
+    const QUIET: usize = 4;
+    let code = qrcode::QrCode::new(payload).map_err(|e| format!("qr: {}", e))?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET) * SCALE;
+    let mut pixels = vec![255u8; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != qrcode::Color::Dark { continue; }
+        let (mx, my) = (i % modules + QUIET, i / modules + QUIET);
+        for y in my * SCALE..(my + 1) * SCALE {
+            pixels[y * size + mx * SCALE..y * size + (mx + 1) * SCALE].fill(0);
+        }
+    }
+    let file = std::io::BufWriter::new(std::fs::File::create(path).map_err(|e| format!("create: {}", e))?);
+    let mut encoder = png::Encoder::new(file, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| format!("png: {}", e))?;
+    writer.write_image_data(&pixels).map_err(|e| format!("png: {}", e))
+}
+
+// `spectrum`: the ambient noise per protocol family band, as a table or one JSON event
+#[cfg(all(feature = "audio", feature = "spectrogram"))]
+fn run_spectrum(args: &Args) -> Result<(), String> {
+    if !args.common.json {
+        eprintln!("Recording {} s of ambient sound; keep quiet unless that noise is typical", args.seconds);
+    }
+    let (samples, sample_rate) = audio::sample(args.capture.input_device.as_deref(), std::time::Duration::from_secs(args.seconds.into()))?;
+    let bands = spectrogram::band_noise(&samples, sample_rate)?;
+    let cleanest = spectrogram::cleanest(&bands).map(|band| band.family.clone());
+    if args.common.json {
+        print_event(&Event::Spectrum { sample_rate, seconds: args.seconds, bands, cleanest });
+        return Ok(());
+    }
+    println!("Noise by band ({} Hz):", sample_rate);
+    for band in &bands {
+        let level = band.noise_dbfs.map_or_else(|| "above Nyquist".to_owned(), |db| format!("{:6.1} dBFS", db));
+        println!("  {:<11} {:>5.0}-{:<5.0} Hz  {}", band.family, band.low_hz, band.high_hz, level);
+    }
+    if let Some(family) = cleanest {
+        println!("Quietest: {} (--protocol {}:fast)", family, family);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "spectrogram"))]
+fn write_spectrogram(_path: &std::path::Path, _samples: &[f32], _sample_rate: u32) -> Result<f64, String> {
+    Err(without("spectrogram"))
+}
+
+#[cfg(feature = "spectrogram")]
+fn write_spectrogram(path: &std::path::Path, samples: &[f32], sample_rate: u32) -> Result<f64, String> {
+    spectrogram::render(path, samples, sample_rate)
+}
+
+struct GgwaveModem {
+    protocol: i32,
+    sample_rate: Option<u32>,
+    samples_per_frame: Option<i32>,
+    // From ggwave_params()
+    params: ParametersBuilder,
+    // Built by the first modulate and reused by every thread that transmits with this modem
+    tx: std::sync::OnceLock<SharedDecoder>,
+}
+
+impl Modem for GgwaveModem {
+    fn max_freq_hz(&self) -> f32 {
+        let samples_per_frame = self.samples_per_frame.unwrap_or(gibberlink_core::MAX_SAMPLES_PER_FRAME);
+        protocol_max_hz(self.protocol, self.sample_rate.unwrap_or(48000), samples_per_frame)
+    }
+
+    fn modulate(&self, payload: &[u8], volume: i32) -> Result<Waveform, ModemError> {
+        let instance = match self.tx.get() {
+            Some(instance) => instance,
+            None => {
+                // TX only, mono 16-bit output
+                let mut builder = self.params.clone()
+                    .operating_mode(OperatingMode::Tx)
+                    .output_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16);
+                if let Some(sr) = self.sample_rate { builder = builder.sample_rate(sr); }
+                let instance = builder.init().map_err(|e| ModemError::Init(format!("Failed to init ggwave: {}", e)))?;
+                self.tx.get_or_init(|| instance.into())
+            }
+        };
+        let buf = instance.encode(payload, self.protocol, volume).map_err(|e| ModemError::Encode(e.to_string()))?;
+        let samples = buf.chunks_exact(2).map(|c| i16::from_le_bytes([c[0], c[1]])).collect();
+        Ok(Waveform { sample_rate: instance.parameters().sampleRateOut as u32, samples })
+    }
+
+    fn demodulate(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
+        gibberlink_core::decode_all_using(samples, sample_rate, 1, self.params.clone())
+            .map(|mut messages| messages.swap_remove(0))
+            .map_err(|e| e.to_string())
+    }
+
+    fn demodulate_all(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<Vec<u8>>, String> {
+        gibberlink_core::decode_all_using(samples, sample_rate, usize::MAX, self.params.clone()).map_err(|e| e.to_string())
+    }
+
+    fn demodulate_each(&self, samples: &[f32], sample_rate: u32, on_message: &mut dyn FnMut(Vec<u8>) -> ControlFlow<()>) -> Result<(), String> {
+        gibberlink_core::decode_each_using(samples, sample_rate, self.params.clone(), on_message).map_err(|e| e.to_string())
+    }
+}
+
+// --baud: `rtty` or a positive rate; build_modem() checks the rate against the sample rate
+fn parse_baud(s: &str) -> Result<String, String> {
+    match s.parse::<f32>() {
+        _ if s.eq_ignore_ascii_case("rtty") => Ok(s.to_owned()),
+        Ok(baud) if baud > 0.0 && baud.is_finite() => Ok(s.to_owned()),
+        _ => Err("expected a positive baud rate or `rtty`".into()),
+    }
+}
+
+fn parse_dbfs(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(db) if (-96.0..=0.0).contains(&db) => Ok(db),
+        Ok(_) => Err("level must be between -96 and 0 dBFS".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Everything that goes on the air: modulation at --volume, then --peak-dbfs and --limit-dbfs
+fn modulate_for_air(modem: &dyn Modem, frames: &[Vec<u8>], args: &Args) -> Result<Waveform, ModemError> {
+    let mut waveform = modulate_frames(modem, frames, args.signal.volume.clamp(0, 100))?;
+    if let Some(db) = args.shaping.peak_dbfs {
+        waveform.normalize_peak(db);
+    }
+    if let Some(db) = args.shaping.limit_dbfs {
+        waveform.limit(db);
+    }
+    Ok(waveform)
+}
+
+// Back-to-back transmissions with a short silence between them, so the receiver sees each one
+fn modulate_frames(modem: &dyn Modem, frames: &[Vec<u8>], volume: i32) -> Result<Waveform, ModemError> {
+    let mut waveform = Waveform { sample_rate: 0, samples: Vec::new() };
+    for (i, frame) in frames.iter().enumerate() {
+        let w = modem.modulate(frame, volume)?;
+        if i > 0 {
+            waveform.samples.resize(waveform.samples.len() + w.sample_rate as usize / 10, 0);
+        }
+        waveform.sample_rate = w.sample_rate;
+        waveform.samples.extend(w.samples);
+    }
+    Ok(waveform)
+}
+
+#[cfg(feature = "audio")]
+const RELIABLE_ATTEMPTS: u32 = 5;
+
+// ggwave carries at most MAX_LENGTH_VARIABLE bytes per transmission; longer payloads go out as chunks
+// With --fec, even a short payload is coded, so a lost transmission can be made up by parity
+fn to_frames(payload: &[u8], ggwave: bool, always_chunk: bool, fec: Option<RsCode>, verbose: bool) -> Result<Vec<Vec<u8>>, String> {
+    if !ggwave || (!always_chunk && fec.is_none() && payload.len() <= gibberlink_core::MAX_LENGTH_VARIABLE) {
+        return Ok(vec![payload.to_vec()]);
+    }
+    let id = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.subsec_nanos() as u8);
+    let frames = match fec {
+        Some(code) => split_fec(payload, id, code)?,
+        None => chunk::split(payload, id)?,
+    };
+    if verbose {
+        eprintln!("Split {} byte payload into {} chunks", payload.len(), frames.len());
+    }
+    Ok(frames)
+}
+
+#[cfg(feature = "fec")]
+fn split_fec(payload: &[u8], id: u8, code: RsCode) -> Result<Vec<Vec<u8>>, String> {
+    chunk::split_fec(payload, id, code.n, code.k)
+}
+
+#[cfg(not(feature = "fec"))]
+fn split_fec(_payload: &[u8], _id: u8, _code: RsCode) -> Result<Vec<Vec<u8>>, String> {
+    Err(without("fec"))
+}
+
+// Half-duplex stop-and-wait: send the chunks still missing plus a poll, then listen for the
+// receiver's bitmap. The wait doubles every round (2 s, 4 s, ...) to ride out busy channels.
+#[cfg(feature = "audio")]
+fn send_reliable(modem: &dyn Modem, frames: &[Vec<u8>], args: &Args) -> Result<(), String> {
+    let (id, count) = (frames[0][1], frames.len());
+    let mut missing: Vec<usize> = (0..count).collect();
+    let mut wait = std::time::Duration::from_secs(2);
+    for attempt in 1..=RELIABLE_ATTEMPTS {
+        let mut round: Vec<Vec<u8>> = missing.iter().map(|&i| frames[i].clone()).collect();
+        round.push(Control::Poll { id, count: count as u8 }.to_bytes());
+        let waveform = modulate_for_air(modem, &round, args).map_err(|e| e.to_string())?;
+        play_waveform(&waveform, args.playback.device.as_deref())?;
+        let ack = audio::listen_for(args.capture.input_device.as_deref(), wait, ggwave_params(args)?, |bytes| match Control::parse(&bytes) {
+            Some(Control::Ack { id: acked, received }) if acked == id => Some(received),
+            _ => None,
+        })?;
+        match ack {
+            Some(received) => {
+                missing.retain(|&i| !received.get(i).copied().unwrap_or(false));
+                if missing.is_empty() {
+                    return Ok(());
+                }
+                eprintln!("Receiver is missing {} of {} chunk(s), resending (attempt {}/{})", missing.len(), count, attempt, RELIABLE_ATTEMPTS);
+            }
+            None => eprintln!("No acknowledgement within {} s (attempt {}/{})", wait.as_secs(), attempt, RELIABLE_ATTEMPTS),
+        }
+        wait *= 2;
+    }
+    Err(format!("no complete acknowledgement after {} attempts", RELIABLE_ATTEMPTS))
+}
+
+// --rx-protocols: `family` enables its three speeds, `family:speed` just that one
+fn rx_protocols(names: &[String]) -> Result<Option<Vec<i32>>, String> {
+    use ggwave_consts::*;
+    if names.is_empty() {
+        return Ok(None);
+    }
+    let mut ids = Vec::new();
+    for name in names {
+        let (family, speed) = name.split_once(':').map_or((name.as_str(), None), |(f, s)| (f, Some(s)));
+        let normal = match family.to_ascii_lowercase().as_str() {
+            "audible" => GGWAVE_PROTOCOL_AUDIBLE_NORMAL,
+            "ultrasound" => GGWAVE_PROTOCOL_ULTRASOUND_NORMAL,
+            "dt" => GGWAVE_PROTOCOL_DT_NORMAL,
+            "mt" => GGWAVE_PROTOCOL_MT_NORMAL,
+            _ => return Err(format!("unknown protocol family {:?} in --rx-protocols", family)),
+        };
+        match speed.map(str::to_ascii_lowercase).as_deref() {
+            None => ids.extend(normal..normal + 3),
+            Some("normal") => ids.push(normal),
+            Some("fast") => ids.push(normal + 1),
+            Some("fastest") => ids.push(normal + 2),
+            Some(other) => return Err(format!("unknown protocol speed {:?} in --rx-protocols", other)),
+        }
+    }
+    Ok(Some(ids))
+}
+
+fn parse_marker_threshold(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(t) if t > 0.0 && t.is_finite() => Ok(t),
+        Ok(_) => Err("threshold must be positive".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// ggwave settings beyond the protocol and rate: --payload-length and --samples-per-frame on both
+// ends, --marker-threshold and --rx-protocols when receiving
+fn ggwave_params(args: &Args) -> Result<ParametersBuilder, String> {
+    let mut builder = ParametersBuilder::new();
+    if let Some(len) = args.common.payload_length {
+        builder = builder.payload_len_fixed(len);
+    }
+    if let Some(n) = args.common.samples_per_frame {
+        builder = builder.samples_per_frame(n);
+    }
+    if let Some(threshold) = args.receiver.marker_threshold {
+        builder = builder.sound_marker_threshold(threshold);
+    }
+    if let Some(protocols) = rx_protocols(&args.receiver.rx_protocols)? {
+        builder = builder.rx_protocols(&protocols);
+    }
+    Ok(builder)
+}
+
+fn build_modem(args: &Args) -> Result<Box<dyn Modem>, String> {
+    let sample_rate = args.signal.sample_rate.unwrap_or(48000);
+    if args.common.modem != ModemKind::Ggwave {
+        for (flag, given) in [
+            ("--payload-length", args.common.payload_length.is_some()),
+            ("--samples-per-frame", args.common.samples_per_frame.is_some()),
+            ("--marker-threshold", args.receiver.marker_threshold.is_some()),
+        ] {
+            if given {
+                return Err(format!("{} only supports --modem ggwave", flag));
+            }
+        }
+    }
+    Ok(match args.common.modem {
+        ModemKind::Ggwave => Box::new(GgwaveModem {
+            protocol: parse_protocol(&args.common.protocol).map_err(|e| e.to_string())?,
+            sample_rate: args.signal.sample_rate,
+            samples_per_frame: args.common.samples_per_frame,
+            params: ggwave_params(args)?,
+            tx: Default::default(),
+        }),
+        ModemKind::Afsk => Box::new(modem::Afsk::bell202(sample_rate)),
+        ModemKind::Minimodem if args.common.baud.eq_ignore_ascii_case("rtty") => Box::new(modem::Rtty::new(sample_rate)),
+        ModemKind::Minimodem => {
+            let baud: f32 = args.common.baud.parse().ok().filter(|b| *b > 0.0).ok_or_else(|| format!("invalid --baud {:?}", args.common.baud))?;
+            // Every bit needs at least two samples
+            if baud > sample_rate as f32 / 2.0 {
+                return Err(format!("--baud {} is too fast for {} Hz audio; at most {}", args.common.baud, sample_rate, sample_rate / 2));
+            }
+            let mut afsk = modem::Afsk::minimodem(baud, sample_rate);
+            afsk.mark_hz = args.common.mark_hz.unwrap_or(afsk.mark_hz);
+            afsk.space_hz = args.common.space_hz.unwrap_or(afsk.space_hz);
+            Box::new(afsk)
+        }
+        ModemKind::Dtmf => Box::new(modem::Dtmf::new(sample_rate)),
+    })
+}
+
+#[cfg(feature = "decode")]
+// One row per ggwave protocol (those in --rx-protocols, or all of them), or one for the other modems
+fn run_bench(args: &Args) -> Result<(), String> {
+    let alphabet: &[u8] = match args.common.modem {
+        ModemKind::Dtmf => b"0123456789ABCD*#",
+        _ => b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+    };
+    let config = bench::Config {
+        snrs_db: &args.benchmark.snr,
+        noise: args.benchmark.noise,
+        trials: args.benchmark.trials,
+        payload_len: args.benchmark.bench_bytes as usize,
+        volume: args.signal.volume.clamp(0, 100),
+        alphabet,
+    };
+    let settings: Vec<(String, Box<dyn Modem>)> = if args.common.modem == ModemKind::Ggwave {
+        let params = ggwave_params(args)?;
+        let protocols = rx_protocols(&args.receiver.rx_protocols)?.unwrap_or_else(|| (0..gibberlink_core::PROTOCOL_COUNT).collect());
+        protocols
+            .into_iter()
+            .map(|protocol| {
+                let modem = GgwaveModem {
+                    protocol,
+                    sample_rate: args.signal.sample_rate,
+                    samples_per_frame: args.common.samples_per_frame,
+                    params: params.clone(),
+                    tx: Default::default(),
+                };
+                (gibberlink_core::protocol_name(protocol), Box::new(modem) as Box<dyn Modem>)
+            })
+            .collect()
+    } else {
+        vec![(modem_name(args.common.modem), build_modem(args)?)]
+    };
+
+    if !args.common.json {
+        print!("{:<20}", "setting");
+        for snr in &args.benchmark.snr {
+            print!("{:>8}", format!("{} dB", snr));
+        }
+        println!("{:>10}", "bytes/s");
+    }
+    let mut rng = bench::Rng::from_time();
+    for (setting, modem) in settings {
+        let row = bench::run(modem.as_ref(), &config, &mut rng).map_err(|e| format!("{}: {}", setting, e))?;
+        if args.common.json {
+            for (&snr_db, &successes) in args.benchmark.snr.iter().zip(&row.successes) {
+                print_event(&Event::Bench { setting: setting.clone(), snr_db, trials: args.benchmark.trials, successes, bytes_per_s: row.bytes_per_s });
+            }
+            continue;
+        }
+        print!("{:<20}", setting);
+        for successes in &row.successes {
+            print!("{:>8}", format!("{:.0}%", 100.0 * *successes as f64 / args.benchmark.trials as f64));
+        }
+        println!("{:>10.1}", row.bytes_per_s);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "decode")]
+#[derive(Default)]
+struct DecodeStats {
+    // Messages that made it through unframe()
+    messages: usize,
+    // Keyed by UnframeError::stage, plus "demodulation" and "incomplete"
+    failures: std::collections::BTreeMap<String, usize>,
+    // Set by --detect
+    protocol: Option<String>,
+    // Demodulated frames, per ggwave protocol (only counted with --stats) or under the modem's name
+    frames: std::collections::BTreeMap<String, usize>,
+    snr_db: Option<f32>,
+    sample_rate: u32,
+    seconds: f64,
+    wall: std::time::Duration,
+}
+
+#[cfg(feature = "decode")]
+impl DecodeStats {
+    fn fail(&mut self, stage: &str) {
+        *self.failures.entry(stage.to_owned()).or_default() += 1;
+    }
+
+    fn failed(&self) -> usize {
+        self.failures.values().sum()
+    }
+
+    // Same names as the JSON decode_summary
+    fn print(&self, modem: ModemKind) {
+        eprintln!("Decode summary:");
+        eprintln!("  messages:        {}", self.messages);
+        eprintln!("  failures:        {}", self.failed());
+        for (stage, count) in &self.failures {
+            eprintln!("    {:<14} {}", format!("{}:", stage), count);
+        }
+        eprintln!("  modem:           {}", modem_name(modem));
+        if let Some(protocol) = &self.protocol {
+            eprintln!("  protocol:        {} at {} Hz", protocol, self.sample_rate);
+        }
+        if !self.frames.is_empty() {
+            eprintln!("  frames:");
+            for (protocol, count) in &self.frames {
+                eprintln!("    {:<14} {}", format!("{}:", protocol), count);
+            }
+        }
+        match self.snr_db {
+            Some(snr) => eprintln!("  snr:             {:.1} dB", snr),
+            None => eprintln!("  snr:             n/a (no signal, or a silent noise floor)"),
+        }
+        eprintln!("  audio processed: {:.2} s", self.seconds);
+        eprintln!("  wall time:       {:.1} ms", self.wall.as_secs_f64() * 1000.0);
+    }
+
+    fn summary(&self, modem: ModemKind) -> Event {
+        Event::DecodeSummary {
+            messages: self.messages,
+            failures: self.failed(),
+            failures_by_stage: self.failures.clone(),
+            modem: modem_name(modem),
+            protocol: self.protocol.clone(),
+            frames_by_protocol: self.frames.clone(),
+            snr_db: self.snr_db,
+            sample_rate: self.sample_rate,
+            duration_s: self.seconds,
+            elapsed_ms: self.wall.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+#[cfg(feature = "decode")]
+// Rough SNR of a recording from 1024-sample frame powers: the quietest tenth is the noise floor,
+// frames 6 dB above it are signal, averaged in dB. None on digital silence, which has no floor
+fn estimate_snr_db(samples: &[f32]) -> Option<f32> {
+    let powers: Vec<f32> = samples.chunks(1024).map(|f| f.iter().map(|s| s * s).sum::<f32>() / f.len() as f32).collect();
+    let mut sorted = powers.clone();
+    sorted.sort_by(f32::total_cmp);
+    let noise = *sorted.get(sorted.len() / 10)?;
+    if noise <= 0.0 {
+        return None;
+    }
+    let snrs: Vec<f32> = powers.iter().filter(|&&p| p > 4.0 * noise).map(|p| 10.0 * ((p - noise) / noise).log10()).collect();
+    (!snrs.is_empty()).then(|| snrs.iter().sum::<f32>() / snrs.len() as f32)
+}
+
+#[cfg(feature = "decode")]
+// --stats: how many frames each enabled ggwave protocol demodulates on its own
+fn count_protocol_frames(args: &Args, samples: &[f32], sample_rate: u32, stats: &mut DecodeStats) -> Result<(), String> {
+    let params = ggwave_params(args)?;
+    let enabled = rx_protocols(&args.receiver.rx_protocols)?.unwrap_or_else(|| (0..gibberlink_core::PROTOCOL_COUNT).collect());
+    for protocol in enabled {
+        let only = params.clone().rx_protocols(&[protocol]);
+        if let Ok(frames) = gibberlink_core::decode_all_using(samples, sample_rate, usize::MAX, only) {
+            stats.frames.insert(gibberlink_core::protocol_name(protocol), frames.len());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "decode")]
+// Mono samples and their rate from --decode-wav, or --decode-raw; `-` reads either from stdin
+fn read_recording(args: &Args) -> Result<(Vec<f32>, u32), String> {
+    if let Some(path) = args.decode_wav.as_deref() {
+        let stdin = if path.as_os_str() == "-" {
+            let mut buf = Vec::new();
+            std::io::stdin().read_to_end(&mut buf).map_err(|e| format!("stdin: {}", e))?;
+            Some(buf)
+        } else {
+            None
+        };
+        let mut magic = [0u8; 4];
+        let is_wav = match stdin.as_deref() {
+            Some(bytes) => bytes.starts_with(b"RIFF"),
+            None => std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut magic)).map_or(true, |_| &magic == b"RIFF"),
+        };
+        if !is_wav {
+            return decode_compressed(path, stdin);
+        }
+        let wav = match stdin { Some(bytes) => read_wav_from(bytes.as_slice()), None => read_wav(path) }?;
+        return Ok((to_mono_f32(&wav)?, wav.sample_rate));
+    }
+    let path = args.decode_raw.as_deref().ok_or("nothing to decode")?;
+    let bytes = if path.as_os_str() == "-" {
+        let mut buf = Vec::new();
+        std::io::stdin().read_to_end(&mut buf).map(|_| buf)
+    } else {
+        std::fs::read(path)
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))?;
+    // A pipe cut mid-sample leaves a partial sample at the end; it is dropped
+    let samples = match args.recording.raw_format {
+        RawFormat::S16le => bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0).collect(),
+        RawFormat::F32le => bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect(),
+    };
+    Ok((samples, args.recording.raw_rate.unwrap_or(gibberlink_core::DEFAULT_SAMPLE_RATE)))
+}
+
+#[cfg(all(feature = "decode", not(feature = "codecs")))]
+fn decode_compressed(path: &std::path::Path, _stdin: Option<Vec<u8>>) -> Result<(Vec<f32>, u32), String> {
+    Err(format!("{} is not a WAV file, and {}", path.display(), without("codecs")))
+}
+
+#[cfg(feature = "codecs")]
+fn decode_compressed(path: &std::path::Path, stdin: Option<Vec<u8>>) -> Result<(Vec<f32>, u32), String> {
+    codecs::decode(path, stdin)
+}
+
+#[cfg(feature = "decode")]
+// Hands each demodulated frame to `on_frame` as it is found, until it returns `Break`
+fn decode_recording(modem: &dyn Modem, args: &Args, stats: &mut DecodeStats, on_frame: &mut dyn FnMut(Vec<u8>) -> ControlFlow<()>) -> Result<(), String> {
+    let started = std::time::Instant::now();
+    let result = read_recording(args).and_then(|(samples, sample_rate)| {
+        stats.sample_rate = sample_rate;
+        stats.seconds += samples.len() as f64 / sample_rate as f64;
+        if args.decode.stats {
+            stats.snr_db = estimate_snr_db(&samples);
+        }
+        if let Some(png) = args.recording.spectrogram.as_deref() {
+            let step = write_spectrogram(png, &samples, sample_rate).map_err(|e| format!("spectrogram: {}", e))?;
+            eprintln!("Wrote spectrogram to {} ({:.1} ms per column)", png.display(), step * 1000.0);
+        }
+        if args.decode.detect {
+            let found = gibberlink_core::detect(&samples, sample_rate).map_err(|e| e.to_string())?;
+            let protocol = gibberlink_core::protocol_name(found.protocol);
+            eprintln!("Detected ggwave {} at {} Hz", protocol, found.sample_rate);
+            stats.frames.insert(protocol.clone(), found.messages.len());
+            stats.protocol = Some(protocol);
+            stats.sample_rate = found.sample_rate;
+            for frame in found.messages {
+                if on_frame(frame).is_break() {
+                    break;
+                }
+            }
+            return Ok(());
+        }
+        if args.common.modem == ModemKind::Ggwave && args.decode.stats {
+            count_protocol_frames(args, &samples, sample_rate, stats)?;
+        }
+        let mut frames = 0;
+        modem.demodulate_each(&samples, sample_rate, &mut |frame| {
+            frames += 1;
+            on_frame(frame)
+        })?;
+        if args.common.modem != ModemKind::Ggwave {
+            stats.frames.insert(modem_name(args.common.modem), frames);
+        }
+        Ok(())
+    });
+    stats.wall += started.elapsed();
+    if result.is_err() {
+        stats.fail("demodulation");
+    }
+    result
+}
+
+#[cfg(feature = "decode")]
+// Why unframe() rejected a message; `stage` is what --stats counts it under
+#[derive(Debug)]
+struct UnframeError {
+    stage: &'static str,
+    message: String,
+}
+
+#[cfg(feature = "decode")]
+impl std::fmt::Display for UnframeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[cfg(feature = "decode")]
+fn failed_at(stage: &'static str) -> impl Fn(String) -> UnframeError {
+    move |message| UnframeError { stage, message }
+}
+
+#[cfg(feature = "decode")]
+// Between the demodulator and the output: chunk reassembly, decryption, then the shared dictionary.
+// Ok(None) while a chunked message is still incomplete; its receive_* events go to `progress`.
+fn unframe(reassembler: &mut Reassembler, bytes: Vec<u8>, key: Option<&Key>, dict: Option<&[u8]>, verbose: bool, progress: &mut dyn FnMut(Event)) -> Result<Option<Vec<u8>>, UnframeError> {
+    // Polls and acknowledgements of a --reliable exchange are not messages
+    if Control::parse(&bytes).is_some() {
+        return Ok(None);
+    }
+    let id = chunk::message_id(&bytes);
+    let bytes = match reassembler.push(bytes).map_err(failed_at("reassembly"))? {
+        Received::Message(bytes) => bytes,
+        Received::Duplicate { id } => {
+            if verbose {
+                eprintln!("Message {}: repeated chunk ignored", id);
+            }
+            return Ok(None);
+        }
+        Received::Partial { id, received, count } => {
+            if received == 1 {
+                progress(Event::ReceiveBegin { id, count });
+            }
+            progress(Event::ReceiveProgress { id, received, count });
+            return Ok(None);
+        }
+    };
+    let result = unpack(bytes, key, dict, verbose);
+    if let Some(id) = id {
+        progress(Event::ReceiveEnd { id, ok: result.is_ok(), error: result.as_ref().err().map(|e| e.message.clone()) });
+    }
+    result.map(Some)
+}
+
+#[cfg(feature = "decode")]
+// The rest of unframe() once a whole message is in
+fn unpack(bytes: Vec<u8>, key: Option<&Key>, dict: Option<&[u8]>, verbose: bool) -> Result<Vec<u8>, UnframeError> {
+    // Checked before anything else looks at the bytes
+    let bytes = match integrity::unpack(&bytes) {
+        Some(checked) => checked.map_err(failed_at("integrity"))?,
+        None => bytes,
+    };
+    let bytes = match key {
+        _ if !is_envelope(&bytes) => {
+            if key.is_some() {
+                eprintln!("Warning: message was not encrypted");
+            }
+            bytes
+        }
+        Some(key) => decrypt(key, &bytes).map_err(failed_at("decryption"))?,
+        None => return Err(failed_at("decryption")("message is encrypted; pass --key or --key-file".into())),
+    };
+    let bytes = match dict {
+        Some(d) => dict_decompress(&bytes, d).map_err(failed_at("dictionary"))?,
+        None => bytes,
+    };
+    match decompress(&bytes) {
+        Some(expanded) => {
+            let expanded = expanded.map_err(failed_at("decompression"))?;
+            if verbose {
+                eprintln!("Decompressed {} -> {} bytes", bytes.len(), expanded.len());
+            }
+            Ok(expanded)
+        }
+        None => Ok(bytes),
+    }
+}
+
+#[cfg(feature = "decode")]
+// --out-bin collects every message of a run, so start it empty
+fn truncate_out_bin(args: &Args) {
+    if let Some(out_bin) = args.output.out_bin.as_ref().filter(|p| p.as_os_str() != "-") {
+        if let Err(e) = std::fs::write(out_bin, b"") {
+            eprintln!("Failed to write decoded bytes: {}", e);
+            std::process::exit(5);
+        }
+    }
+}
+
+#[cfg(feature = "decode")]
+// Files are only written when asked for, and never over an existing one: a numbered name is picked instead
+fn save_file(dir: Option<&std::path::Path>, received: file::FileMessage) {
+    let Some(dir) = dir else {
+        eprintln!("Received file {} ({} bytes); pass --save-dir to keep it", received.name, received.contents.len());
+        return;
+    };
+    let stem = std::path::Path::new(&received.name).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = std::path::Path::new(&received.name).extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let written = std::fs::create_dir_all(dir).and_then(|_| {
+        let mut n = 0;
+        loop {
+            let name = if n == 0 { received.name.clone() } else { format!("{}.{}{}", stem, n, ext) };
+            let path = dir.join(name);
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut f) => return f.write_all(&received.contents).map(|_| path),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    });
+    match written {
+        Ok(path) => eprintln!("Saved {} ({} bytes) to {}", received.name, received.contents.len(), path.display()),
+        Err(e) => {
+            eprintln!("Failed to save {}: {}", received.name, e);
+            std::process::exit(5);
+        }
+    }
+}
+
+#[cfg(feature = "decode")]
+// Output side of decoding, shared by --decode-wav and --listen: unwrap, on_receive, sinks, then print
+fn emit_decoded(args: &Args, bytes: Vec<u8>, script: Option<&Script>, proto: Option<&ProtoMessage>) {
+    let Some(bytes) = process_received(args, bytes, script) else { return };
+    if !args.output.sink.is_empty() {
+        let event = decoded_event(&bytes);
+        for cmd in &args.output.sink {
+            if let Err(e) = run_sink(cmd, &event) {
+                eprintln!("Sink '{}' failed: {}", cmd, e);
+            }
+        }
+    }
+    if let Some(out_bin) = args.output.out_bin.as_ref() {
+        let written = if out_bin.as_os_str() == "-" {
+            std::io::stdout().lock().write_all(&bytes)
+        } else {
+            std::fs::OpenOptions::new().append(true).open(out_bin).and_then(|mut f| f.write_all(&bytes))
+        };
+        if let Err(e) = written {
+            eprintln!("Failed to write decoded bytes: {}", e);
+            std::process::exit(5);
+        }
+        return;
+    }
+    if args.common.json {
+        print_event(&decoded_event(&bytes));
+        return;
+    }
+    match decode_payload(&bytes, args.common.payload_format, proto) {
+        Ok(s) => { println!("{}", s); }
+        Err(e) => {
+            if args.common.payload_format != PayloadFormat::Text {
+                eprintln!("Payload is not valid {:?}: {}", args.common.payload_format, e);
+            }
+            print!("0x");
+            for b in bytes { print!("{:02x}", b); }
+            println!();
+        }
+    }
+}
+
+#[cfg(feature = "decode")]
+// Saves file transfers, then --unwrap and on_receive; None once the message has been dealt with
+fn process_received(args: &Args, mut bytes: Vec<u8>, script: Option<&Script>) -> Option<Vec<u8>> {
+    match file::unpack(&bytes) {
+        Some(Ok(received)) => {
+            save_file(args.received.save_dir.as_deref(), received);
+            return None;
+        }
+        Some(Err(e)) => {
+            eprintln!("File transfer failed: {}", e);
+            return None;
+        }
+        None => {}
+    }
+    // Only now: a payload of its own that starts like a file envelope came escaped
+    bytes = framing::unescape(bytes);
+    if args.received.unwrap {
+        if let Some((encoding, inner)) = unwrap_encoded(&bytes) {
+            if args.common.verbose {
+                eprintln!("Unwrapped {} payload: {} -> {} bytes", encoding, bytes.len(), inner.len());
+            }
+            bytes = inner;
+        }
+    }
+    if args.common.verbose {
+        eprintln!("Decoded {} bytes ({})", bytes.len(), classify_payload(&bytes));
+    }
+    if let (Some(script), Ok(text)) = (script, std::str::from_utf8(&bytes)) {
+        match script.call("on_receive", text) {
+            Ok(Some(t)) => bytes = t.into_bytes(),
+            Ok(None) => {
+                if args.common.verbose { eprintln!("Message dropped by on_receive"); }
+                return None;
+            }
+            Err(e) => {
+                eprintln!("Script error: {}", e);
+                std::process::exit(9);
+            }
+        }
+    }
+    Some(bytes)
+}
+
+// Playback is in-process through cpal (the `audio` feature), so no external player or OS-specific
+// API is involved; without it the WAV is only written
+#[cfg(feature = "audio")]
+fn play_waveform(waveform: &Waveform, device: Option<&str>) -> Result<(), String> {
+    play_waveform_at(waveform, device, None)
+}
+
+#[cfg(feature = "audio")]
+fn play_waveform_at(waveform: &Waveform, device: Option<&str>, start: Option<std::time::SystemTime>) -> Result<(), String> {
+    let samples: Vec<f32> = waveform.samples.iter().map(|&s| s as f32 / 32768.0).collect();
+    audio::play_at(&samples, waveform.sample_rate, device, start)
+}
+
+#[cfg(not(feature = "audio"))]
+fn play_waveform(_waveform: &Waveform, _device: Option<&str>) -> Result<(), String> {
+    Err(without("audio"))
+}
+
+#[cfg(feature = "audio")]
+fn play_wav_file(path: &std::path::Path, device: Option<&str>) -> Result<(), String> {
+    let wav = read_wav(path)?;
+    let samples = to_mono_f32(&wav)?;
+    audio::play(&samples, wav.sample_rate, device)
+}
+
+#[cfg(not(feature = "audio"))]
+fn play_wav_file(_path: &std::path::Path, _device: Option<&str>) -> Result<(), String> {
+    Err(without("audio"))
+}
+
+// --max-freq-hz, checked before anything is played or written; on failure, suggests the ggwave
+// protocols that fit
+fn check_ceiling(modem: &dyn Modem, args: &Args) -> Result<(), String> {
+    let Some(max) = args.shaping.max_freq_hz else { return Ok(()) };
+    let top = modem.max_freq_hz();
+    if top <= max {
+        return Ok(());
+    }
+    let mut message = format!("Refusing to transmit: tones reach {:.0} Hz, above the {:.0} Hz ceiling", top, max);
+    if args.common.modem == ModemKind::Ggwave {
+        let sample_rate = args.signal.sample_rate.unwrap_or(48000);
+        let samples_per_frame = args.common.samples_per_frame.unwrap_or(gibberlink_core::MAX_SAMPLES_PER_FRAME);
+        let fits: Vec<String> = ["audible", "ultrasound", "dt", "mt"]
+            .iter()
+            .flat_map(|f| ["normal", "fast", "fastest"].map(|s| format!("{}:{}", f, s)))
+            .filter(|p| protocol_max_hz(parse_protocol(p).expect("known protocol"), sample_rate, samples_per_frame) <= max)
+            .collect();
+        if fits.is_empty() {
+            message += &format!("\nNo ggwave protocol fits under {:.0} Hz", max);
+        } else {
+            message += &format!("\nProtocols within the ceiling: {}", fits.join(", "));
+        }
+    }
+    Err(message)
+}
+
+// How long after a bridge sends a payload the microphone hearing it back is taken as its echo
+#[cfg(all(feature = "audio", feature = "decode"))]
+const ECHO_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+// Payloads a bridge transmitted (serve --serial, bridge), so the copy the microphone picks up is not
+// handed back to the tool that sent it; each is dropped once, so a genuine repeat from a peer still arrives
+#[cfg(all(feature = "audio", feature = "decode"))]
+#[derive(Default)]
+struct Echoes(std::sync::Mutex<Vec<(std::time::Instant, Vec<u8>)>>);
+
+#[cfg(all(feature = "audio", feature = "decode"))]
+impl Echoes {
+    fn sent(&self, payload: &[u8]) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push((std::time::Instant::now(), payload.to_vec()));
+    }
+
+    fn is_echo(&self, payload: &[u8]) -> bool {
+        let mut sent = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        sent.retain(|(at, _)| at.elapsed() < ECHO_WINDOW);
+        match sent.iter().position(|(_, p)| p == payload) {
+            Some(i) => {
+                sent.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn play_with_retries(retries: u32, mut play: impl FnMut() -> Result<(), String>) -> Result<(), String> {
+    let mut delay = std::time::Duration::from_secs(1);
+    let mut attempt = 0;
+    loop {
+        match play() {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= retries => return Err(e),
+            Err(e) => {
+                eprintln!("Playback failed ({}), retrying in {} s", e, delay.as_secs());
+                std::thread::sleep(delay);
+                delay = (delay * 2).min(std::time::Duration::from_secs(300));
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// Queued files are named by enqueue time in ms, so lexical order is send order
+fn enqueue_for_retry(dir: &std::path::Path, waveform: &Waveform) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let millis = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    let dest = dir.join(format!("{:016}.wav", millis));
+    write_wav(&dest, waveform.sample_rate, ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16, &waveform.to_le_bytes())
+        .map_err(|e| format!("{}: {}", dest.display(), e))?;
+    Ok(dest)
+}
+
+// Replays the backlog oldest first; stops at the first failure so order is preserved
+fn flush_retry_queue(dir: &std::path::Path, retries: u32, device: Option<&str>) -> Result<usize, String> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return Ok(0) };
+    let mut queued: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "wav"))
+        .collect();
+    queued.sort();
+    for (sent, path) in queued.iter().enumerate() {
+        play_with_retries(retries, || play_wav_file(path, device)).map_err(|e| format!("{} queued message(s) still pending: {}", queued.len() - sent, e))?;
+        std::fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(queued.len())
+}
+
+// The text steps: --template, the script's on_transmit, --normalize/--ascii, the JSON checks and
+// --payload-format. Ok(None) when on_transmit dropped the message; errors carry their exit code.
+fn prepare_text(text: String, args: &Args, script: Option<&Script>, proto: Option<&ProtoMessage>) -> Result<Option<Vec<u8>>, (i32, String)> {
+    let invalid = |e: String| (7, format!("Invalid payload: {}", e));
+    let text = if args.steps.template { expand_template(&text).map_err(invalid)? } else { text };
+    let text = match script.map(|s| s.call("on_transmit", &text)).transpose() {
+        Ok(Some(Some(t))) => t,
+        Ok(Some(None)) => return Ok(None),
+        Ok(None) => text,
+        Err(e) => return Err((9, format!("Script error: {}", e))),
+    };
+    let text = normalize_text(&text, args.steps.normalize, args.steps.ascii);
+    let text = if args.steps.json_payload || args.steps.json_schema.is_some() {
+        prepare_json(&text, args.steps.json_schema.as_deref()).map_err(invalid)?
+    } else {
+        text
+    };
+    encode_payload(&text, args.common.payload_format, proto).map(Some).map_err(invalid)
+}
+
+// What every message goes through after the text steps: the framing escape, --compress, --dict,
+// --key, then --crc
+fn seal(message: Vec<u8>, args: &Args, key: Option<&Key>, dict: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    seal_frame(framing::escape(message), args, key, dict)
+}
+
+// seal() for a file or chat frame, whose own marker must reach the receiver unescaped
+fn seal_frame(message: Vec<u8>, args: &Args, key: Option<&Key>, dict: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let message = match args.sealing.compress {
+        Some(method) => {
+            let packed = compress(&message, method)?;
+            if args.common.verbose {
+                eprintln!(
+                    "Compressed {} -> {} bytes{}",
+                    message.len(),
+                    packed.len(),
+                    if packed.len() < message.len() { "" } else { "; sending uncompressed" }
+                );
+            }
+            if packed.len() < message.len() { packed } else { message }
+        }
+        None => message,
+    };
+    let message = match dict {
+        Some(d) => dict_compress(&message, d)?,
+        None => message,
+    };
+    let message = match key {
+        Some(k) => encrypt(k, &message)?,
+        None => message,
+    };
+    if args.sealing.crc { integrity::pack(&message) } else { Ok(message) }
+}
+
+// One file from --watch through the text steps (a .json file must also be valid JSON), played
+// right away
+#[cfg(all(feature = "watch", feature = "audio"))]
+fn send_watched(path: &std::path::Path, modem: &dyn Modem, args: &Args, key: Option<&Key>, dict: Option<&[u8]>, script: Option<&Script>, proto: Option<&ProtoMessage>) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let text = text.trim_end();
+    if text.is_empty() {
+        return Err("empty file".into());
+    }
+    let is_json = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    let text = if is_json { prepare_json(text, args.steps.json_schema.as_deref())? } else { text.to_owned() };
+    let message = match prepare_text(text, args, script, proto).map_err(|(_, e)| e)? {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let payload = seal(message, args, key, dict)?;
+    let frames = to_frames(&payload, args.common.modem == ModemKind::Ggwave, false, args.sealing.fec, args.common.verbose)?;
+    let waveform = modulate_for_air(modem, &frames, args).map_err(|e| e.to_string())?;
+    play_with_retries(args.playback.retries, || play_waveform(&waveform, args.playback.device.as_deref()))
+}
+
+// Moves `path` into `dir`, numbering the name (`alert.1.txt`, ...) rather than overwriting
+#[cfg(all(feature = "watch", feature = "audio"))]
+fn move_into(dir: &std::path::Path, path: &std::path::Path) -> std::io::Result<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut dest = dir.join(&*name);
+    let mut n = 0;
+    while dest.exists() {
+        n += 1;
+        dest = dir.join(format!("{}.{}{}", stem, n, ext));
+    }
+    // rename() can't cross filesystems
+    std::fs::rename(path, &dest).or_else(|_| std::fs::copy(path, &dest).and_then(|_| std::fs::remove_file(path)))?;
+    Ok(dest)
+}
+
+// --token-file, else GIBBERLINK_TOKEN
+#[cfg(all(feature = "serve", feature = "audio", feature = "decode"))]
+fn load_token(path: Option<&std::path::Path>) -> Result<Option<String>, String> {
+    let token = match path {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => match std::env::var("GIBBERLINK_TOKEN") {
+            Ok(token) => token,
+            Err(_) => return Ok(None),
+        },
+    };
+    match token.trim() {
+        "" => Err("the token is empty".into()),
+        token => Ok(Some(token.to_owned())),
+    }
+}
+
+// A payload from a `serve` client, through the same steps as `tx` input; the caller plays or returns it
+#[cfg(all(feature = "serve", feature = "audio", feature = "decode"))]
+fn encode_submission(
+    submission: serve::Submission,
+    modem: &dyn Modem,
+    args: &Args,
+    key: Option<&Key>,
+    dict: Option<&[u8]>,
+    script: Option<&Script>,
+    proto: Option<&ProtoMessage>,
+) -> Result<(Event, Waveform), String> {
+    let started = std::time::Instant::now();
+    let message = match submission {
+        serve::Submission::Text(text) => match prepare_text(text, args, script, proto).map_err(|(_, e)| e)? {
+            Some(message) => message,
+            None => return Err("on_transmit dropped the message".into()),
+        },
+        serve::Submission::Bytes(bytes) => bytes,
+    };
+    let payload = seal(message, args, key, dict)?;
+    let frames = to_frames(&payload, true, false, args.sealing.fec, args.common.verbose)?;
+    let waveform = modulate_for_air(modem, &frames, args).map_err(|e| e.to_string())?;
+    let event = encoded_event(args, &payload, frames.len(), &waveform, started.elapsed(), None);
+    Ok((event, waveform))
+}
+
+// --dump-failures: the audio leading up to a message that failed to decode live
+#[cfg(all(feature = "audio", feature = "decode"))]
+fn dump_failure(args: &Args) {
+    let Some(dir) = args.capture.dump_failures.as_deref() else { return };
+    let millis = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_millis());
+    let path = dir.join(format!("failed-{}.wav", millis));
+    match std::fs::create_dir_all(dir).map_err(|e| e.to_string()).and_then(|_| audio::save_recent(&path)) {
+        Ok(()) => eprintln!("Saved the last {} s of audio to {}", audio::RECENT_SECONDS, path.display()),
+        Err(e) => eprintln!("Failed to save {}: {}", path.display(), e),
+    }
+}
+
+// A chat line or bridged message through the same compress/dict/key/crc/chunking steps as a
+// transmission, played right away
+#[cfg(all(feature = "audio", feature = "decode"))]
+fn send_message(modem: &dyn Modem, args: &Args, message: Vec<u8>, key: Option<&Key>, dict: Option<&[u8]>) -> Result<(), String> {
+    send_sealed(modem, args, seal(message, args, key, dict)?)
+}
+
+// send_message() once the payload is sealed
+#[cfg(all(feature = "audio", feature = "decode"))]
+fn send_sealed(modem: &dyn Modem, args: &Args, payload: Vec<u8>) -> Result<(), String> {
+    let frames = to_frames(&payload, true, false, args.sealing.fec, args.common.verbose)?;
+    let waveform = modulate_for_air(modem, &frames, args).map_err(|e| e.to_string())?;
+    play_with_retries(args.playback.retries, || play_waveform(&waveform, args.playback.device.as_deref()))
+}
+
+#[cfg(all(feature = "audio", feature = "decode"))]
+fn chat_clock() -> String {
+    #[cfg(feature = "template")]
+    return chrono::Local::now().format("%H:%M:%S").to_string();
+    // UTC without chrono
+    #[cfg(not(feature = "template"))]
+    {
+        let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        format!("{:02}:{:02}:{:02}", secs / 3600 % 24, secs / 60 % 60, secs % 60)
+    }
+}
+
+// Parsed options and the matches they came from (for telling explicit flags from defaults)
+fn parse_args() -> (Args, clap::ArgMatches) {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    match (args.command.take(), matches.subcommand()) {
+        (Some(command), Some((_, sub))) => (command.into_args(), sub.clone()),
+        _ => (args, matches),
+    }
+}
+
+// --decode-wav (or `rx`'s input) when it names a network stream rather than a file
+fn stream_url(args: &Args) -> Option<&str> {
+    let input = args.decode_wav.as_deref()?.to_str()?;
+    input.split_once("://").is_some_and(|(scheme, _)| !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))).then_some(input)
+}
+
+// First optional feature this invocation needs that the build left out
+fn missing_feature(args: &Args) -> Option<&'static str> {
+    let needed = [
+        ("decode", args.decode_wav.is_some() || args.decode_raw.is_some() || args.listen || args.bench || args.chat || !args.bridge.is_empty() || args.agent.is_some() || args.serve.any(), cfg!(feature = "decode")),
+        ("stream", stream_url(args).is_some(), cfg!(feature = "stream")),
+        ("watch", args.watch.is_some(), cfg!(feature = "watch")),
+        ("serve", args.serve.any() || args.serve.token_file.is_some(), cfg!(feature = "serve")),
+        ("agent", args.agent.is_some(), cfg!(feature = "agent")),
+        ("tls", args.serve.tls_cert.is_some() || !args.serve.tls_self_signed.is_empty(), cfg!(feature = "tls")),
+        ("audio", args.listen || args.spectrum || args.chat || !args.bridge.is_empty() || args.agent.is_some() || args.watch.is_some() || args.serve.any() || args.encode.no_wav || args.encode.reliable || args.playback.device.is_some() || args.playback.list_output_devices || args.playback.test_tone
+            || args.capture.input_device.is_some() || args.capture.monitor_of.is_some() || args.playback.buffer_frames.is_some() || args.playback.output_latency_ms.is_some() || args.capture.list_input_devices
+            || args.capture.record_to.is_some() || args.capture.dump_failures.is_some() || args.capture.levels, cfg!(feature = "audio")),
+        ("cbor", args.common.payload_format == PayloadFormat::Cbor, cfg!(feature = "cbor")),
+        ("msgpack", args.common.payload_format == PayloadFormat::Msgpack, cfg!(feature = "msgpack")),
+        ("protobuf", args.common.payload_format == PayloadFormat::Protobuf, cfg!(feature = "protobuf")),
+        ("json-schema", args.steps.json_schema.is_some(), cfg!(feature = "json-schema")),
+        ("dict", args.common.dict.is_some(), cfg!(feature = "dict")),
+        ("compress", args.sealing.compress.is_some(), cfg!(feature = "compress")),
+        ("fec", args.sealing.fec.is_some(), cfg!(feature = "fec")),
+        ("crypto", args.common.key.is_some() || args.common.key_file.is_some(), cfg!(feature = "crypto")),
+        ("script", args.common.script.is_some(), cfg!(feature = "script")),
+        ("qr", args.encode.qr.is_some(), cfg!(feature = "qr")),
+        ("spectrogram", args.recording.spectrogram.is_some() || args.spectrum, cfg!(feature = "spectrogram")),
+        ("template", args.steps.template, cfg!(feature = "template")),
+        ("unicode", args.steps.normalize.is_some() || args.steps.ascii, cfg!(feature = "unicode")),
+    ];
+    needed.into_iter().find(|&(_, used, built)| used && !built).map(|(name, _, _)| name)
+}
+
+fn main() {
+    let (mut args, matches) = parse_args();
+    if let Err(e) = apply_config(&mut args, &matches) {
+        eprintln!("Config error: {}", e);
+        std::process::exit(1);
+    }
+    if let Some(feature) = missing_feature(&args) {
+        eprintln!("{}", without(feature));
+        std::process::exit(1);
+    }
+    #[cfg(feature = "audio")]
+    if let Some(frames) = args.playback.buffer_frames {
+        audio::set_buffer_frames(frames);
+    }
+    #[cfg(feature = "audio")]
+    if let Some(ms) = args.playback.output_latency_ms {
+        audio::set_output_latency(std::time::Duration::from_millis(ms.into()));
+    }
+    #[cfg(feature = "audio")]
+    if let Some(output) = &args.capture.monitor_of {
+        if let Err(e) = audio::monitor_of(output) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    #[cfg(feature = "audio")]
+    if let Some(path) = args.capture.record_to.clone() {
+        audio::record_to(path);
+    }
+    #[cfg(feature = "audio")]
+    if args.capture.dump_failures.is_some() {
+        audio::keep_recent();
+    }
+    #[cfg(feature = "audio")]
+    if args.capture.levels {
+        audio::report_levels(|rms_dbfs, peak_dbfs| print_event(&Event::Level { rms_dbfs, peak_dbfs }));
+    }
+    #[cfg(feature = "audio")]
+    if args.playback.list_output_devices || args.capture.list_input_devices {
+        let devices = if args.capture.list_input_devices { audio::input_devices() } else { audio::output_devices() };
+        match devices {
+            Ok(devices) => {
+                for device in devices {
+                    let rates: Vec<String> = device
+                        .sample_rates
+                        .iter()
+                        .map(|&(min, max)| if min == max { min.to_string() } else { format!("{}-{}", min, max) })
+                        .collect();
+                    let kind = if device.is_virtual { " [virtual]" } else { "" };
+                    println!("{} {} ({} Hz){}", if device.is_default { "*" } else { " " }, device.name, rates.join(", "), kind);
+                }
+                if args.capture.list_input_devices {
+                    for output in audio::monitors() {
+                        println!("  Monitor of {} (--monitor-of {:?})", output, output);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to list devices: {}", e);
+                std::process::exit(5);
+            }
+        }
+        return;
+    }
+    #[cfg(feature = "audio")]
+    if args.playback.test_tone {
+        const RATE: u32 = 48000;
+        let amplitude = 0.5 * args.signal.volume.clamp(0, 100) as f32 / 100.0;
+        let tone: Vec<f32> = (0..RATE).map(|i| amplitude * (std::f32::consts::TAU * 1000.0 * i as f32 / RATE as f32).sin()).collect();
+        if let Err(e) = audio::play(&tone, RATE, args.playback.device.as_deref()) {
+            eprintln!("Playback failed: {}", e);
+            std::process::exit(5);
+        }
+        return;
+    }
+    #[cfg(all(feature = "audio", feature = "spectrogram"))]
+    if args.spectrum {
+        if let Err(e) = run_spectrum(&args) {
+            eprintln!("Spectrum failed: {}", e);
+            std::process::exit(6);
+        }
+        return;
+    }
+    gibberlink_core::disable_logging();
+    // Raw PCM on stdout is generated at --raw-rate, so pipelines only state the rate once
+    let raw_out = args.encode.out.as_os_str() == "-";
+    if raw_out && args.recording.raw_rate.is_some() {
+        args.signal.sample_rate = args.recording.raw_rate;
+    }
+
+    if let (Some(samples), Some(dict_path)) = (args.encode.train_dict.as_ref(), args.common.dict.as_ref()) {
+        match train_dict(samples, dict_path) {
+            Ok(n) => { println!("Wrote {} byte dictionary to {}", n, dict_path.display()); return; }
+            Err(e) => {
+                eprintln!("Dictionary training failed: {}", e);
+                std::process::exit(8);
+            }
+        }
+    }
+    let dict = match args.common.dict.as_ref().map(std::fs::read).transpose() {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Failed to read dictionary: {}", e);
+            std::process::exit(8);
+        }
+    };
+
+    let key = match load_key(args.common.key.as_deref(), args.common.key_file.as_deref()) {
+        Ok(k) => k,
+        Err(e) => {
+            eprintln!("Invalid key: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let script = match args.common.script.as_deref().map(Script::load).transpose() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Script error: {}", e);
+            std::process::exit(9);
+        }
+    };
+
+    let proto = if args.common.payload_format == PayloadFormat::Protobuf {
+        match load_proto_message(args.common.proto_descriptor.as_deref(), args.common.proto_message.as_deref()) {
+            Ok(desc) => Some(desc),
+            Err(e) => {
+                eprintln!("Invalid protobuf schema: {}", e);
+                std::process::exit(7);
+            }
+        }
+    } else {
+        None
+    };
+
+    let modem = match build_modem(&args) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    #[cfg(feature = "decode")]
+    if args.bench {
+        if let Err(e) = run_bench(&args) {
+            eprintln!("Benchmark failed: {}", e);
+            std::process::exit(3);
+        }
+        return;
+    }
+
+    // Live network stream: decoded as it arrives, like the microphone
+    #[cfg(feature = "stream")]
+    if let Some(url) = stream_url(&args) {
+        if args.common.modem != ModemKind::Ggwave {
+            eprintln!("Stream decoding only supports --modem ggwave");
+            std::process::exit(1);
+        }
+        truncate_out_bin(&args);
+        let mut reassembler = Reassembler::new();
+        let mut failed = false;
+        let params = ggwave_params(&args).unwrap_or_default();
+        let result = stream::listen(url, params, |bytes| {
+            match unframe(&mut reassembler, bytes, key.as_ref(), dict.as_deref(), args.common.verbose, &mut |e| report_progress(args.common.json, e)) {
+                Ok(Some(bytes)) => emit_decoded(&args, bytes, script.as_ref(), proto.as_ref()),
+                Ok(None) => {}
+                Err(e) => {
+                    report_failure(args.common.json, &e.to_string());
+                    failed = true;
+                }
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("Stream failed: {}", e);
+            std::process::exit(6);
+        }
+        for (id, received, count) in reassembler.incomplete() {
+            report_failure(args.common.json, &format!("message {} incomplete, {} of {} chunks received", id, received, count));
+            failed = true;
+        }
+        if failed {
+            std::process::exit(6);
+        }
+        return;
+    }
+
+    // Decode mode
+    #[cfg(feature = "decode")]
+    if args.decode_wav.is_some() || args.decode_raw.is_some() {
+        if args.decode.detect && args.common.modem != ModemKind::Ggwave {
+            eprintln!("--detect only supports --modem ggwave");
+            std::process::exit(1);
+        }
+        let mut stats = DecodeStats::default();
+        truncate_out_bin(&args);
+        let mut reassembler = Reassembler::new();
+        let mut remaining = args.decode.max_messages.unwrap_or(u64::MAX);
+        let mut messages = 0;
+        let mut failures = Vec::new();
+        // Unframed as they are demodulated, so --max-messages stops decoding the rest of the recording
+        let decoded = decode_recording(modem.as_ref(), &args, &mut stats, &mut |bytes| {
+            match unframe(&mut reassembler, bytes, key.as_ref(), dict.as_deref(), args.common.verbose, &mut |e| report_progress(args.common.json, e)) {
+                Ok(Some(bytes)) => {
+                    emit_decoded(&args, bytes, script.as_ref(), proto.as_ref());
+                    messages += 1;
+                    remaining -= 1;
+                    if remaining == 0 {
+                        return ControlFlow::Break(());
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    report_failure(args.common.json, &e.message);
+                    failures.push(e.stage);
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        stats.messages = messages;
+        for stage in failures {
+            stats.fail(stage);
+        }
+        if let Err(e) = decoded {
+            eprintln!("Decode failed: {}", e);
+            if args.decode.stats {
+                stats.print(args.common.modem);
+            }
+            std::process::exit(6);
+        }
+        // Chunks of later messages are expected to be missing when --max-messages stopped early
+        for (id, received, count) in reassembler.incomplete().filter(|_| remaining > 0) {
+            report_failure(args.common.json, &format!("message {} incomplete, {} of {} chunks received", id, received, count));
+            stats.fail("incomplete");
+        }
+        if args.decode.stats {
+            stats.print(args.common.modem);
+        }
+        if args.common.json {
+            print_event(&stats.summary(args.common.modem));
+        }
+        if stats.failed() > 0 {
+            std::process::exit(6);
+        }
+        return;
+    }
+
+    // Live receive mode
+    #[cfg(all(feature = "audio", feature = "decode"))]
+    if args.listen {
+        if args.common.modem != ModemKind::Ggwave {
+            eprintln!("--listen only supports --modem ggwave");
+            std::process::exit(1);
+        }
+        // Acknowledgements are transmissions too
+        if args.ack {
+            if let Err(e) = check_ceiling(modem.as_ref(), &args) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        truncate_out_bin(&args);
+        let mut reassembler = Reassembler::new();
+        // build_modem() has already rejected a bad list
+        let params = ggwave_params(&args).unwrap_or_default();
+        let result = audio::listen(args.capture.input_device.as_deref(), params, |bytes| {
+            if let (true, Some(Control::Poll { id, count })) = (args.ack, Control::parse(&bytes)) {
+                let ack = Control::Ack { id, received: reassembler.received(id, count as usize) };
+                let sent = modulate_for_air(modem.as_ref(), &[ack.to_bytes()], &args)
+                    .map_err(|e| e.to_string())
+                    .and_then(|w| play_with_retries(args.playback.retries, || play_waveform(&w, args.playback.device.as_deref())));
+                if let Err(e) = sent {
+                    eprintln!("Acknowledgement failed: {}", e);
+                }
+                return;
+            }
+            match unframe(&mut reassembler, bytes, key.as_ref(), dict.as_deref(), args.common.verbose, &mut |e| report_progress(args.common.json, e)) {
+                Ok(Some(bytes)) => emit_decoded(&args, bytes, script.as_ref(), proto.as_ref()),
+                Ok(None) => {}
+                Err(e) => {
+                    report_failure(args.common.json, &e.to_string());
+                    dump_failure(&args);
+                }
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("Listen failed: {}", e);
+            std::process::exit(6);
+        }
+        return;
+    }
+
+    // Full-duplex chat: a second thread sends typed lines while this one keeps listening
+    #[cfg(all(feature = "audio", feature = "decode"))]
+    if args.chat {
+        if args.common.modem != ModemKind::Ggwave {
+            eprintln!("--chat only supports --modem ggwave");
+            std::process::exit(1);
+        }
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+        let peer = args.peer_id.clone().unwrap_or_else(|| format!("{:04x}", nanos as u16));
+        if let Err(e) = chat::pack(&peer, "") {
+            eprintln!("Invalid --peer-id: {}", e);
+            std::process::exit(1);
+        }
+        if let Err(e) = check_ceiling(modem.as_ref(), &args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        eprintln!("Chatting as {}; type a line and press Enter to send, Ctrl+D to quit", peer);
+        let params = ggwave_params(&args).unwrap_or_default();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for line in std::io::stdin().lines().map_while(Result::ok) {
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    if let Err(e) = chat::pack(&peer, line).and_then(|m| seal_frame(m, &args, key.as_ref(), dict.as_deref())).and_then(|p| send_sealed(modem.as_ref(), &args, p)) {
+                        eprintln!("Send failed: {}", e);
+                    }
+                }
+                std::process::exit(0);
+            });
+            let mut reassembler = Reassembler::new();
+            let result = audio::listen(args.capture.input_device.as_deref(), params, |bytes| match unframe(&mut reassembler, bytes, key.as_ref(), dict.as_deref(), args.common.verbose, &mut |e| report_progress(args.common.json, e)) {
+                Ok(Some(bytes)) => match chat::unpack(&bytes) {
+                    // Our own lines, picked up by the microphone as they play
+                    Some(message) if message.peer == peer => {}
+                    Some(message) if args.common.json => print_event(&Event::Chat { time: chat_clock(), peer: message.peer, text: message.text }),
+                    Some(message) => println!("[{}] <{}> {}", chat_clock(), message.peer, message.text),
+                    // Plain transmissions from `tx` still show up
+                    None => emit_decoded(&args, bytes, script.as_ref(), proto.as_ref()),
+                },
+                Ok(None) => {}
+                Err(e) => {
+                    report_failure(args.common.json, &e.to_string());
+                    dump_failure(&args);
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Listen failed: {}", e);
+                std::process::exit(6);
+            }
+        });
+        return;
+    }
+
+    // Child-process bridge: a second thread transmits each line the program prints while this one
+    // writes every message heard to its stdin
+    #[cfg(all(feature = "audio", feature = "decode"))]
+    if let Some((program, program_args)) = args.bridge.split_first() {
+        if args.common.modem != ModemKind::Ggwave {
+            eprintln!("bridge only supports --modem ggwave");
+            std::process::exit(1);
+        }
+        if let Err(e) = check_ceiling(modem.as_ref(), &args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        let spawned = std::process::Command::new(program)
+            .args(program_args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Failed to start {}: {}", program, e);
+                std::process::exit(5);
+            }
+        };
+        let (mut input, output) = (child.stdin.take().expect("stdin is piped"), child.stdout.take().expect("stdout is piped"));
+        let echoes = Echoes::default();
+        let params = ggwave_params(&args).unwrap_or_default();
+        let (args, modem, key, dict, proto, echoes) = (&args, &modem, key.as_ref(), dict.as_deref(), proto.as_ref(), &echoes);
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                // Scripts aren't shared across threads, so the transmitter loads its own
+                let script = args.common.script.as_deref().map(Script::load).transpose().expect("script already loaded once");
+                use std::io::BufRead;
+                for line in std::io::BufReader::new(output).lines().map_while(Result::ok) {
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let message = match prepare_text(line.to_owned(), args, script.as_ref(), proto) {
+                        Ok(Some(message)) => message,
+                        Ok(None) => continue,
+                        Err((_, e)) => {
+                            eprintln!("{}", e);
+                            continue;
+                        }
+                    };
+                    // Recorded before playing: the microphone can decode it before playback returns
+                    echoes.sent(&message);
+                    if let Err(e) = send_message(modem.as_ref(), args, message, key, dict) {
+                        eprintln!("Send failed: {}", e);
+                    }
+                }
+                // The program closed its stdout; its exit status becomes ours
+                let status = child.wait().map_or(1, |s| s.code().unwrap_or(1));
+                std::process::exit(status);
+            });
+            let mut reassembler = Reassembler::new();
+            let result = audio::listen(args.capture.input_device.as_deref(), params, |bytes| match unframe(&mut reassembler, bytes, key, dict, args.common.verbose, &mut |e| report_progress(args.common.json, e)) {
+                Ok(Some(bytes)) if echoes.is_echo(&framing::unescape(bytes.clone())) => {}
+                Ok(Some(bytes)) => {
+                    let Some(mut bytes) = process_received(args, bytes, script.as_ref()) else { return };
+                    bytes.push(b'\n');
+                    if let Err(e) = input.write_all(&bytes).and_then(|_| input.flush()) {
+                        eprintln!("Failed to write to {}: {}", program, e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    report_failure(args.common.json, &e.to_string());
+                    dump_failure(args);
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Listen failed: {}", e);
+                std::process::exit(6);
+            }
+        });
+        return;
+    }
+
+    // LLM agent: this thread queues what the microphone hears; a second one waits for the peer to
+    // finish, answers everything heard since its last turn at once and transmits the reply
+    #[cfg(all(feature = "agent", feature = "audio", feature = "decode"))]
+    if let Some(options) = args.agent.as_ref() {
+        if args.common.modem != ModemKind::Ggwave {
+            eprintln!("agent only supports --modem ggwave");
+            std::process::exit(1);
+        }
+        if let Err(e) = check_ceiling(modem.as_ref(), &args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        // What a reply may take up once sealed: one plain transmission, or that many chunks
+        let budget = match options.reply_frames {
+            1 => gibberlink_core::MAX_LENGTH_VARIABLE,
+            n => n as usize * chunk::CHUNK_DATA_LEN,
+        };
+        let fits = |message: &[u8]| seal(message.to_vec(), &args, key.as_ref(), dict.as_deref()).is_ok_and(|sealed| sealed.len() <= budget);
+        let overhead = seal(Vec::new(), &args, key.as_ref(), dict.as_deref()).map_or(0, |sealed| sealed.len());
+        let system = format!("{} Replies are sent as sound and cut off after {} bytes.", options.system, budget.saturating_sub(overhead));
+        let api_key = std::env::var(&options.api_key_env).ok().filter(|key| !key.is_empty());
+        let mut model = agent::Agent::new(&options.url, &options.model, system, api_key, options.history, std::time::Duration::from_secs(options.timeout));
+        let turn_gap = std::time::Duration::from_secs_f32(options.turn_gap.max(0.0));
+        let echoes = Echoes::default();
+        let (heard_tx, heard) = std::sync::mpsc::channel::<String>();
+        let params = ggwave_params(&args).unwrap_or_default();
+        let (args, modem, key, dict, proto, echoes) = (&args, &modem, key.as_ref(), dict.as_deref(), proto.as_ref(), &echoes);
+        eprintln!("Agent using {} at {}; replies up to {} bytes", options.model, options.url, budget.saturating_sub(overhead));
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                let script = args.common.script.as_deref().map(Script::load).transpose().expect("script already loaded once");
+                while let Ok(first) = heard.recv() {
+                    // The peer's turn lasts until it has been quiet for --turn-gap
+                    let mut turn = vec![first];
+                    while let Ok(more) = heard.recv_timeout(turn_gap) {
+                        turn.push(more);
+                    }
+                    let reply = match model.reply(&turn.join("\n")) {
+                        Ok(reply) if reply.is_empty() => continue,
+                        Ok(reply) => reply,
+                        Err(e) => {
+                            eprintln!("Agent request failed: {}", e);
+                            continue;
+                        }
+                    };
+                    let message = match prepare_text(reply.clone(), args, script.as_ref(), proto) {
+                        Ok(Some(message)) => message,
+                        Ok(None) => continue,
+                        Err((_, e)) => {
+                            eprintln!("{}", e);
+                            continue;
+                        }
+                    };
+                    let message = match std::str::from_utf8(&message) {
+                        Ok(text) => agent::truncate(text, |t| fits(t.as_bytes())).as_bytes().to_vec(),
+                        Err(_) => message,
+                    };
+                    let said = String::from_utf8_lossy(&message).into_owned();
+                    if said.len() < reply.len() {
+                        eprintln!("Reply cut from {} to {} bytes to fit {} transmission(s)", reply.len(), said.len(), options.reply_frames);
+                    }
+                    if args.common.json {
+                        print_event(&Event::Chat { time: chat_clock(), peer: "agent".into(), text: said });
+                    } else {
+                        println!("[{}] <agent> {}", chat_clock(), said);
+                    }
+                    // Recorded before playing: the microphone can decode it before playback returns
+                    echoes.sent(&message);
+                    if let Err(e) = send_message(modem.as_ref(), args, message, key, dict) {
+                        eprintln!("Send failed: {}", e);
+                    }
+                }
+            });
+            let mut reassembler = Reassembler::new();
+            let result = audio::listen(args.capture.input_device.as_deref(), params, |bytes| match unframe(&mut reassembler, bytes, key, dict, args.common.verbose, &mut |e| report_progress(args.common.json, e)) {
+                Ok(Some(bytes)) if echoes.is_echo(&framing::unescape(bytes.clone())) => {}
+                Ok(Some(bytes)) => {
+                    let Some(bytes) = process_received(args, bytes, script.as_ref()) else { return };
+                    let text = String::from_utf8_lossy(&bytes).into_owned();
+                    if args.common.json {
+                        print_event(&Event::Chat { time: chat_clock(), peer: "peer".into(), text: text.clone() });
+                    } else {
+                        println!("[{}] <peer> {}", chat_clock(), text);
+                    }
+                    let _ = heard_tx.send(text);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    report_failure(args.common.json, &e.to_string());
+                    dump_failure(args);
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Listen failed: {}", e);
+                std::process::exit(6);
+            }
+        });
+        return;
+    }
+
+    if let Err(e) = check_ceiling(modem.as_ref(), &args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    if args.sealing.fec.is_some() && args.common.modem != ModemKind::Ggwave {
+        eprintln!("--fec only supports --modem ggwave");
+        std::process::exit(1);
+    }
+    // Sound modem daemon: the microphone is decoded here, network clients are served on another
+    // thread and their payloads are played one at a time by a third
+    #[cfg(all(feature = "serve", feature = "audio", feature = "decode"))]
+    if args.serve.any() {
+        if args.common.modem != ModemKind::Ggwave {
+            eprintln!("--ws, --http, --mqtt, --serial and --udp only support --modem ggwave");
+            std::process::exit(1);
+        }
+        let token = match load_token(args.serve.token_file.as_deref()) {
+            Ok(token) => token,
+            Err(e) => {
+                eprintln!("Failed to load token: {}", e);
+                std::process::exit(1);
+            }
+        };
+        #[cfg(feature = "tls")]
+        let tls = match (&args.serve.tls_cert, &args.serve.tls_key) {
+            (Some(cert), Some(key)) => Some(tls::load(cert, key)),
+            _ if !args.serve.tls_self_signed.is_empty() => Some(tls::self_signed(&args.serve.tls_self_signed).map(|(config, fingerprint)| {
+                eprintln!("Self-signed certificate for {}, SHA-256 fingerprint {}", args.serve.tls_self_signed.join(", "), fingerprint);
+                config
+            })),
+            _ => None,
+        };
+        #[cfg(feature = "tls")]
+        let tls = match tls.transpose() {
+            Ok(tls) => tls,
+            Err(e) => {
+                eprintln!("Failed to load TLS certificate: {}", e);
+                std::process::exit(1);
+            }
+        };
+        for (flag, addr) in [("--ws", &args.serve.ws), ("--http", &args.serve.http)] {
+            if let Err(e) = addr.as_deref().map_or(Ok(()), |addr| serve::check_exposure(flag, addr, token.as_deref())) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        let hub = serve::Hub::default();
+        let received = serve::Hub::<Vec<u8>>::default();
+        let queue = &match args.serve.rate_limit {
+            0 => serve::Queue::default(),
+            per_minute => serve::Queue::with_rate_limit(serve::RateLimit { per_minute, burst: args.serve.rate_burst }),
+        };
+        let health = &serve::Health::default();
+        let params = ggwave_params(&args).unwrap_or_default();
+        // Every change to the queue goes out as a `queue` event
+        let queue_changed = || hub.broadcast(&serde_json::to_string(&Event::Queue(queue.status())).expect("events always serialize"));
+        let submit = |client: &str, submission, schedule: serve::Schedule, play| {
+            let (reply, answer) = std::sync::mpsc::channel();
+            let priority = schedule.priority;
+            let ahead = match queue.push(client, priority, serve::Job { submission, play, at: schedule.at, reply }) {
+                Ok(ahead) => ahead,
+                Err(wait) => {
+                    let message = format!("rate limit exceeded by {}; retry in {:.1} s", client, wait.as_secs_f64());
+                    let event = serde_json::to_string(&Event::Warning { message }).expect("events always serialize");
+                    return serve::Reply { ok: false, event, wav: Vec::new(), retry_after: Some(wait) };
+                }
+            };
+            if ahead > 0 {
+                eprintln!("Transmitter busy; {:?} priority submission queued behind {}", priority, ahead);
+            }
+            queue_changed();
+            answer.recv().expect("transmitter runs until exit")
+        };
+        let (args, modem, key, dict, proto) = (&args, &modem, key.as_ref(), dict.as_deref(), proto.as_ref());
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                // Scripts aren't shared across threads, so the transmitter loads its own
+                let script = args.common.script.as_deref().map(Script::load).transpose().expect("script already loaded once");
+                loop {
+                    let serve::Job { submission, play, at, reply } = queue.pop();
+                    queue_changed();
+                    let submission = match (submission, at) {
+                        (Ok(_), Some(_)) if !play => Err("\"at\" only applies to submissions that are transmitted".into()),
+                        (submission, _) => submission,
+                    };
+                    let result = submission.and_then(|s| encode_submission(s, modem.as_ref(), args, key, dict, script.as_ref(), proto)).and_then(|(event, waveform)| {
+                        let mut wav = Vec::new();
+                        if play {
+                            // A scheduled start can't be retried: a second attempt would be late
+                            let retries = if at.is_some() { 0 } else { args.playback.retries };
+                            let played = play_with_retries(retries, || play_waveform_at(&waveform, args.playback.device.as_deref(), at));
+                            health.played(played.as_ref().err().map(String::as_str));
+                            played?;
+                        } else {
+                            write_wav_to(&mut wav, waveform.sample_rate, ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16, &waveform.to_le_bytes()).map_err(|e| e.to_string())?;
+                        }
+                        Ok((event, wav))
+                    });
+                    let (ok, (event, wav)) = match result {
+                        Ok(reply) => (true, reply),
+                        Err(message) => (false, (Event::Warning { message }, Vec::new())),
+                    };
+                    let event = serde_json::to_string(&event).expect("events always serialize");
+                    queue.done();
+                    queue_changed();
+                    let _ = reply.send(serve::Reply { ok, event, wav, retry_after: None });
+                }
+            });
+            let hub = &hub;
+            let submit = &submit;
+            let api = serve::Api {
+                hub,
+                submit,
+                queue,
+                health,
+                token: token.as_deref(),
+                #[cfg(feature = "tls")]
+                tls: tls.as_ref(),
+            };
+            if let Some(addr) = args.serve.ws.as_deref() {
+                scope.spawn(move || {
+                    if let Err(e) = serve::websocket(addr, api) {
+                        eprintln!("Serve failed: {}", e);
+                        std::process::exit(5);
+                    }
+                });
+            }
+            if let Some(addr) = args.serve.http.as_deref() {
+                scope.spawn(move || {
+                    if let Err(e) = serve::http(addr, api) {
+                        eprintln!("Serve failed: {}", e);
+                        std::process::exit(5);
+                    }
+                });
+            }
+            if let Some(broker) = args.serve.mqtt.as_deref() {
+                scope.spawn(move || {
+                    if let Err(e) = serve::mqtt(broker, &args.serve.mqtt_tx_topic, &args.serve.mqtt_rx_topic, hub, submit) {
+                        eprintln!("Serve failed: {}", e);
+                        std::process::exit(5);
+                    }
+                });
+            }
+            let received = &received;
+            if let Some(port) = args.serve.serial.as_deref() {
+                scope.spawn(move || {
+                    if let Err(e) = serve::serial(port, args.serve.serial_baud, received, submit) {
+                        eprintln!("Serve failed: {}", e);
+                        std::process::exit(5);
+                    }
+                });
+            }
+            if let Some(addr) = args.serve.udp.as_deref() {
+                scope.spawn(move || {
+                    if let Err(e) = serve::udp(addr, args.serve.udp_peer.as_deref(), &args.serve.udp_from, received, submit) {
+                        eprintln!("Serve failed: {}", e);
+                        std::process::exit(5);
+                    }
+                });
+            }
+            let broadcast = |event: &Event| hub.broadcast(&serde_json::to_string(event).expect("events always serialize"));
+            let mut reassembler = Reassembler::new();
+            let result = audio::listen(args.capture.input_device.as_deref(), params, |bytes| match unframe(&mut reassembler, bytes, key, dict, args.common.verbose, &mut |e| broadcast(&e)) {
+                Ok(Some(bytes)) => {
+                    health.decoded();
+                    if let Some(bytes) = process_received(args, bytes, script.as_ref()) {
+                        broadcast(&decoded_event(&bytes));
+                        received.broadcast(&bytes);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    broadcast(&Event::Warning { message: e.message });
+                    dump_failure(args);
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Listen failed: {}", e);
+                std::process::exit(6);
+            }
+        });
+        return;
+    }
+
+    // Watch-folder daemon: every .txt/.json file dropped into the directory is sent and moved away
+    #[cfg(all(feature = "watch", feature = "audio"))]
+    if let Some(dir) = args.watch.as_deref() {
+        let done = args.done_dir.clone().unwrap_or_else(|| dir.join("done"));
+        if let Err(e) = std::fs::create_dir_all(&done) {
+            eprintln!("Failed to create {}: {}", done.display(), e);
+            std::process::exit(5);
+        }
+        eprintln!("Watching {} for .txt/.json files (sent files move to {}); Ctrl+C to stop", dir.display(), done.display());
+        let result = watch::run(dir, |path| {
+            let ext = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+            if !matches!(ext.as_deref(), Some("txt" | "json")) {
+                return;
+            }
+            // Failed files stay where they are; saving them again retries
+            if let Err(e) = send_watched(path, modem.as_ref(), &args, key.as_ref(), dict.as_deref(), script.as_ref(), proto.as_ref()) {
+                eprintln!("Failed to send {}: {}", path.display(), e);
+                return;
+            }
+            match move_into(&done, path) {
+                Ok(dest) => status(args.common.json, format_args!("Sent {} (moved to {})", path.display(), dest.display())),
+                Err(e) => eprintln!("Sent {} but failed to move it: {}", path.display(), e),
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("Watch failed: {}", e);
+            std::process::exit(5);
+        }
+        return;
+    }
+
+    // Batch mode: each line is its own message, written to files rather than played
+    if let Some(input) = args.encode.batch.as_deref() {
+        if args.encode.out_dir.is_none() && args.encode.concat.is_none() {
+            eprintln!("--batch needs --out-dir and/or --concat");
+            std::process::exit(1);
+        }
+        let read = if input.as_os_str() == "-" {
+            let mut buf = String::new();
+            std::io::stdin().read_to_string(&mut buf).map(|_| buf)
+        } else {
+            std::fs::read_to_string(input)
+        };
+        let lines = match read {
+            Ok(lines) => lines,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", input.display(), e);
+                std::process::exit(1);
+            }
+        };
+        if let Some(dir) = args.encode.out_dir.as_deref() {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                eprintln!("Failed to create {}: {}", dir.display(), e);
+                std::process::exit(5);
+            }
+        }
+        let mut joined = Waveform { sample_rate: 0, samples: Vec::new() };
+        let mut encoded = 0;
+        for (number, line) in lines.lines().enumerate().map(|(i, line)| (i + 1, line.trim_end())) {
+            if line.is_empty() {
+                continue;
+            }
+            let started = std::time::Instant::now();
+            let message = match prepare_text(line.to_owned(), &args, script.as_ref(), proto.as_ref()) {
+                Ok(Some(message)) => message,
+                Ok(None) => continue,
+                Err((code, e)) => {
+                    eprintln!("Line {}: {}", number, e);
+                    std::process::exit(code);
+                }
+            };
+            let (payload, frames) = match seal(message, &args, key.as_ref(), dict.as_deref())
+                .and_then(|p| to_frames(&p, args.common.modem == ModemKind::Ggwave, false, args.sealing.fec, args.common.verbose).map(|f| (p, f)))
+            {
+                Ok(sealed) => sealed,
+                Err(e) => {
+                    eprintln!("Line {}: Invalid payload: {}", number, e);
+                    std::process::exit(7);
+                }
+            };
+            let waveform = match modulate_for_air(modem.as_ref(), &frames, &args) {
+                Ok(w) => w,
+                Err(e) => {
+                    eprintln!("Line {}: {}", number, e);
+                    std::process::exit(match e { ModemError::Init(_) => 2, ModemError::Encode(_) => 3 });
+                }
+            };
+            let out = args.encode.out_dir.as_ref().map(|dir| dir.join(format!("{:04}.wav", number)));
+            if let Some(path) = out.as_deref() {
+                if let Err(e) = write_wav(path, waveform.sample_rate, ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16, &waveform.to_le_bytes()) {
+                    eprintln!("Failed to write {}: {}", path.display(), e);
+                    std::process::exit(5);
+                }
+            }
+            if args.encode.concat.is_some() {
+                if encoded > 0 {
+                    joined.samples.resize(joined.samples.len() + (args.encode.gap.max(0.0) * waveform.sample_rate as f32) as usize, 0);
+                }
+                joined.sample_rate = waveform.sample_rate;
+                joined.samples.extend_from_slice(&waveform.samples);
+            }
+            if args.common.json {
+                print_event(&encoded_event(&args, &payload, frames.len(), &waveform, started.elapsed(), out));
+            }
+            encoded += 1;
+        }
+        if encoded == 0 {
+            eprintln!("No lines to encode in {}", input.display());
+            std::process::exit(1);
+        }
+        if let Some(dir) = args.encode.out_dir.as_deref() {
+            status(args.common.json, format_args!("Wrote {} WAV(s) to {}", encoded, dir.display()));
+        }
+        if let Some(path) = args.encode.concat.as_deref() {
+            if let Err(e) = write_wav(path, joined.sample_rate, ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16, &joined.to_le_bytes()) {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                std::process::exit(5);
+            }
+            status(args.common.json, format_args!("Wrote {} message(s) to {}", encoded, path.display()));
+        }
+        return;
+    }
+
+    // A file goes out as-is inside its envelope; text passes through the template/script/JSON steps
+    let framed = args.encode.file.is_some();
+    let message = if let Some(path) = args.encode.file.as_deref() {
+        let contents = match std::fs::read(path) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        };
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        file::pack(&name, &contents)
+    } else if args.encode.input_format != InputFormat::Text {
+        // Bytes go out untouched: none of the text steps below apply
+        let text_only = [
+            ("--template", args.steps.template),
+            ("--normalize", args.steps.normalize.is_some()),
+            ("--ascii", args.steps.ascii),
+            ("--json-payload", args.steps.json_payload || args.steps.json_schema.is_some()),
+            ("--payload-format", args.common.payload_format != PayloadFormat::Text),
+        ];
+        if let Some((flag, _)) = text_only.iter().find(|(_, used)| *used) {
+            eprintln!("{} only applies to --input-format text", flag);
+            std::process::exit(1);
+        }
+        let input = match args.text.take() {
+            Some(t) => t.into_bytes(),
+            None => {
+                let mut buf = Vec::new();
+                std::io::stdin().read_to_end(&mut buf).expect("failed to read stdin");
+                buf
+            }
+        };
+        parse_binary_input(&input, args.encode.input_format)
+    } else {
+        // Read text
+        let text = match args.text.take() {
+            Some(t) => t,
+            None => {
+                let mut buf = String::new();
+                std::io::stdin().read_to_string(&mut buf).expect("failed to read stdin");
+                buf.trim_end().to_owned()
+            }
+        };
+        if text.is_empty() {
+            eprintln!("No text provided");
+            std::process::exit(1);
+        }
+        match prepare_text(text, &args, script.as_ref(), proto.as_ref()) {
+            Ok(Some(message)) => Ok(message),
+            Ok(None) => {
+                eprintln!("Nothing to send: on_transmit dropped the message");
+                return;
+            }
+            Err((code, e)) => {
+                eprintln!("{}", e);
+                std::process::exit(code);
+            }
+        }
+    };
+    let sealed = message.and_then(|m| if framed { seal_frame(m, &args, key.as_ref(), dict.as_deref()) } else { seal(m, &args, key.as_ref(), dict.as_deref()) });
+    let payload = match sealed {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Invalid payload: {}", e);
+            std::process::exit(7);
+        }
+    };
+
+    // --reliable always chunks, since acknowledgements refer to a message id and chunk indices
+    let frames = match to_frames(&payload, args.common.modem == ModemKind::Ggwave, args.encode.reliable, args.sealing.fec, args.common.verbose) {
+        Ok(frames) => frames,
+        Err(e) => {
+            eprintln!("Invalid payload: {}", e);
+            std::process::exit(7);
+        }
+    };
+
+    #[cfg(feature = "audio")]
+    if args.encode.reliable {
+        if args.common.modem != ModemKind::Ggwave {
+            eprintln!("--reliable only supports --modem ggwave");
+            std::process::exit(1);
+        }
+        if let Err(e) = send_reliable(modem.as_ref(), &frames, &args) {
+            eprintln!("Reliable send failed: {}", e);
+            std::process::exit(5);
+        }
+        println!("Delivered {} chunk(s)", frames.len());
+        return;
+    }
+
+    let started = std::time::Instant::now();
+    let waveform = match modulate_for_air(modem.as_ref(), &frames, &args) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(match e { ModemError::Init(_) => 2, ModemError::Encode(_) => 3 });
+        }
+    };
+
+    if let Some(png) = args.recording.spectrogram.as_deref() {
+        let samples: Vec<f32> = waveform.samples.iter().map(|&s| s as f32 / 32768.0).collect();
+        match write_spectrogram(png, &samples, waveform.sample_rate) {
+            // Reported on stderr: with --out - stdout carries the audio
+            Ok(step) => eprintln!("Wrote spectrogram to {} ({:.1} ms per column)", png.display(), step * 1000.0),
+            Err(e) => {
+                eprintln!("Failed to write spectrogram: {}", e);
+                std::process::exit(5);
+            }
+        }
+    }
+
+    // stdout carries the audio, so nothing else may be printed there and there is no file to play
+    if raw_out {
+        let buf: Vec<u8> = match args.recording.raw_format {
+            RawFormat::S16le => waveform.to_le_bytes(),
+            RawFormat::F32le => waveform.samples.iter().flat_map(|&s| (s as f32 / 32768.0).to_le_bytes()).collect(),
+        };
+        let mut stdout = std::io::stdout().lock();
+        if let Err(e) = stdout.write_all(&buf).and_then(|_| stdout.flush()) {
+            eprintln!("Failed to write raw PCM: {}", e);
+            std::process::exit(5);
+        }
+        if let Some(qr) = args.encode.qr.as_ref() {
+            if let Err(e) = write_qr_png(qr, &payload) {
+                eprintln!("Failed to write QR code: {}", e);
+                std::process::exit(5);
+            }
+        }
+        return;
+    }
+
+    // Write WAV
+    if !args.encode.no_wav {
+        let buf = waveform.to_le_bytes();
+        if let Err(e) = write_wav(&args.encode.out, waveform.sample_rate, ggwave_consts::GGWAVE_SAMPLE_FORMAT_I16, &buf) {
+            eprintln!("Failed to write WAV: {}", e);
+            std::process::exit(5);
+        }
+        if !args.common.json {
+            println!("Wrote {} bytes to {}", buf.len(), args.encode.out.display());
+        }
+    }
+    let encode_time = started.elapsed();
+
+    if let Some(qr) = args.encode.qr.as_ref() {
+        match write_qr_png(qr, &payload) {
+            Ok(()) => status(args.common.json, format_args!("Wrote QR code to {}", qr.display())),
+            Err(e) => {
+                eprintln!("Failed to write QR code: {}", e);
+                std::process::exit(5);
+            }
+        }
+    }
+
+    if args.common.json {
+        print_event(&encoded_event(&args, &payload, frames.len(), &waveform, encode_time, (!args.encode.no_wav).then(|| args.encode.out.clone())));
+    }
+
+    // --play is on by default, so builds without `audio` just write the file; an explicit --play
+    // (as the UI passes) gets told why nothing was heard
+    if args.encode.play && !cfg!(feature = "audio") {
+        if matches.value_source("play") == Some(ValueSource::CommandLine) {
+            eprintln!("Not played: {}", without("audio"));
+        }
+    } else if args.encode.play {
+        let backlog = args.encode.retry_queue.as_deref().map_or(Ok(0), |dir| flush_retry_queue(dir, args.playback.retries, args.playback.device.as_deref()));
+        if let Ok(n @ 1..) = backlog {
+            status(args.common.json, format_args!("Played {} queued message(s)", n));
+        }
+        // Don't jump the queue: if the backlog could not be sent, this message joins it
+        let played = backlog.and_then(|_| play_with_retries(args.playback.retries, || play_waveform(&waveform, args.playback.device.as_deref())));
+        if let Err(e) = played {
+            if args.encode.no_wav {
+                eprintln!("Playback failed: {}", e);
+            } else {
+                eprintln!("Playback failed: {} ({} was still written)", e, args.encode.out.display());
+            }
+            if let Some(dir) = args.encode.retry_queue.as_deref() {
+                match enqueue_for_retry(dir, &waveform) {
+                    Ok(path) => eprintln!("Queued for retry as {}", path.display()),
+                    Err(e) => {
+                        eprintln!("Failed to queue for retry: {}", e);
+                        std::process::exit(5);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "crypto", feature = "decode"))]
+mod tests {
+    use super::*;
+
+    fn passphrase() -> Key {
+        Key::Passphrase("correct horse".into())
+    }
+
+    #[test]
+    fn passphrase_round_trip() {
+        let sealed = encrypt(&passphrase(), b"hello").unwrap();
+        assert!(is_envelope(&sealed));
+        assert_eq!(sealed[1], ENVELOPE_SALTED);
+        assert_eq!(sealed.len(), 5 + ENVELOPE_OVERHEAD + SALT_LEN);
+        assert_eq!(decrypt(&passphrase(), &sealed).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn every_message_gets_its_own_salt() {
+        let (a, b) = (encrypt(&passphrase(), b"hello").unwrap(), encrypt(&passphrase(), b"hello").unwrap());
+        assert_ne!(a[2..2 + SALT_LEN], b[2..2 + SALT_LEN]);
+    }
+
+    #[test]
+    fn raw_key_round_trip() {
+        let key = Key::Raw([7; 32]);
+        let sealed = encrypt(&key, b"hello").unwrap();
+        assert_eq!(sealed[1], ENVELOPE_UNSALTED);
+        assert_eq!(sealed.len(), 5 + ENVELOPE_OVERHEAD);
+        assert_eq!(decrypt(&key, &sealed).unwrap(), b"hello");
+        assert!(decrypt(&Key::Raw([8; 32]), &sealed).is_err());
+    }
+
+    #[test]
+    fn wrong_passphrase_or_key_kind_fails() {
+        let sealed = encrypt(&passphrase(), b"hello").unwrap();
+        assert!(decrypt(&Key::Passphrase("wrong".into()), &sealed).is_err());
+        assert!(decrypt(&Key::Raw([7; 32]), &sealed).is_err());
+    }
+
+    #[test]
+    fn tampered_or_truncated_envelopes_fail() {
+        let sealed = encrypt(&passphrase(), b"hello").unwrap();
+        for i in [2, 2 + SALT_LEN, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[i] ^= 0x01;
+            assert!(decrypt(&passphrase(), &tampered).is_err(), "byte {} flipped", i);
+        }
+        assert!(decrypt(&passphrase(), &sealed[..ENVELOPE_OVERHEAD + SALT_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn stream_urls_are_told_from_recordings() {
+        let input = |path: &str| Args::parse_from(["gibberlink-tx", "--decode-wav", path]);
+        assert_eq!(stream_url(&input("http://radio.example:8000/live.mp3")), Some("http://radio.example:8000/live.mp3"));
+        assert_eq!(stream_url(&input("rtsp://camera.local/stream1")), Some("rtsp://camera.local/stream1"));
+        for path in ["recording.wav", "-", "C:\\recordings\\a.wav", "://nothing"] {
+            assert_eq!(stream_url(&input(path)), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn unsalted_envelopes_need_the_key_file() {
+        let sealed = encrypt(&Key::Raw([7; 32]), b"hello").unwrap();
+        assert!(decrypt(&passphrase(), &sealed).unwrap_err().contains("--key-file"));
+    }
+
+    #[test]
+    fn the_ceiling_refuses_protocols_above_it_and_names_those_below() {
+        let args = |protocol: &str| Args::parse_from(["gibberlink-tx", "--protocol", protocol, "--max-freq-hz", "8000"]);
+        let audible = args("audible:fast");
+        assert_eq!(check_ceiling(build_modem(&audible).unwrap().as_ref(), &audible), Ok(()));
+        let ultrasound = args("ultrasound:fast");
+        let refused = check_ceiling(build_modem(&ultrasound).unwrap().as_ref(), &ultrasound).unwrap_err();
+        assert!(refused.contains("above the 8000 Hz ceiling") && refused.contains("audible:fast"), "{}", refused);
+        assert!(!refused.contains("ultrasound:"), "{}", refused);
+        assert!(build_modem(&args("audibel")).is_err_and(|e| e.contains("unknown protocol")));
+    }
+
+    #[cfg(feature = "template")]
+    #[test]
+    fn templates_expand_placeholders_and_escaped_braces() {
+        let unix = expand_template("{{at}} {unix}").unwrap();
+        assert!(unix.strip_prefix("{at} ").is_some_and(|t| t.parse::<i64>().is_ok()), "{}", unix);
+        assert_eq!(expand_template("{now:%Y}").unwrap().len(), 4);
+        assert_eq!(expand_template("{env:PATH}").unwrap(), std::env::var("PATH").unwrap());
+        for (template, error) in [
+            ("a}", "unmatched '}'"),
+            ("{now", "unclosed '{'"),
+            ("{nope}", "unknown placeholder {nope}"),
+            ("{now:%Q}", "invalid time format"),
+            ("{env:GIBBERLINK_TEST_UNSET}", "GIBBERLINK_TEST_UNSET is not set"),
+        ] {
+            assert!(expand_template(template).is_err_and(|e| e.contains(error)), "{}", template);
+        }
+    }
+
+    #[test]
+    fn base45_follows_rfc_9285() {
+        assert_eq!(base45_decode("BB8").unwrap(), b"AB");
+        assert_eq!(base45_decode("%69 VD92EX0").unwrap(), b"Hello!!");
+        // A dangling digit, a character outside the alphabet, and groups past 0xffff and 0xff
+        for bad in ["BB8B", "bb8", ":::", "::"] {
+            assert_eq!(base45_decode(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn wrapped_payloads_are_unwrapped_by_their_alphabet() {
+        assert_eq!(unwrap_encoded(b"%69 VD92EX0"), Some(("base45", b"Hello!!".to_vec())));
+        assert_eq!(unwrap_encoded(b"aGVsbG8=\n"), Some(("base64", b"hello".to_vec())));
+        for plain in [&b"abc"[..], b"hello there", b"\xff\xfe\xfd\xfc"] {
+            assert_eq!(unwrap_encoded(plain), None, "{:?}", plain);
+        }
+    }
+
+    #[test]
+    fn reed_solomon_codes_are_checked() {
+        for spelling in ["rs(6,4)", " 6, 4 "] {
+            let code = parse_rs_code(spelling).unwrap();
+            assert_eq!((code.n, code.k), (6, 4));
+        }
+        for (code, error) in [("6", "expected rs(N,K)"), ("x,4", "invalid N"), ("6,y", "invalid K"), ("4,4", "0 < K < N"), ("6,0", "0 < K < N"), ("256,4", "N <= 255")] {
+            assert!(parse_rs_code(code).is_err_and(|e| e.contains(error)), "{}", code);
+        }
+    }
+
+    #[test]
+    fn binary_input_is_parsed_by_format() {
+        assert_eq!(parse_binary_input(b"0x de ad\n", InputFormat::Hex).unwrap(), [0xde, 0xad]);
+        assert_eq!(parse_binary_input(b"aGk=", InputFormat::Base64).unwrap(), b"hi");
+        assert_eq!(parse_binary_input(b"\0\xff", InputFormat::Raw).unwrap(), [0, 0xff]);
+        for (input, format, error) in [
+            (&b"\xff"[..], InputFormat::Hex, "not ASCII"),
+            (b"abc", InputFormat::Hex, "odd number"),
+            (b"zz", InputFormat::Hex, "invalid hex"),
+            (b"!!!!", InputFormat::Base64, "invalid base64"),
+            (b"", InputFormat::Text, "empty payload"),
+            (b" 0x ", InputFormat::Hex, "empty payload"),
+        ] {
+            assert!(parse_binary_input(input, format).is_err_and(|e| e.contains(error)), "{:?} as {:?}", input, format);
+        }
+    }
+
+    #[test]
+    fn rx_protocols_take_families_or_single_speeds() {
+        use ggwave_consts::*;
+        assert_eq!(rx_protocols(&[]), Ok(None));
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(rx_protocols(&names(&["dt"])), Ok(Some(vec![GGWAVE_PROTOCOL_DT_NORMAL, GGWAVE_PROTOCOL_DT_FAST, GGWAVE_PROTOCOL_DT_FASTEST])));
+        assert_eq!(rx_protocols(&names(&["audible:fast", "MT:Fastest"])), Ok(Some(vec![GGWAVE_PROTOCOL_AUDIBLE_FAST, GGWAVE_PROTOCOL_MT_FASTEST])));
+        assert!(rx_protocols(&names(&["audibel"])).is_err_and(|e| e.contains("family \"audibel\"")));
+        assert!(rx_protocols(&names(&["audible:slow"])).is_err_and(|e| e.contains("speed \"slow\"")));
+    }
+
+    #[test]
+    fn presets_fill_in_what_the_command_line_left_out() {
+        let path = std::env::temp_dir().join(format!("gibberlink-presets-{}.toml", std::process::id()));
+        std::fs::write(&path, "max_freq_hz = 9000.0\n\n[presets.quiet]\nprotocol = \"ultrasound:fast\"\nvolume = 10\n").unwrap();
+        let config = path.to_str().unwrap();
+        let apply = |extra: &[&str]| {
+            let matches = Args::command().get_matches_from(["gibberlink-tx", "--config", config].iter().chain(extra));
+            let mut args = Args::from_arg_matches(&matches).unwrap();
+            apply_config(&mut args, &matches).map(|()| args)
+        };
+        let quiet = apply(&["--preset", "quiet"]).unwrap();
+        assert_eq!((quiet.common.protocol.as_str(), quiet.signal.volume, quiet.shaping.max_freq_hz), ("ultrasound:fast", 10, Some(9000.0)));
+        let louder = apply(&["--preset", "quiet", "--volume", "80", "--max-freq-hz", "5000"]).unwrap();
+        assert_eq!((louder.common.protocol.as_str(), louder.signal.volume, louder.shaping.max_freq_hz), ("ultrasound:fast", 80, Some(5000.0)));
+        assert!(apply(&["--preset", "loud"]).is_err_and(|e| e.contains("preset \"loud\" not found")));
+        std::fs::write(&path, "presets = 3").unwrap();
+        assert!(apply(&[]).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(apply(&[]).is_err());
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn compressed_payloads_round_trip() {
+        let text = b"the quick brown fox jumps over the lazy dog, the quick brown fox jumps over the lazy dog".to_vec();
+        for (method, marker) in [(Compression::Zstd, ZSTD_MAGIC), (Compression::Deflate, DEFLATE_MAGIC)] {
+            let packed = compress(&text, method).unwrap();
+            assert_eq!(packed[0], marker);
+            assert!(packed.len() < text.len());
+            assert_eq!(decompress(&packed), Some(Ok(text.clone())));
+            assert!(matches!(decompress(&[marker, 0xff, 0xff, 0xff]), Some(Err(_))));
         }
+        assert_eq!(decompress(b"plain text"), None);
     }
 }
@@ -0,0 +1,419 @@
+// Modulation backends. ggwave lives in the gibberlink-core crate; the tone modems here are plain Rust
+// so they can talk to gear that doesn't speak ggwave (ham TNCs, telephony).
+
+use std::f32::consts::PI;
+use std::ops::ControlFlow;
+
+#[derive(Debug)]
+pub enum ModemError {
+    Init(String),
+    Encode(String),
+}
+
+impl std::fmt::Display for ModemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModemError::Init(e) | ModemError::Encode(e) => f.write_str(e),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Waveform {
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+impl Waveform {
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        self.samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+    }
+
+    /// Scales the waveform so its loudest sample sits at `dbfs` (0 is full scale).
+    pub fn normalize_peak(&mut self, dbfs: f32) {
+        let peak = self.samples.iter().map(|&s| (s as f32).abs()).fold(0.0, f32::max);
+        if peak == 0.0 {
+            return;
+        }
+        let gain = 10f32.powf(dbfs / 20.0) * i16::MAX as f32 / peak;
+        for s in &mut self.samples {
+            *s = (*s as f32 * gain).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+
+    /// Soft-knee limiter: samples pass unchanged up to 6 dB below `dbfs` and bend smoothly towards
+    /// `dbfs` above that, so the output never goes past it.
+    pub fn limit(&mut self, dbfs: f32) {
+        let ceiling = 10f32.powf(dbfs / 20.0) * i16::MAX as f32;
+        let knee = ceiling / 2.0;
+        for s in &mut self.samples {
+            let x = *s as f32;
+            if x.abs() > knee {
+                let over = (x.abs() - knee) / (ceiling - knee);
+                *s = (x.signum() * (knee + (ceiling - knee) * over.tanh())).round() as i16;
+            }
+        }
+    }
+}
+
+/// A modulation scheme. One modem serves every thread of a session (chat, bridge, agent, serve).
+pub trait Modem: Send + Sync {
+    fn modulate(&self, payload: &[u8], volume: i32) -> Result<Waveform, ModemError>;
+    fn demodulate(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, String>;
+    /// Every transmission in a recording, in order. The tone modems treat a whole recording as one
+    /// message, so only ggwave overrides this.
+    fn demodulate_all(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<Vec<u8>>, String> {
+        self.demodulate(samples, sample_rate).map(|m| vec![m])
+    }
+    /// Like `demodulate_all`, handing each transmission to `on_message` as it is found; `Break`
+    /// stops decoding the rest of the recording where the modem can (ggwave).
+    fn demodulate_each(&self, samples: &[f32], sample_rate: u32, on_message: &mut dyn FnMut(Vec<u8>) -> ControlFlow<()>) -> Result<(), String> {
+        for message in self.demodulate_all(samples, sample_rate)? {
+            if on_message(message).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+    /// Highest tone the modulator emits, for --max-freq-hz
+    fn max_freq_hz(&self) -> f32;
+}
+
+fn amplitude(volume: i32) -> f32 {
+    volume.clamp(0, 100) as f32 / 100.0 * i16::MAX as f32
+}
+
+// Short linear fade so tone bursts don't start/stop with a click
+fn apply_ramp(samples: &mut [f32], sample_rate: u32) {
+    let n = ((sample_rate / 500) as usize).min(samples.len() / 2);
+    for i in 0..n {
+        let g = i as f32 / n as f32;
+        samples[i] *= g;
+        let j = samples.len() - 1 - i;
+        samples[j] *= g;
+    }
+}
+
+fn to_i16(samples: &[f32]) -> Vec<i16> {
+    samples.iter().map(|s| s.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16).collect()
+}
+
+/// Continuous-phase binary FSK with asynchronous UART framing (start bit, LSB-first data, stop bits).
+pub struct Afsk {
+    pub sample_rate: u32,
+    pub baud: f32,
+    pub mark_hz: f32,
+    pub space_hz: f32,
+    pub data_bits: usize,
+    pub stop_bits: f32,
+}
+
+impl Afsk {
+    const LEADER_BITS: usize = 32;
+    const TRAILER_BITS: usize = 8;
+
+    /// Bell 202: 1200 baud, mark 1200 Hz, space 2200 Hz, 8N1.
+    pub fn bell202(sample_rate: u32) -> Self {
+        Self::minimodem(1200.0, sample_rate)
+    }
+
+    /// The tone plan `minimodem <baud>` picks by default: Bell 202-style spacing from 400 baud up,
+    /// Bell 103 (1270/1070 Hz) below that, and 170 Hz-shift RTTY tones for very slow rates.
+    pub fn minimodem(baud: f32, sample_rate: u32) -> Self {
+        let (mark_hz, space_hz) = if baud >= 400.0 {
+            (baud / 2.0 + 600.0, baud / 2.0 + 600.0 + baud * 5.0 / 6.0)
+        } else if baud >= 100.0 {
+            (1270.0, 1070.0)
+        } else {
+            (1585.0, 1415.0)
+        };
+        Afsk { sample_rate, baud, mark_hz, space_hz, data_bits: 8, stop_bits: 1.0 }
+    }
+
+    fn bits(&self, payload: &[u8]) -> Vec<(bool, f32)> {
+        let mut bits = vec![(true, Self::LEADER_BITS as f32)];
+        for byte in payload {
+            bits.push((false, 1.0));
+            bits.extend((0..self.data_bits).map(|i| ((byte >> i) & 1 == 1, 1.0)));
+            bits.push((true, self.stop_bits));
+        }
+        bits.push((true, Self::TRAILER_BITS as f32));
+        bits
+    }
+}
+
+impl Modem for Afsk {
+    fn max_freq_hz(&self) -> f32 {
+        self.mark_hz.max(self.space_hz)
+    }
+
+    fn modulate(&self, payload: &[u8], volume: i32) -> Result<Waveform, ModemError> {
+        if payload.is_empty() {
+            return Err(ModemError::Encode("empty payload".into()));
+        }
+        let sr = self.sample_rate as f32;
+        let samples_per_bit = sr / self.baud;
+        let amp = amplitude(volume);
+        let mut out = Vec::new();
+        let (mut phase, mut t) = (0.0f32, 0.0f32);
+        for (mark, len) in self.bits(payload) {
+            let freq = if mark { self.mark_hz } else { self.space_hz };
+            t += len * samples_per_bit;
+            while (out.len() as f32) < t.round() {
+                out.push(amp * phase.sin());
+                phase = (phase + 2.0 * PI * freq / sr) % (2.0 * PI);
+            }
+        }
+        apply_ramp(&mut out, self.sample_rate);
+        Ok(Waveform { sample_rate: self.sample_rate, samples: to_i16(&out) })
+    }
+
+    fn demodulate(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
+        let spb = sample_rate as f64 / self.baud as f64;
+        if spb < 2.0 {
+            return Err(format!("{} baud is too fast for {} Hz audio", self.baud, sample_rate));
+        }
+        let win = spb.round() as usize;
+        let frame_bits = self.data_bits + 2;
+        if samples.len() < win * (frame_bits + 1) {
+            return Err("Recording too short".into());
+        }
+        // Sliding non-coherent correlation over one bit: d > 0 means mark, d < 0 means space
+        let mark = SlidingTone::new(samples, self.mark_hz, sample_rate);
+        let space = SlidingTone::new(samples, self.space_hz, sample_rate);
+        let power: Vec<f64> = (0..samples.len()).map(|i| if i + 1 >= win { mark.energy(i, win) + space.energy(i, win) } else { 0.0 }).collect();
+        let d: Vec<f64> = (0..samples.len()).map(|i| if i + 1 >= win { mark.energy(i, win) - space.energy(i, win) } else { 0.0 }).collect();
+        let threshold = power.iter().cloned().fold(0.0, f64::max) * 0.05;
+        if threshold <= 0.0 {
+            return Err("No carrier found".into());
+        }
+
+        let bit_at = |start: f64, k: usize| -> Option<(bool, f64)> {
+            let i = (start + (k + 1) as f64 * spb).round() as usize - 1;
+            (i < d.len()).then(|| (d[i] > 0.0, power[i]))
+        };
+        let mut out = Vec::new();
+        let mut i = win;
+        while i < d.len() {
+            if !(d[i] < 0.0 && d[i - 1] >= 0.0 && power[i] > threshold) {
+                i += 1;
+                continue;
+            }
+            // The window is half into the start bit when d crosses zero
+            let start = i as f64 - spb / 2.0;
+            let frame: Option<Vec<(bool, f64)>> = (0..frame_bits).map(|k| bit_at(start, k)).collect();
+            match frame {
+                Some(f) if !f[0].0 && f[frame_bits - 1].0 && f.iter().all(|b| b.1 > threshold) => {
+                    out.push((1..=self.data_bits).fold(0u8, |acc, k| acc | ((f[k].0 as u8) << (k - 1))));
+                    // Resume the search from the middle of the stop bit
+                    i = (start + (frame_bits as f64 - 0.5) * spb) as usize;
+                }
+                Some(_) => i += 1,
+                None => break,
+            }
+        }
+        if out.is_empty() { Err("No payload decoded".into()) } else { Ok(out) }
+    }
+}
+
+// ITA2 letters and US-TTY figures; 0x1b/0x1f are the FIGS/LTRS shift codes
+const BAUDOT_LTRS: &[u8; 32] = b"\0E\nA SIU\rDRJNFCKTZLWHYPQOBG\0MXV\0";
+const BAUDOT_FIGS: &[u8; 32] = b"\x003\n- \x0787\r$4',!:(5\")2#6019?&\0./;\0";
+const BAUDOT_FIGS_SHIFT: u8 = 0x1b;
+const BAUDOT_LTRS_SHIFT: u8 = 0x1f;
+
+/// minimodem's `rtty` mode: 45.45 baud Baudot (5N1.5) on 1585/1415 Hz.
+pub struct Rtty {
+    afsk: Afsk,
+}
+
+impl Rtty {
+    pub fn new(sample_rate: u32) -> Self {
+        let mut afsk = Afsk::minimodem(45.45, sample_rate);
+        afsk.data_bits = 5;
+        afsk.stop_bits = 1.5;
+        Rtty { afsk }
+    }
+}
+
+fn baudot_encode(text: &[u8]) -> Result<Vec<u8>, ModemError> {
+    let mut out = vec![BAUDOT_LTRS_SHIFT];
+    let mut figs = false;
+    for &c in text {
+        let c = c.to_ascii_uppercase();
+        let find = |table: &[u8; 32]| (c != 0).then(|| table.iter().position(|&t| t == c)).flatten().map(|p| p as u8);
+        // Space, CR and LF exist in both shifts, so only switch when the other table is required
+        match (find(BAUDOT_LTRS), find(BAUDOT_FIGS)) {
+            (Some(code), Some(_)) => out.push(code),
+            (Some(code), None) => {
+                if figs { out.push(BAUDOT_LTRS_SHIFT); figs = false; }
+                out.push(code);
+            }
+            (None, Some(code)) => {
+                if !figs { out.push(BAUDOT_FIGS_SHIFT); figs = true; }
+                out.push(code);
+            }
+            (None, None) => return Err(ModemError::Encode(format!("'{}' can't be sent in Baudot", c.escape_ascii()))),
+        }
+    }
+    Ok(out)
+}
+
+fn baudot_decode(codes: &[u8]) -> Vec<u8> {
+    let mut figs = false;
+    let mut out = Vec::with_capacity(codes.len());
+    for &code in codes {
+        match code {
+            BAUDOT_FIGS_SHIFT => figs = true,
+            BAUDOT_LTRS_SHIFT => figs = false,
+            0 => {}
+            _ => out.push(if figs { BAUDOT_FIGS } else { BAUDOT_LTRS }[code as usize & 0x1f]),
+        }
+    }
+    out
+}
+
+impl Modem for Rtty {
+    fn max_freq_hz(&self) -> f32 {
+        self.afsk.max_freq_hz()
+    }
+
+    fn modulate(&self, payload: &[u8], volume: i32) -> Result<Waveform, ModemError> {
+        self.afsk.modulate(&baudot_encode(payload)?, volume)
+    }
+
+    fn demodulate(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
+        self.afsk.demodulate(samples, sample_rate).map(|codes| baudot_decode(&codes))
+    }
+}
+
+// Prefix sums of x·cos and x·sin so the tone energy over any window is O(1)
+struct SlidingTone {
+    re: Vec<f64>,
+    im: Vec<f64>,
+}
+
+impl SlidingTone {
+    fn new(samples: &[f32], freq: f32, sample_rate: u32) -> Self {
+        let w = 2.0 * std::f64::consts::PI * freq as f64 / sample_rate as f64;
+        let (mut re, mut im) = (vec![0.0; samples.len() + 1], vec![0.0; samples.len() + 1]);
+        for (n, &x) in samples.iter().enumerate() {
+            re[n + 1] = re[n] + x as f64 * (w * n as f64).cos();
+            im[n + 1] = im[n] + x as f64 * (w * n as f64).sin();
+        }
+        SlidingTone { re, im }
+    }
+
+    // Energy of the window of `len` samples ending at index `end` (inclusive)
+    fn energy(&self, end: usize, len: usize) -> f64 {
+        let (a, b) = (end + 1 - len, end + 1);
+        let (re, im) = (self.re[b] - self.re[a], self.im[b] - self.im[a]);
+        re * re + im * im
+    }
+}
+
+const DTMF_ROWS: [f32; 4] = [697.0, 770.0, 852.0, 941.0];
+const DTMF_COLS: [f32; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
+const DTMF_KEYS: [[u8; 4]; 4] = [*b"123A", *b"456B", *b"789C", *b"*0#D"];
+
+/// Telephone keypad tones. Payloads are digit strings (0-9, A-D, *, #), so they interoperate with
+/// ordinary DTMF decoders.
+pub struct Dtmf {
+    pub sample_rate: u32,
+    pub tone_ms: u32,
+    pub gap_ms: u32,
+}
+
+impl Dtmf {
+    pub fn new(sample_rate: u32) -> Self {
+        Dtmf { sample_rate, tone_ms: 70, gap_ms: 50 }
+    }
+}
+
+impl Modem for Dtmf {
+    fn max_freq_hz(&self) -> f32 {
+        DTMF_COLS[3]
+    }
+
+    fn modulate(&self, payload: &[u8], volume: i32) -> Result<Waveform, ModemError> {
+        let sr = self.sample_rate as f32;
+        let tone_len = (self.sample_rate * self.tone_ms / 1000) as usize;
+        let gap = vec![0i16; (self.sample_rate * self.gap_ms / 1000) as usize];
+        let mut out = vec![0i16; self.sample_rate as usize / 10];
+        for &c in payload {
+            let c = c.to_ascii_uppercase();
+            let (row, col) = (0..16)
+                .map(|i| (i / 4, i % 4))
+                .find(|&(r, k)| DTMF_KEYS[r][k] == c)
+                .ok_or_else(|| ModemError::Encode(format!("'{}' is not a DTMF digit (use 0-9, A-D, *, #)", c.escape_ascii())))?;
+            // Each of the two tones gets half the amplitude so the sum can't clip
+            let amp = amplitude(volume) / 2.0;
+            let mut tone: Vec<f32> = (0..tone_len)
+                .map(|n| {
+                    let t = n as f32 / sr;
+                    amp * ((2.0 * PI * DTMF_ROWS[row] * t).sin() + (2.0 * PI * DTMF_COLS[col] * t).sin())
+                })
+                .collect();
+            apply_ramp(&mut tone, self.sample_rate);
+            out.extend(to_i16(&tone));
+            out.extend_from_slice(&gap);
+        }
+        if out.len() == self.sample_rate as usize / 10 {
+            return Err(ModemError::Encode("empty payload".into()));
+        }
+        out.extend(vec![0i16; self.sample_rate as usize / 10]);
+        Ok(Waveform { sample_rate: self.sample_rate, samples: out })
+    }
+
+    fn demodulate(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
+        let block = (sample_rate / 50) as usize; // 20 ms
+        let mut out = Vec::new();
+        let (mut last, mut run) = (None, 0);
+        for chunk in samples.chunks(block / 2).collect::<Vec<_>>().windows(2) {
+            let frame: Vec<f32> = chunk.concat();
+            let key = detect_dtmf(&frame, sample_rate);
+            match key {
+                Some(k) if key == last => {
+                    run += 1;
+                    // Two agreeing overlapping blocks (~30 ms) make a digit; held tones emit it once
+                    if run == 2 {
+                        out.push(k);
+                    }
+                }
+                _ => run = 1,
+            }
+            last = key;
+        }
+        if out.is_empty() { Err("No DTMF digits decoded".into()) } else { Ok(out) }
+    }
+}
+
+fn goertzel(frame: &[f32], freq: f32, sample_rate: u32) -> f32 {
+    let coeff = 2.0 * (2.0 * PI * freq / sample_rate as f32).cos();
+    let (mut s1, mut s2) = (0.0f32, 0.0f32);
+    for &x in frame {
+        let s = x + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+    s1 * s1 + s2 * s2 - coeff * s1 * s2
+}
+
+fn detect_dtmf(frame: &[f32], sample_rate: u32) -> Option<u8> {
+    let energy: f32 = frame.iter().map(|x| x * x).sum();
+    if energy < 1e-6 * frame.len() as f32 {
+        return None;
+    }
+    let strongest = |freqs: &[f32; 4]| {
+        let p: Vec<f32> = freqs.iter().map(|&f| goertzel(frame, f, sample_rate)).collect();
+        let best = (0..4).max_by(|&a, &b| p[a].total_cmp(&p[b])).unwrap();
+        // The winning tone has to stand clear of its neighbours
+        let clear = (0..4).all(|i| i == best || p[i] * 4.0 < p[best]);
+        (best, p[best], clear)
+    };
+    let (row, pr, row_clear) = strongest(&DTMF_ROWS);
+    let (col, pc, col_clear) = strongest(&DTMF_COLS);
+    // For a clean two-tone signal (pr + pc) * 2 / N is about equal to the frame energy
+    let tonal = (pr + pc) * 2.0 / frame.len() as f32 / energy;
+    (row_clear && col_clear && tonal > 0.5).then_some(DTMF_KEYS[row][col])
+}
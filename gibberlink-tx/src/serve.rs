@@ -0,0 +1,1019 @@
+// Network bridges behind `serve` (the `serve` feature): clients submit payloads to transmit and
+// receive every decoded message as a JSON event line, over WebSocket, HTTP or MQTT; a serial port
+// or UDP socket is bridged transparently instead, raw bytes each way.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use serialport::SerialPort;
+use tungstenite::handshake::server::{self as handshake, Callback, ErrorResponse};
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::Message;
+use crate::Echoes;
+
+// Largest request body or message accepted; far beyond anything worth minutes of airtime
+const MAX_REQUEST: usize = 64 * 1024;
+// How long a connection waits for a read before checking its other direction
+const POLL: Duration = Duration::from_millis(50);
+// Connections served at once per listener, each on its own thread; more are turned away
+const MAX_CONNECTIONS: usize = 64;
+// Furthest ahead a start time may be; a scheduled submission holds up the transmitter until then
+const MAX_SCHEDULE_AHEAD: Duration = Duration::from_secs(300);
+
+/// Something a client asked to have transmitted.
+pub enum Submission {
+    /// Goes through the text steps (--template, on_transmit, ...) like `tx` input
+    Text(String),
+    /// Sent as-is, like `--input-format` binary input
+    Bytes(Vec<u8>),
+}
+
+/// What came of a submission: its `encoded` event line (a `warning` when `ok` is false) and, for
+/// submissions that were encoded but not played, the WAV file.
+pub struct Reply {
+    pub ok: bool,
+    pub event: String,
+    pub wav: Vec<u8>,
+    /// Set when the client was over its rate limit: how long until it may submit again
+    pub retry_after: Option<Duration>,
+}
+
+/// Hands a submission from a client (its address, or the bridge's name) to the transmitter, which
+/// plays it if the flag is set, and waits for the outcome.
+pub type Submit<'a> = &'a (dyn Fn(&str, Result<Submission, String>, Schedule, bool) -> Reply + Sync);
+
+/// When a submission goes out: its priority in the queue and, for a broadcast synchronized across
+/// hosts, the wall-clock instant its first sample should leave the speaker.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Schedule {
+    pub priority: Priority,
+    pub at: Option<SystemTime>,
+}
+
+// A start time sent as Unix milliseconds, which must be in the next few minutes
+fn start_time(unix_ms: u64) -> Result<SystemTime, String> {
+    let at = UNIX_EPOCH + Duration::from_millis(unix_ms);
+    let now = SystemTime::now();
+    match at.duration_since(now) {
+        Err(e) => Err(format!("start time {} passed {} ms ago", unix_ms, e.duration().as_millis())),
+        Ok(ahead) if ahead > MAX_SCHEDULE_AHEAD => Err(format!("start time {} is more than {} s ahead", unix_ms, MAX_SCHEDULE_AHEAD.as_secs())),
+        Ok(_) => Ok(at),
+    }
+}
+
+/// How urgent a submission is. While the transmitter is busy, higher priorities go first and equal
+/// ones in the order they arrived.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        serde_json::from_value(serde_json::Value::String(s.to_owned())).map_err(|_| format!("unknown priority {:?} (low, normal or high)", s))
+    }
+}
+
+/// A submission waiting for the transmitter, and where its reply goes.
+pub struct Job {
+    pub submission: Result<Submission, String>,
+    pub play: bool,
+    pub at: Option<SystemTime>,
+    pub reply: Sender<Reply>,
+}
+
+/// What is waiting for the transmitter, as `GET /queue` and the `queue` event report it.
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct QueueStatus {
+    /// Whether a submission is being encoded or played right now
+    pub busy: bool,
+    pub high: usize,
+    pub normal: usize,
+    pub low: usize,
+}
+
+/// Submissions by priority, then arrival; the transmitter takes them one at a time. With a rate
+/// limit, each client has a token bucket that a submission must take a token from.
+#[derive(Default)]
+pub struct Queue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+    limit: Option<RateLimit>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    jobs: BinaryHeap<Queued>,
+    arrivals: u64,
+    busy: bool,
+    buckets: HashMap<String, Bucket>,
+}
+
+/// How many submissions a client may make: `burst` at once, refilled at `per_minute`.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimit {
+    // Takes a token from `client`'s bucket, or says how long until one is back. Buckets that have
+    // filled up again are forgotten, so clients that come and go don't pile up
+    fn take(self, buckets: &mut HashMap<String, Bucket>, client: &str, now: Instant) -> Result<(), Duration> {
+        let (rate, burst) = (self.per_minute as f64 / 60.0, self.burst as f64);
+        let refilled = |bucket: &Bucket| (bucket.tokens + now.saturating_duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        buckets.retain(|_, bucket| refilled(bucket) < burst);
+        let tokens = buckets.get(client).map_or(burst, refilled);
+        if tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - tokens) / rate));
+        }
+        buckets.insert(client.to_owned(), Bucket { tokens: tokens - 1.0, updated: now });
+        Ok(())
+    }
+}
+
+struct Queued {
+    priority: Priority,
+    arrival: Reverse<u64>,
+    job: Job,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.priority, self.arrival).cmp(&(other.priority, other.arrival))
+    }
+}
+
+impl Queue {
+    pub fn with_rate_limit(limit: RateLimit) -> Self {
+        Queue { limit: Some(limit), ..Queue::default() }
+    }
+
+    /// Queues `job` from `client`, returning how many submissions (including one in progress) are
+    /// ahead of it, or, if `client` is over the rate limit, how long until it may submit again.
+    pub fn push(&self, client: &str, priority: Priority, job: Job) -> Result<usize, Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(limit) = self.limit {
+            limit.take(&mut state.buckets, client, Instant::now())?;
+        }
+        let ahead = state.jobs.iter().filter(|q| q.priority >= priority).count() + state.busy as usize;
+        let arrival = Reverse(state.arrivals);
+        state.arrivals += 1;
+        state.jobs.push(Queued { priority, arrival, job });
+        self.ready.notify_one();
+        Ok(ahead)
+    }
+
+    /// Waits for the most urgent submission; the queue counts as busy until [`Queue::done`].
+    pub fn pop(&self) -> Job {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(queued) = state.jobs.pop() {
+                state.busy = true;
+                return queued.job;
+            }
+            state = self.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn done(&self) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).busy = false;
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let count = |priority| state.jobs.iter().filter(|q| q.priority == priority).count();
+        QueueStatus { busy: state.busy, high: count(Priority::High), normal: count(Priority::Normal), low: count(Priority::Low) }
+    }
+}
+
+// Capture that has delivered nothing for this long counts as dead; devices deliver every few ms
+const CAPTURE_STALL: Duration = Duration::from_secs(2);
+
+/// The state of the audio paths, as `GET /healthz` reports it; the daemon records playbacks and decodes.
+#[derive(Default)]
+pub struct Health {
+    // Unix ms
+    last_decode: Mutex<Option<u128>>,
+    playback_error: Mutex<Option<String>>,
+}
+
+#[derive(serde::Serialize)]
+struct HealthReport {
+    ok: bool,
+    capture_alive: bool,
+    capture_idle_ms: Option<u128>,
+    playback_ok: bool,
+    playback_error: Option<String>,
+    last_decode_unix_ms: Option<u128>,
+    queue: QueueStatus,
+}
+
+impl Health {
+    pub fn decoded(&self) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+        *self.last_decode.lock().unwrap_or_else(|e| e.into_inner()) = Some(now);
+    }
+
+    /// The outcome of the latest playback; an error marks playback as failed until one succeeds.
+    pub fn played(&self, error: Option<&str>) {
+        *self.playback_error.lock().unwrap_or_else(|e| e.into_inner()) = error.map(str::to_owned);
+    }
+
+    fn report(&self, queue: &Queue) -> HealthReport {
+        let capture_idle = crate::audio::since_capture();
+        let capture_alive = capture_idle.is_some_and(|idle| idle < CAPTURE_STALL);
+        let playback_error = self.playback_error.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        HealthReport {
+            ok: capture_alive && playback_error.is_none(),
+            capture_alive,
+            capture_idle_ms: capture_idle.map(|idle| idle.as_millis()),
+            playback_ok: playback_error.is_none(),
+            playback_error,
+            last_decode_unix_ms: *self.last_decode.lock().unwrap_or_else(|e| e.into_inner()),
+            queue: queue.status(),
+        }
+    }
+}
+
+/// What the WebSocket and HTTP bridges share.
+#[derive(Clone, Copy)]
+pub struct Api<'a> {
+    pub hub: &'a Hub,
+    pub submit: Submit<'a>,
+    pub queue: &'a Queue,
+    pub health: &'a Health,
+    /// Bearer token clients must present, if set
+    pub token: Option<&'a str>,
+    /// Serve HTTPS and WSS with this instead of plain HTTP and WebSocket
+    #[cfg(feature = "tls")]
+    pub tls: Option<&'a crate::tls::Config>,
+}
+
+impl Api<'_> {
+    // `authorization` is the Authorization header, `url` the request target whose `token` query
+    // parameter stands in for it (browsers can't set headers on WebSockets or EventSource)
+    fn authorized(&self, authorization: Option<&str>, url: &str) -> bool {
+        let Some(token) = self.token else { return true };
+        let bearer = authorization
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, given)| given.trim());
+        let query = url
+            .split_once('?')
+            .and_then(|(_, query)| query.split('&').find_map(|pair| pair.strip_prefix("token=")));
+        bearer.into_iter().chain(query).any(|given| same_token(given.as_bytes(), token.as_bytes()))
+    }
+}
+
+// Constant time for a given length, so response timing doesn't tell how much of a guess was right
+fn same_token(given: &[u8], token: &[u8]) -> bool {
+    given.len() == token.len() && given.iter().zip(token).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Without a token, refuses addresses other machines could reach: anyone there could make the
+/// speaker play whatever they like.
+pub fn check_exposure(flag: &str, addr: &str, token: Option<&str>) -> Result<(), String> {
+    if token.is_some() {
+        return Ok(());
+    }
+    let addrs: Vec<_> = addr.to_socket_addrs().map_err(|e| format!("{} {}: {}", flag, addr, e))?.collect();
+    if !addrs.is_empty() && addrs.iter().all(|a| a.ip().is_loopback()) {
+        Ok(())
+    } else {
+        Err(format!("{} {} is reachable from other machines; bind to 127.0.0.1 or set a token with --token-file or GIBBERLINK_TOKEN", flag, addr))
+    }
+}
+
+// Counts the connections a listener is serving, so a flood of them can't spawn threads without bound
+#[derive(Default)]
+struct Slots(AtomicUsize);
+
+struct Slot<'a>(&'a AtomicUsize);
+
+impl Slots {
+    // A slot for one more connection, held until it is served; None once MAX_CONNECTIONS are
+    fn take(&self) -> Option<Slot<'_>> {
+        if self.0.fetch_add(1, Ordering::SeqCst) < MAX_CONNECTIONS {
+            Some(Slot(&self.0))
+        } else {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+            None
+        }
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Fans event lines (or, for the serial and UDP bridges, received payloads) out to every connected client.
+#[derive(Default)]
+pub struct Hub<T = String> {
+    clients: Mutex<Vec<Sender<T>>>,
+}
+
+impl<T: Clone> Hub<T> {
+    pub fn broadcast(&self, item: &T) {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        clients.retain(|client| client.send(item.clone()).is_ok());
+    }
+
+    fn subscribe(&self) -> Receiver<T> {
+        let (tx, rx) = channel();
+        self.clients.lock().unwrap_or_else(|e| e.into_inner()).push(tx);
+        rx
+    }
+}
+
+// Text frames and JSON bodies are requests, {"text": "..."} or {"base64": "..."}, optionally with a
+// "priority" and an "at" start time in Unix milliseconds
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    text: Option<String>,
+    base64: Option<String>,
+    #[serde(default)]
+    priority: Priority,
+    at: Option<u64>,
+}
+
+// The priority is read even from requests that are otherwise invalid, so their error isn't queued
+// behind everything else
+fn parse_request(json: &str) -> (Result<Submission, String>, Schedule) {
+    use base64::Engine;
+    let request: Request = match serde_json::from_str(json) {
+        Ok(request) => request,
+        Err(e) => return (Err(format!("invalid request: {}", e)), Schedule::default()),
+    };
+    let submission = match (request.text, request.base64) {
+        (Some(text), None) => Ok(Submission::Text(text)),
+        (None, Some(b64)) => base64::engine::general_purpose::STANDARD.decode(b64.trim()).map(Submission::Bytes).map_err(|e| format!("invalid base64: {}", e)),
+        _ => Err("a request needs exactly one of \"text\" and \"base64\"".into()),
+    };
+    match request.at.map(start_time).transpose() {
+        Ok(at) => (submission, Schedule { priority: request.priority, at }),
+        Err(e) => (Err(e), Schedule { priority: request.priority, at: None }),
+    }
+}
+
+/// Accepts WebSocket clients on `addr` until the listener fails. Each text or binary message a
+/// client sends is played through `api.submit`, whose event line is sent back to that client; every
+/// line broadcast on `api.hub` goes to all of them. Handshakes without the token get a 401.
+pub fn websocket(addr: &str, api: Api) -> Result<(), String> {
+    let listener = TcpListener::bind(addr).map_err(|e| format!("bind {}: {}", addr, e))?;
+    let scheme = if secure(api) { "wss" } else { "ws" };
+    eprintln!("WebSocket bridge on {}://{}", scheme, listener.local_addr().map_or_else(|_| addr.to_owned(), |a| a.to_string()));
+    let slots = &Slots::default();
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = stream.map_err(|e| format!("accept: {}", e))?;
+            // Dropped unanswered when full
+            let Some(slot) = slots.take() else { continue };
+            scope.spawn(move || {
+                let _slot = slot;
+                let peer = stream.peer_addr().map_or_else(|_| "client".into(), |a| a.to_string());
+                if let Err(e) = websocket_connection(stream, api) {
+                    eprintln!("WebSocket {}: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    })
+}
+
+fn secure(api: Api) -> bool {
+    #[cfg(feature = "tls")]
+    return api.tls.is_some();
+    #[cfg(not(feature = "tls"))]
+    {
+        let _ = api;
+        false
+    }
+}
+
+fn websocket_connection(stream: TcpStream, api: Api) -> Result<(), String> {
+    // A second handle on the socket, whose read timeout still applies once TLS wraps the first
+    let tcp = stream.try_clone().map_err(|e| e.to_string())?;
+    #[cfg(feature = "tls")]
+    if let Some(config) = api.tls {
+        return websocket_client(crate::tls::accept(config, stream)?, &tcp, api);
+    }
+    websocket_client(stream, &tcp, api)
+}
+
+// Turns away WebSocket handshakes without the token
+struct Handshake<'a>(Api<'a>);
+
+impl Callback for Handshake<'_> {
+    fn on_request(self, request: &handshake::Request, response: handshake::Response) -> Result<handshake::Response, ErrorResponse> {
+        let authorization = request.headers().get("Authorization").and_then(|v| v.to_str().ok());
+        if self.0.authorized(authorization, &request.uri().to_string()) {
+            Ok(response)
+        } else {
+            Err(tungstenite::http::Response::builder().status(401).body(Some("missing or wrong token".into())).expect("valid response"))
+        }
+    }
+}
+
+fn websocket_client(stream: impl Read + Write, tcp: &TcpStream, api: Api) -> Result<(), String> {
+    let config = WebSocketConfig::default().max_message_size(Some(MAX_REQUEST)).max_frame_size(Some(MAX_REQUEST));
+    let mut socket = tungstenite::accept_hdr_with_config(stream, Handshake(api), Some(config)).map_err(|e| format!("handshake: {}", e))?;
+    // Short reads so broadcasts go out while the client is quiet
+    tcp.set_read_timeout(Some(POLL)).map_err(|e| e.to_string())?;
+    let events = api.hub.subscribe();
+    let client = tcp.peer_addr().map_or_else(|_| "client".into(), |a| a.ip().to_string());
+    loop {
+        let submission = match socket.read() {
+            Ok(Message::Text(text)) => Some(parse_request(text.as_str())),
+            Ok(Message::Binary(bytes)) => Some((Ok(Submission::Bytes(bytes.to_vec())), Schedule::default())),
+            Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            // Clients that just drop the connection
+            Err(tungstenite::Error::Protocol(tungstenite::error::ProtocolError::ResetWithoutClosingHandshake)) => return Ok(()),
+            Ok(_) => None,
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => None,
+            Err(e) => return Err(e.to_string()),
+        };
+        if let Some((submission, schedule)) = submission {
+            socket.send(Message::text((api.submit)(&client, submission, schedule, true).event)).map_err(|e| e.to_string())?;
+        }
+        while let Ok(line) = events.try_recv() {
+            socket.send(Message::text(line)).map_err(|e| e.to_string())?;
+        }
+    }
+}
+
+/// Serves the HTTP API on `addr`: `POST /encode` answers with the WAV, `POST /tx` plays the payload
+/// and answers with its event line, `GET /rx/stream` streams every line broadcast on `api.hub` and
+/// `GET /events` the same lines as server-sent events, `GET /queue` reports what is waiting to be
+/// transmitted, and `GET /healthz` whether the audio paths work (503 if not; no token needed, for
+/// orchestrators' probes). Bodies are sent as-is when `application/octet-stream`, as text when
+/// `text/plain`, and are otherwise read as a JSON request; `priority` and `at` query parameters set
+/// the priority and start time of any of them. Requests without the token get a 401.
+pub fn http(addr: &str, api: Api) -> Result<(), String> {
+    #[cfg(feature = "tls")]
+    if let Some(config) = api.tls {
+        return https(addr, config, api);
+    }
+    let server = tiny_http::Server::http(addr).map_err(|e| format!("bind {}: {}", addr, e))?;
+    eprintln!("HTTP API on http://{}", server.server_addr());
+    serve_http(&server, api, &Relayed::default());
+    Ok(())
+}
+
+// The HTTPS relay's end of each connection it opened to the plain HTTP server, and the client it
+// relays for, so requests are told apart by their real sender
+type Relayed = Mutex<HashMap<SocketAddr, SocketAddr>>;
+
+fn serve_http(server: &tiny_http::Server, api: Api, relayed: &Relayed) {
+    let slots = &Slots::default();
+    std::thread::scope(|scope| {
+        for request in server.incoming_requests() {
+            let peer = request.remote_addr().map(|a| relayed.lock().unwrap_or_else(|e| e.into_inner()).get(a).copied().unwrap_or(*a));
+            let Some(slot) = slots.take() else {
+                let _ = request.respond(tiny_http::Response::from_string("too many connections").with_status_code(503));
+                continue;
+            };
+            scope.spawn(move || {
+                let _slot = slot;
+                if let Err(e) = http_request(request, api, peer) {
+                    eprintln!("HTTP {}: {}", peer.map_or_else(|| "client".into(), |a| a.to_string()), e);
+                }
+            });
+        }
+    });
+}
+
+// tiny_http's own TLS support is tied to an old rustls, so the API is served in plain HTTP on a
+// loopback port and every connection to `addr` is decrypted and relayed to it
+#[cfg(feature = "tls")]
+fn https(addr: &str, config: &crate::tls::Config, api: Api) -> Result<(), String> {
+    let server = tiny_http::Server::http("127.0.0.1:0").map_err(|e| format!("bind 127.0.0.1:0: {}", e))?;
+    let inner = server.server_addr().to_ip().ok_or("HTTP API not on an IP address")?;
+    let listener = TcpListener::bind(addr).map_err(|e| format!("bind {}: {}", addr, e))?;
+    eprintln!("HTTP API on https://{}", listener.local_addr().map_or_else(|_| addr.to_owned(), |a| a.to_string()));
+    let (relayed, slots) = (&Relayed::default(), &Slots::default());
+    std::thread::scope(|scope| {
+        scope.spawn(|| serve_http(&server, api, relayed));
+        for stream in listener.incoming() {
+            let stream = stream.map_err(|e| format!("accept: {}", e))?;
+            let Some(slot) = slots.take() else { continue };
+            scope.spawn(move || {
+                let _slot = slot;
+                let peer = stream.peer_addr().map_or_else(|_| "client".into(), |a| a.to_string());
+                if let Err(e) = relay(stream, config, inner, relayed) {
+                    eprintln!("HTTPS {}: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    })
+}
+
+// Both directions on one thread (a TLS session can't be split), taking turns with short reads
+#[cfg(feature = "tls")]
+fn relay(client: TcpStream, config: &crate::tls::Config, inner: SocketAddr, relayed: &Relayed) -> Result<(), String> {
+    let idle = |e: &std::io::Error| matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut);
+    client.set_read_timeout(Some(POLL)).map_err(|e| e.to_string())?;
+    let peer = client.peer_addr().map_err(|e| e.to_string())?;
+    let mut client = crate::tls::accept(config, client)?;
+    let mut server = TcpStream::connect(inner).map_err(|e| format!("connect {}: {}", inner, e))?;
+    server.set_read_timeout(Some(POLL)).map_err(|e| e.to_string())?;
+    let local = server.local_addr().map_err(|e| e.to_string())?;
+    relayed.lock().unwrap_or_else(|e| e.into_inner()).insert(local, peer);
+    let mut buf = vec![0; 16 * 1024];
+    let relaying = (|| loop {
+        match client.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => server.write_all(&buf[..n]).map_err(|e| e.to_string())?,
+            Err(e) if idle(&e) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+        match server.read(&mut buf) {
+            Ok(0) => {
+                client.conn.send_close_notify();
+                return client.flush().map_err(|e| e.to_string());
+            }
+            Ok(n) => client.write_all(&buf[..n]).and_then(|_| client.flush()).map_err(|e| e.to_string())?,
+            Err(e) if idle(&e) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    })();
+    relayed.lock().unwrap_or_else(|e| e.into_inner()).remove(&local);
+    relaying
+}
+
+// `peer` is where the request came from, the key of its rate limit
+fn http_request(mut request: tiny_http::Request, api: Api, peer: Option<SocketAddr>) -> std::io::Result<()> {
+    use tiny_http::{Header, Method, Response};
+    let header = |name: &str, value: &str| Header::from_bytes(name, value).expect("valid header");
+    let authorization = request.headers().iter().find(|h| h.field.equiv("Authorization")).map(|h| h.value.as_str());
+    let probe = *request.method() == Method::Get && request.url().split('?').next() == Some("/healthz");
+    if !probe && !api.authorized(authorization, request.url()) {
+        let response = Response::from_string("missing or wrong token").with_status_code(401).with_header(header("WWW-Authenticate", "Bearer"));
+        return request.respond(response);
+    }
+    let path = request.url().split('?').next().unwrap_or_default().to_owned();
+    match (request.method(), path.as_str()) {
+        (Method::Post, "/encode" | "/tx") => {
+            let play = path == "/tx";
+            let content_type = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("Content-Type"))
+                .map(|h| h.value.as_str().to_ascii_lowercase())
+                .unwrap_or_default();
+            let mut body = Vec::new();
+            request.as_reader().take(MAX_REQUEST as u64 + 1).read_to_end(&mut body)?;
+            if body.len() > MAX_REQUEST {
+                let message = format!("request body over {} bytes", MAX_REQUEST);
+                return request.respond(Response::from_string(message).with_status_code(413));
+            }
+            let (submission, schedule) = if content_type.starts_with("application/octet-stream") {
+                (Ok(Submission::Bytes(body)), Schedule::default())
+            } else if content_type.starts_with("text/plain") {
+                (String::from_utf8(body).map(Submission::Text).map_err(|_| "text/plain body is not UTF-8".into()), Schedule::default())
+            } else {
+                match String::from_utf8(body) {
+                    Ok(json) => parse_request(&json),
+                    Err(_) => (Err("request body is not UTF-8".into()), Schedule::default()),
+                }
+            };
+            // ?priority= and ?at= work for every kind of body, and win over the JSON fields
+            let query = request.url().split_once('?').map_or("", |(_, query)| query);
+            let param = |name: &str| query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='));
+            let (submission, schedule) = match param("priority").map(str::parse) {
+                Some(Ok(priority)) => (submission, Schedule { priority, ..schedule }),
+                Some(Err(e)) => (Err(e), Schedule::default()),
+                None => (submission, schedule),
+            };
+            let (submission, schedule) = match param("at").map(|at| at.parse().map_err(|_| format!("invalid start time {:?}", at)).and_then(start_time)) {
+                Some(Ok(at)) => (submission, Schedule { at: Some(at), ..schedule }),
+                Some(Err(e)) => (Err(e), schedule),
+                None => (submission, schedule),
+            };
+            let client = peer.map_or_else(|| "client".into(), |a| a.ip().to_string());
+            let reply = (api.submit)(&client, submission, schedule, play);
+            let response = if let Some(wait) = reply.retry_after {
+                Response::from_string(reply.event)
+                    .with_header(header("Content-Type", "application/json"))
+                    .with_header(header("Retry-After", &wait.as_secs_f64().ceil().to_string()))
+                    .with_status_code(429)
+            } else if reply.ok && !play {
+                Response::from_data(reply.wav).with_header(header("Content-Type", "audio/wav"))
+            } else {
+                Response::from_string(reply.event)
+                    .with_header(header("Content-Type", "application/json"))
+                    .with_status_code(if reply.ok { 200 } else { 400 })
+            };
+            request.respond(response)
+        }
+        (Method::Get, "/rx/stream") => stream(request, api.hub, "application/x-ndjson", |line| format!("{}\n", line)),
+        // Server-sent events: one event per line, for EventSource, curl and dashboards
+        (Method::Get, "/events") => stream(request, api.hub, "text/event-stream", |line| format!("data: {}\n\n", line)),
+        (Method::Get, "/queue") => {
+            let status = serde_json::to_string(&api.queue.status()).expect("queue status always serializes");
+            request.respond(Response::from_string(status).with_header(header("Content-Type", "application/json")))
+        }
+        (Method::Get, "/healthz") => {
+            let report = api.health.report(api.queue);
+            let status = if report.ok { 200 } else { 503 };
+            let body = serde_json::to_string(&report).expect("health always serializes");
+            request.respond(Response::from_string(body).with_header(header("Content-Type", "application/json")).with_status_code(status))
+        }
+        (_, "/encode" | "/tx" | "/rx/stream" | "/events" | "/queue" | "/healthz") => request.respond(Response::from_string("method not allowed").with_status_code(405)),
+        _ => request.respond(Response::from_string("not found").with_status_code(404)),
+    }
+}
+
+// Writes every line broadcast on `hub`, as `format` frames it, until the client goes away.
+// tiny_http's chunked responses buffer, so the stream is written by hand and flushed per line
+fn stream(request: tiny_http::Request, hub: &Hub, content_type: &str, format: impl Fn(&str) -> String) -> std::io::Result<()> {
+    let events = hub.subscribe();
+    let mut writer = request.into_writer();
+    write!(writer, "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n", content_type)?;
+    writer.flush()?;
+    for line in events {
+        writer.write_all(format(&line).as_bytes())?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Bridges to the MQTT broker at `broker` (HOST or HOST:PORT, IPv6 addresses in brackets when a port
+/// follows, port 1883 by default), reconnecting
+/// as needed: every message published on `tx_topic` is played through `submit` (as text when it is
+/// UTF-8, as bytes otherwise) and every line broadcast on `hub` is published on `rx_topic`.
+pub fn mqtt(broker: &str, tx_topic: &str, rx_topic: &str, hub: &Hub, submit: Submit) -> Result<(), String> {
+    if tx_topic == rx_topic {
+        return Err("the MQTT TX and RX topics must differ".into());
+    }
+    let (host, port) = broker_address(broker)?;
+    let mut options = MqttOptions::new(format!("gibberlink-{}", std::process::id()), host.as_str(), port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (client, mut connection) = Client::new(options, 16);
+    let events = hub.subscribe();
+    let (incoming, queue) = channel::<Vec<u8>>();
+    eprintln!("MQTT bridge to {}:{}: playing {}, publishing to {}", host, port, tx_topic, rx_topic);
+    std::thread::scope(|scope| {
+        let publisher = client.clone();
+        scope.spawn(move || {
+            for line in events {
+                // Queued while disconnected; only fails once the event loop is gone
+                if publisher.publish(rx_topic, QoS::AtLeastOnce, false, line).is_err() {
+                    break;
+                }
+            }
+        });
+        // Played on their own thread so the connection keeps being serviced meanwhile
+        scope.spawn(move || {
+            for payload in queue {
+                let submission = String::from_utf8(payload).map_or_else(|e| Submission::Bytes(e.into_bytes()), Submission::Text);
+                let reply = submit("mqtt", Ok(submission), Schedule::default(), true);
+                if !reply.ok {
+                    eprintln!("MQTT: {}", reply.event);
+                }
+            }
+        });
+        for notification in connection.iter() {
+            match notification {
+                // Clean sessions lose their subscriptions on reconnect
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    if let Err(e) = client.try_subscribe(tx_topic, QoS::AtLeastOnce) {
+                        eprintln!("MQTT: subscribe to {}: {}", tx_topic, e);
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    let _ = incoming.send(publish.payload.to_vec());
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("MQTT {}:{}: {}; retrying", host, port, e);
+                    std::thread::sleep(Duration::from_secs(2));
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+// HOST, HOST:PORT, IPV6, [IPV6] or [IPV6]:PORT. IPv6 hosts come back in brackets, as rumqttc
+// joins host and port with a colon to connect
+fn broker_address(broker: &str) -> Result<(String, u16), String> {
+    const DEFAULT_PORT: u16 = 1883;
+    let invalid_port = || format!("invalid port in {}", broker);
+    if let Ok(ip) = broker.parse::<std::net::Ipv6Addr>() {
+        return Ok((format!("[{}]", ip), DEFAULT_PORT));
+    }
+    if let Some(bracketed) = broker.strip_prefix('[') {
+        let (ip, rest) = bracketed.split_once(']').ok_or_else(|| format!("missing ] in {}", broker))?;
+        let ip: std::net::Ipv6Addr = ip.parse().map_err(|_| format!("invalid IPv6 address in {}", broker))?;
+        let port = match rest {
+            "" => DEFAULT_PORT,
+            _ => rest.strip_prefix(':').and_then(|p| p.parse().ok()).ok_or_else(invalid_port)?,
+        };
+        return Ok((format!("[{}]", ip), port));
+    }
+    match broker.rsplit_once(':') {
+        Some((host, port)) => Ok((host.to_owned(), port.parse().map_err(|_| invalid_port())?)),
+        None => Ok((broker.to_owned(), DEFAULT_PORT)),
+    }
+}
+
+// A pause this long in what a tool writes to the serial port ends a message
+const SERIAL_IDLE: Duration = Duration::from_millis(100);
+// Longer writes are split, so a tool streaming without pauses still gets through
+const SERIAL_MAX_MESSAGE: usize = 1024;
+
+/// Bridges the serial port at `path` (or, on Unix, a new pseudo-terminal when `path` is "pty"):
+/// bytes written to it are played through `submit` once the writer pauses, and every payload
+/// broadcast on `received` is written back to it as-is, except the port's own transmissions.
+pub fn serial(path: &str, baud: u32, received: &Hub<Vec<u8>>, submit: Submit) -> Result<(), String> {
+    let (mut port, _far_end) = open_port(path, baud)?;
+    port.set_timeout(SERIAL_IDLE).map_err(|e| format!("{}: {}", path, e))?;
+    let mut writer = port.try_clone().map_err(|e| format!("{}: {}", path, e))?;
+    let payloads = received.subscribe();
+    let echoes = Arc::new(Echoes::default());
+    let heard = Arc::clone(&echoes);
+    // Not scoped: it only stops with the process, so a failing reader must not wait for it
+    std::thread::spawn(move || {
+        for payload in payloads {
+            if heard.is_echo(&payload) {
+                continue;
+            }
+            if let Err(e) = writer.write_all(&payload).and_then(|_| writer.flush()) {
+                eprintln!("Serial: write: {}", e);
+            }
+        }
+    });
+    let mut message = Vec::new();
+    let mut buf = [0u8; 256];
+    loop {
+        match port.read(&mut buf) {
+            Ok(n) if n > 0 && message.len() + n < SERIAL_MAX_MESSAGE => {
+                message.extend_from_slice(&buf[..n]);
+                continue;
+            }
+            Ok(n) => message.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        }
+        if !message.is_empty() {
+            // Recorded before playing: the microphone can decode it before submit() returns
+            echoes.sent(&message);
+            let reply = submit("serial", Ok(Submission::Bytes(std::mem::take(&mut message))), Schedule::default(), true);
+            if !reply.ok {
+                eprintln!("Serial: {}", reply.event);
+            }
+        }
+    }
+}
+
+// The port, plus for a pseudo-terminal the end tools open, held so reads don't fail while none has
+type Port = (Box<dyn SerialPort>, Option<Box<dyn SerialPort>>);
+
+fn open_port(path: &str, baud: u32) -> Result<Port, String> {
+    #[cfg(unix)]
+    if path == "pty" {
+        let (master, slave) = serialport::TTYPort::pair().map_err(|e| format!("pty: {}", e))?;
+        eprintln!("Serial bridge on {}", slave.name().unwrap_or_else(|| "a pseudo-terminal".into()));
+        return Ok((Box::new(master), Some(Box::new(slave))));
+    }
+    let port = serialport::new(path, baud).open().map_err(|e| format!("{}: {}", path, e))?;
+    eprintln!("Serial bridge on {} at {} baud", path, baud);
+    Ok((port, None))
+}
+
+/// Bridges UDP on `addr`, joining the group when it is a multicast address: each datagram from one
+/// of the `from` hosts is played through `submit`, and every payload broadcast on `received` is
+/// sent to `peer` (by default the group, or else whoever sent the last datagram), except the
+/// bridge's own transmissions. Without `from`, only loopback binds are allowed, like
+/// check_exposure() without a token.
+pub fn udp(addr: &str, peer: Option<&str>, from: &[IpAddr], received: &Hub<Vec<u8>>, submit: Submit) -> Result<(), String> {
+    let resolve = |addr: &str| addr.to_socket_addrs().ok().and_then(|mut a| a.next()).ok_or_else(|| format!("cannot resolve {}", addr));
+    let local = resolve(addr)?;
+    if from.is_empty() && !local.ip().is_loopback() {
+        return Err(format!("--udp {} is reachable from other machines; bind to 127.0.0.1 or list the hosts allowed to send with --udp-from", addr));
+    }
+    let socket = match local.ip() {
+        IpAddr::V4(group) if group.is_multicast() => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, local.port()))
+            .and_then(|s| s.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED).and(s.set_multicast_loop_v4(false)).map(|_| s)),
+        IpAddr::V6(group) if group.is_multicast() => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, local.port()))
+            .and_then(|s| s.join_multicast_v6(&group, 0).and(s.set_multicast_loop_v6(false)).map(|_| s)),
+        _ => UdpSocket::bind(local),
+    }
+    .map_err(|e| format!("{}: {}", addr, e))?;
+    let peer = match peer {
+        Some(peer) => Some(resolve(peer)?),
+        None => Some(local).filter(|a| a.ip().is_multicast()),
+    };
+    let sender = socket.try_clone().map_err(|e| format!("{}: {}", addr, e))?;
+    let payloads = received.subscribe();
+    let echoes = Arc::new(Echoes::default());
+    let heard = Arc::clone(&echoes);
+    let last: Arc<Mutex<Option<SocketAddr>>> = Arc::default();
+    let replies = Arc::clone(&last);
+    eprintln!("UDP bridge on {}", local);
+    // Not scoped: it only stops with the process, so a failing receiver must not wait for it
+    std::thread::spawn(move || {
+        for payload in payloads {
+            if heard.is_echo(&payload) {
+                continue;
+            }
+            let Some(to) = peer.or(*replies.lock().unwrap_or_else(|e| e.into_inner())) else { continue };
+            if let Err(e) = sender.send_to(&payload, to) {
+                eprintln!("UDP: send to {}: {}", to, e);
+            }
+        }
+    });
+    let mut buf = vec![0u8; MAX_REQUEST];
+    loop {
+        let (n, sender) = socket.recv_from(&mut buf).map_err(|e| format!("{}: {}", addr, e))?;
+        // A loopback bind only hears this machine, so an empty list lets that through
+        if n == 0 || !from.is_empty() && !from.contains(&sender.ip()) {
+            continue;
+        }
+        *last.lock().unwrap_or_else(|e| e.into_inner()) = Some(sender);
+        // Recorded before playing: the microphone can decode it before submit() returns
+        echoes.sent(&buf[..n]);
+        let reply = submit(&sender.ip().to_string(), Ok(Submission::Bytes(buf[..n].to_vec())), Schedule::default(), true);
+        if !reply.ok {
+            eprintln!("UDP: {}", reply.event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(text: &str) -> Job {
+        Job { submission: Ok(Submission::Text(text.into())), play: true, at: None, reply: channel().0 }
+    }
+
+    fn text(job: Job) -> String {
+        match job.submission {
+            Ok(Submission::Text(text)) => text,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn urgent_submissions_jump_the_queue() {
+        let queue = Queue::default();
+        assert_eq!(queue.push("a", Priority::Normal, job("first")), Ok(0));
+        assert_eq!(queue.push("a", Priority::Low, job("low")), Ok(1));
+        assert_eq!(queue.push("b", Priority::Normal, job("second")), Ok(1));
+        assert_eq!(queue.push("b", Priority::High, job("high")), Ok(0));
+        assert_eq!(text(queue.pop()), "high");
+        let status = queue.status();
+        assert!(status.busy);
+        assert_eq!((status.high, status.normal, status.low), (0, 2, 1));
+        queue.done();
+        let order: Vec<_> = (0..3).map(|_| text(queue.pop())).collect();
+        assert_eq!(order, ["first", "second", "low"]);
+    }
+
+    #[test]
+    fn clients_over_the_rate_limit_wait_for_their_bucket_to_refill() {
+        let limit = RateLimit { per_minute: 6, burst: 2 };
+        let (mut buckets, start) = (HashMap::new(), Instant::now());
+        assert_eq!(limit.take(&mut buckets, "a", start), Ok(()));
+        assert_eq!(limit.take(&mut buckets, "a", start), Ok(()));
+        assert_eq!(limit.take(&mut buckets, "a", start), Err(Duration::from_secs(10)));
+        // Other clients have buckets of their own
+        assert_eq!(limit.take(&mut buckets, "b", start), Ok(()));
+        assert_eq!(limit.take(&mut buckets, "a", start + Duration::from_secs(10)), Ok(()));
+        // Full buckets are forgotten
+        limit.take(&mut buckets, "c", start + Duration::from_secs(60)).unwrap();
+        assert_eq!(buckets.keys().collect::<Vec<_>>(), ["c"]);
+
+        let queue = Queue::with_rate_limit(RateLimit { per_minute: 1, burst: 1 });
+        assert_eq!(queue.push("a", Priority::High, job("first")), Ok(0));
+        assert!(queue.push("a", Priority::High, job("second")).is_err());
+        assert_eq!(queue.status().high, 1);
+    }
+
+    #[test]
+    fn unhealthy_without_capture_or_after_a_failed_playback() {
+        let health = Health::default();
+        let report = health.report(&Queue::default());
+        assert!(!report.ok && !report.capture_alive && report.playback_ok);
+        health.played(Some("device gone"));
+        assert_eq!(health.report(&Queue::default()).playback_error.as_deref(), Some("device gone"));
+        health.played(None);
+        assert!(health.report(&Queue::default()).playback_ok);
+    }
+
+    // An HTTP API on a free loopback port whose submissions get `reply`, and its address
+    fn serve_test_api(hub: &'static Hub, reply: fn(Schedule) -> Reply) -> SocketAddr {
+        let server: &'static tiny_http::Server = Box::leak(Box::new(tiny_http::Server::http("127.0.0.1:0").unwrap()));
+        let api = Api {
+            hub,
+            submit: Box::leak(Box::new(move |_: &str, _, schedule, _| reply(schedule))),
+            queue: Box::leak(Box::default()),
+            health: Box::leak(Box::default()),
+            token: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        };
+        std::thread::spawn(move || serve_http(server, api, &Relayed::default()));
+        server.server_addr().to_ip().unwrap()
+    }
+
+    #[test]
+    fn events_stream_the_hub_as_server_sent_events() {
+        let hub: &'static Hub = Box::leak(Box::default());
+        let reply = |_| Reply { ok: false, event: "{}".into(), wav: Vec::new(), retry_after: None };
+        let mut client = TcpStream::connect(serve_test_api(hub, reply)).unwrap();
+        client.write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut reader = std::io::BufReader::new(client);
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            std::io::BufRead::read_line(&mut reader, &mut head).unwrap();
+        }
+        assert!(head.starts_with("HTTP/1.1 200") && head.contains("Content-Type: text/event-stream"), "{}", head);
+        // The client is subscribed before the headers go out
+        hub.broadcast(&r#"{"type":"decoded","text":"hi"}"#.to_owned());
+        let mut event = String::new();
+        while !event.ends_with("\n\n") {
+            std::io::BufRead::read_line(&mut reader, &mut event).unwrap();
+        }
+        assert_eq!(event, "data: {\"type\":\"decoded\",\"text\":\"hi\"}\n\n");
+    }
+
+    #[test]
+    fn submissions_over_the_rate_limit_get_a_429() {
+        let reply = |_| Reply { ok: false, event: r#"{"type":"warning"}"#.into(), wav: Vec::new(), retry_after: Some(Duration::from_millis(2500)) };
+        let mut client = TcpStream::connect(serve_test_api(Box::leak(Box::default()), reply)).unwrap();
+        client.write_all(b"POST /tx HTTP/1.1\r\nHost: localhost\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 429"), "{}", response);
+        assert!(response.contains("Retry-After: 3\r\n") && response.ends_with(r#"{"type":"warning"}"#), "{}", response);
+    }
+
+    #[test]
+    fn start_times_come_from_the_query_too() {
+        let reply = |schedule: Schedule| Reply { ok: true, event: format!("{:?}", schedule.at.is_some()), wav: Vec::new(), retry_after: None };
+        let addr = serve_test_api(Box::leak(Box::default()), reply);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        let mut client = TcpStream::connect(addr).unwrap();
+        write!(client, "POST /tx?priority=high&at={} HTTP/1.1\r\nHost: localhost\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi", now + 5000).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200") && response.ends_with("true"), "{}", response);
+    }
+
+    #[test]
+    fn priorities_parse_by_name() {
+        assert_eq!("high".parse::<Priority>(), Ok(Priority::High));
+        assert!("urgent".parse::<Priority>().is_err());
+        assert_eq!(parse_request(r#"{"text":"hi"}"#).1.priority, Priority::Normal);
+        assert_eq!(parse_request(r#"{"text":"hi","priority":"low"}"#).1.priority, Priority::Low);
+    }
+
+    #[test]
+    fn start_times_must_be_in_the_next_few_minutes() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let (submission, schedule) = parse_request(&format!(r#"{{"text":"hi","at":{}}}"#, now + 2000));
+        assert!(submission.is_ok());
+        assert_eq!(schedule.at, Some(UNIX_EPOCH + Duration::from_millis(now + 2000)));
+        assert!(matches!(parse_request(&format!(r#"{{"text":"hi","at":{}}}"#, now - 1000)).0, Err(e) if e.contains("passed")));
+        assert!(matches!(parse_request(&format!(r#"{{"text":"hi","at":{}}}"#, now + 3_600_000)).0, Err(e) if e.contains("ahead")));
+        assert_eq!(parse_request(r#"{"text":"hi"}"#).1.at, None);
+    }
+}
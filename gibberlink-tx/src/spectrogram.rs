@@ -0,0 +1,163 @@
+// Spectrogram PNGs (the `spectrogram` feature): time left to right, frequency bottom to top from
+// 0 Hz to Nyquist, so tones can be checked against the band a protocol should use. Also the
+// averaged spectrum `spectrum` surveys a room with, to pick the protocol family it disturbs least.
+
+use gibberlink_core::{protocol_max_hz, protocol_min_hz, protocol_name, DEFAULT_SAMPLE_RATE, MAX_SAMPLES_PER_FRAME};
+use rustfft::num_complex::Complex;
+use std::path::Path;
+
+// One ggwave frame, so each tone fills whole bins at the default rate
+const FFT_LEN: usize = 1024;
+const MAX_COLUMNS: usize = 4000;
+// Dynamic range shown, below the loudest bin
+const FLOOR_DB: f32 = -90.0;
+// Left margin holding a tick every 1 kHz (longer every 5 kHz)
+const MARGIN: usize = 12;
+
+/// Renders mono `samples` to a PNG at `path`; returns the time covered by each column in seconds.
+pub fn render(path: &Path, samples: &[f32], sample_rate: u32) -> Result<f64, String> {
+    if samples.len() < FFT_LEN {
+        return Err("recording too short for a spectrogram".into());
+    }
+    // Half-overlapping frames, or further apart when that would make the image too wide
+    let hop = (FFT_LEN / 2).max((samples.len() - FFT_LEN).div_ceil(MAX_COLUMNS - 1));
+    let window = hann();
+    let fft = rustfft::FftPlanner::new().plan_fft_forward(FFT_LEN);
+    let bins = FFT_LEN / 2;
+    let mut columns: Vec<Vec<f32>> = Vec::new();
+    let mut buf = vec![Complex::default(); FFT_LEN];
+    for start in (0..=samples.len() - FFT_LEN).step_by(hop) {
+        for (slot, (&s, &w)) in buf.iter_mut().zip(samples[start..].iter().zip(&window)) {
+            *slot = Complex::new(s * w, 0.0);
+        }
+        fft.process(&mut buf);
+        columns.push(buf[..bins].iter().map(|c| 10.0 * (c.norm_sqr() + 1e-12).log10()).collect());
+    }
+    let peak = columns.iter().flatten().copied().fold(f32::MIN, f32::max);
+
+    let (width, height) = (MARGIN + columns.len(), bins);
+    let mut pixels = vec![0u8; width * height * 3];
+    for (x, column) in columns.iter().enumerate() {
+        for (bin, &db) in column.iter().enumerate() {
+            let level = ((db - peak - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0);
+            let y = height - 1 - bin;
+            let at = (y * width + MARGIN + x) * 3;
+            pixels[at..at + 3].copy_from_slice(&heat(level));
+        }
+    }
+    let hz_per_bin = sample_rate as f32 / FFT_LEN as f32;
+    for khz in 1..=(sample_rate / 2000) {
+        let y = height as isize - 1 - (khz as f32 * 1000.0 / hz_per_bin).round() as isize;
+        if y < 0 {
+            break;
+        }
+        let len = if khz % 5 == 0 { MARGIN } else { MARGIN / 2 };
+        let row = y as usize * width * 3;
+        pixels[row..row + len * 3].fill(200);
+    }
+
+    let file = std::io::BufWriter::new(std::fs::File::create(path).map_err(|e| format!("create: {}", e))?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| format!("png: {}", e))?;
+    writer.write_image_data(&pixels).map_err(|e| format!("png: {}", e))?;
+    Ok(hop as f64 / sample_rate as f64)
+}
+
+fn hann() -> Vec<f32> {
+    (0..FFT_LEN).map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FFT_LEN as f32).cos()).collect()
+}
+
+/// The noise `spectrum` measured in one protocol family's tone band.
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct BandNoise {
+    pub family: String,
+    pub low_hz: f32,
+    pub high_hz: f32,
+    /// RMS level of everything in the band, in dBFS; `None` when the band is above Nyquist
+    pub noise_dbfs: Option<f32>,
+}
+
+/// The noise in each protocol family's band over mono `samples`, from a Welch average: the power
+/// spectra of half-overlapping Hann-windowed frames, averaged so that brief sounds count for their
+/// share of the time rather than their peak.
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+pub fn band_noise(samples: &[f32], sample_rate: u32) -> Result<Vec<BandNoise>, String> {
+    if samples.len() < FFT_LEN {
+        return Err("recording too short to measure".into());
+    }
+    let window = hann();
+    // Scales summed bin powers to the mean square of the signal in those bins (Parseval), counting
+    // the mirrored negative frequencies
+    let scale = 2.0 / (FFT_LEN as f64 * window.iter().map(|w| (w * w) as f64).sum::<f64>());
+    let fft = rustfft::FftPlanner::new().plan_fft_forward(FFT_LEN);
+    let mut power = vec![0f64; FFT_LEN / 2];
+    let mut frames = 0;
+    let mut buf = vec![Complex::default(); FFT_LEN];
+    for start in (0..=samples.len() - FFT_LEN).step_by(FFT_LEN / 2) {
+        for (slot, (&s, &w)) in buf.iter_mut().zip(samples[start..].iter().zip(&window)) {
+            *slot = Complex::new(s * w, 0.0);
+        }
+        fft.process(&mut buf);
+        for (total, c) in power.iter_mut().zip(&buf) {
+            *total += c.norm_sqr() as f64 * scale;
+        }
+        frames += 1;
+    }
+    let hz_per_bin = sample_rate as f32 / FFT_LEN as f32;
+    // One protocol per family; speeds share the band. Tones are placed for the default frame at 48 kHz
+    let bands = (0..4).map(|family| {
+        let protocol = family * 3;
+        let low_hz = protocol_min_hz(protocol, DEFAULT_SAMPLE_RATE, MAX_SAMPLES_PER_FRAME);
+        let high_hz = protocol_max_hz(protocol, DEFAULT_SAMPLE_RATE, MAX_SAMPLES_PER_FRAME);
+        let bins = (low_hz / hz_per_bin).round() as usize..(high_hz / hz_per_bin).round() as usize;
+        let noise_dbfs = (bins.end <= power.len()).then(|| {
+            let mean_square = power[bins].iter().sum::<f64>() / frames as f64;
+            (10.0 * mean_square.max(1e-12).log10()) as f32
+        });
+        let name = protocol_name(protocol);
+        BandNoise { family: name.split(':').next().unwrap_or(&name).to_owned(), low_hz, high_hz, noise_dbfs }
+    });
+    Ok(bands.collect())
+}
+
+/// The family whose band was quietest.
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+pub fn cleanest(bands: &[BandNoise]) -> Option<&BandNoise> {
+    bands.iter().filter(|b| b.noise_dbfs.is_some()).min_by(|a, b| a.noise_dbfs.partial_cmp(&b.noise_dbfs).expect("levels are finite"))
+}
+
+// Black -> blue -> red -> yellow -> white
+fn heat(level: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 5] = [[0.0, 0.0, 0.0], [0.0, 0.0, 160.0], [200.0, 0.0, 0.0], [255.0, 220.0, 0.0], [255.0, 255.0, 255.0]];
+    let pos = level * (STOPS.len() - 1) as f32;
+    let i = (pos as usize).min(STOPS.len() - 2);
+    let t = pos - i as f32;
+    let mix = |c: usize| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * t) as u8;
+    [mix(0), mix(1), mix(2)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_band_without_the_noise_is_recommended() {
+        let rate = 48000;
+        // A loud 2 kHz hum sits in the audible, dt and mt bands but not ultrasound
+        let hum: Vec<f32> = (0..rate).map(|i| 0.5 * (std::f32::consts::TAU * 2000.0 * i as f32 / rate as f32).sin()).collect();
+        let bands = band_noise(&hum, rate).unwrap();
+        assert_eq!(bands.iter().map(|b| b.family.as_str()).collect::<Vec<_>>(), ["audible", "ultrasound", "dt", "mt"]);
+        // A 0.5 amplitude sine has an RMS of about -9 dBFS
+        assert!((bands[0].noise_dbfs.unwrap() + 9.0).abs() < 0.5, "{:?}", bands[0]);
+        assert_eq!(cleanest(&bands).unwrap().family, "ultrasound");
+
+        // At 16 kHz the ultrasound band is out of reach
+        let bands = band_noise(&hum[..16000], 16000).unwrap();
+        assert_eq!(bands[1].noise_dbfs, None);
+        assert_ne!(cleanest(&bands).unwrap().family, "ultrasound");
+        assert!(band_noise(&hum[..100], rate).is_err());
+    }
+}
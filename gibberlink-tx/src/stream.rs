@@ -0,0 +1,121 @@
+// Live network audio (the `stream` feature): Icecast, SHOUTcast and other HTTP(S) streams are
+// fetched and decoded in-process through symphonia; RTSP, RTMP and the rest go through an `ffmpeg`
+// on the PATH. Samples are demodulated as they arrive, so an endless broadcast can be monitored.
+
+use gibberlink_core::{ggwave_consts, GgWave, OperatingMode, ParametersBuilder, MAX_SAMPLES_PER_FRAME};
+use std::io::Read;
+use std::sync::Mutex;
+use symphonia::core::io::ReadOnlySource;
+use symphonia::core::probe::Hint;
+
+// ffmpeg is asked for mono float samples at this rate
+const FFMPEG_RATE: u32 = 48000;
+
+/// Receives the stream at `url` and calls `on_payload` for every ggwave transmission in it, with the
+/// receiver settings in `params`, until the stream ends.
+pub fn listen(url: &str, params: ParametersBuilder, mut on_payload: impl FnMut(Vec<u8>)) -> Result<(), String> {
+    let mut receiver: Option<(u32, GgWave)> = None;
+    let mut on_samples = |samples: &[f32], rate: u32| {
+        // Built at the stream's own rate, once that is known
+        if receiver.as_ref().is_none_or(|(r, _)| *r != rate) {
+            let instance = params.clone().operating_mode(OperatingMode::Rx).input_format(ggwave_consts::GGWAVE_SAMPLE_FORMAT_F32).sample_rate(rate).init();
+            match instance {
+                Ok(instance) => receiver = Some((rate, instance)),
+                Err(e) => return eprintln!("Stream at {} Hz: ggwave init failed: {}", rate, e),
+            }
+        }
+        let (_, instance) = receiver.as_mut().expect("built above");
+        // A frame at a time: each call yields at most one transmission
+        for frame in samples.chunks(MAX_SAMPLES_PER_FRAME as usize) {
+            let bytes: Vec<u8> = frame.iter().flat_map(|s| s.to_le_bytes()).collect();
+            if let Some(payload) = instance.receive(&bytes) {
+                on_payload(payload);
+            }
+        }
+    };
+    match url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase()) {
+        Some(scheme) if scheme == "http" || scheme == "https" => http(url, &mut on_samples),
+        _ => ffmpeg(url, &mut on_samples),
+    }
+}
+
+fn http(url: &str, on_samples: &mut dyn FnMut(&[f32], u32)) -> Result<(), String> {
+    let response = ureq::get(url).call().map_err(|e| format!("{}: {}", url, e))?;
+    let content_type = response.headers().get("content-type").and_then(|v| v.to_str().ok()).unwrap_or_default().to_ascii_lowercase();
+    eprintln!("Receiving {} ({})", url, if content_type.is_empty() { "no content type" } else { &content_type });
+    let mut hint = Hint::new();
+    if let Some(ext) = extension_for(&content_type).or_else(|| url.split(['?', '#']).next()?.rsplit_once('.').map(|(_, ext)| ext)) {
+        hint.with_extension(ext);
+    }
+    let body = Box::new(ReadOnlySource::new(Shared(Mutex::new(response.into_body().into_reader()))));
+    crate::codecs::decode_each(body, &hint, on_samples).map(|_| ())
+}
+
+// What symphonia's probe knows a stream's Content-Type by
+fn extension_for(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    Some(match mime {
+        "audio/mpeg" | "audio/mp3" => "mp3",
+        "audio/aac" | "audio/aacp" | "audio/x-aac" => "aac",
+        "audio/ogg" | "application/ogg" | "audio/vorbis" => "ogg",
+        "audio/flac" | "audio/x-flac" => "flac",
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+        "audio/mp4" | "audio/x-m4a" => "m4a",
+        _ => return None,
+    })
+}
+
+// Symphonia wants a `Sync` source; the response body is only `Send`
+struct Shared<R>(Mutex<R>);
+
+impl<R: Read> Read for Shared<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.get_mut().unwrap_or_else(|e| e.into_inner()).read(buf)
+    }
+}
+
+fn ffmpeg(url: &str, on_samples: &mut dyn FnMut(&[f32], u32)) -> Result<(), String> {
+    let rate = FFMPEG_RATE.to_string();
+    let mut child = std::process::Command::new("ffmpeg")
+        .args(["-nostdin", "-loglevel", "error", "-i", url, "-vn", "-ac", "1", "-ar", &rate, "-f", "f32le", "-"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{} streams are read through ffmpeg, which could not be started: {}", url.split("://").next().unwrap_or(url), e))?;
+    eprintln!("Receiving {} through ffmpeg", url);
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut buf = vec![0u8; 16 * 1024];
+    // A read can end mid-sample; the partial sample waits for the rest
+    let mut pending = 0;
+    loop {
+        let n = match stdout.read(&mut buf[pending..]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("ffmpeg: {}", e)),
+        };
+        let filled = pending + n;
+        let whole = filled - filled % 4;
+        let samples: Vec<f32> = buf[..whole].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        on_samples(&samples, FFMPEG_RATE);
+        buf.copy_within(whole..filled, 0);
+        pending = filled - whole;
+    }
+    let status = child.wait().map_err(|e| format!("ffmpeg: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("ffmpeg exited with {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_content_types_pick_the_demuxer() {
+        assert_eq!(extension_for("audio/mpeg; charset=binary"), Some("mp3"));
+        assert_eq!(extension_for("text/html"), None);
+    }
+}
@@ -0,0 +1,45 @@
+// HTTPS and WSS for the `serve` bridges (the `tls` feature), through rustls: a certificate and key
+// from PEM files, or a self-signed certificate generated at startup.
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Arc;
+
+pub type Config = Arc<rustls::ServerConfig>;
+
+/// Loads a PEM certificate chain (leaf first) and its private key.
+pub fn load(cert: &Path, key: &Path) -> Result<Config, String> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("{}: {}", cert.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("{}: no certificate", cert.display()));
+    }
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|e| format!("{}: {}", key.display(), e))?;
+    config(certs, key)
+}
+
+/// Generates a self-signed certificate for `names` (DNS names or IP addresses). Clients have to be
+/// told to trust it, so its SHA-256 fingerprint is returned for them to check.
+pub fn self_signed(names: &[String]) -> Result<(Config, String), String> {
+    let rcgen::CertifiedKey { cert, key_pair } = rcgen::generate_simple_self_signed(names).map_err(|e| format!("self-signed certificate: {}", e))?;
+    let fingerprint: Vec<String> = Sha256::digest(cert.der()).iter().map(|b| format!("{:02X}", b)).collect();
+    let key = PrivateKeyDer::Pkcs8(key_pair.serialize_der().into());
+    Ok((config(vec![cert.der().clone()], key)?, fingerprint.join(":")))
+}
+
+fn config(certs: Vec<CertificateDer<'static>>, key: PrivateKeyDer<'static>) -> Result<Config, String> {
+    rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map(Arc::new)
+        .map_err(|e| format!("TLS: {}", e))
+}
+
+/// Starts a server-side TLS session over `stream`; the handshake happens on first use.
+pub fn accept<S: std::io::Read + std::io::Write>(config: &Config, stream: S) -> Result<rustls::StreamOwned<rustls::ServerConnection, S>, String> {
+    let connection = rustls::ServerConnection::new(config.clone()).map_err(|e| format!("TLS: {}", e))?;
+    Ok(rustls::StreamOwned::new(connection, stream))
+}
@@ -0,0 +1,54 @@
+// Watch-folder mode (the `watch` feature): notices files dropped into a directory through notify
+// and hands each one over once it has stopped changing.
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+// Writers rarely create a file in one step, so it counts as finished once nothing has touched it
+// for this long
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// Calls `on_file` for every file already in `dir` (in name order), then for each file created or
+/// changed there once it has settled. Runs until interrupted or the watcher fails.
+pub fn run(dir: &Path, mut on_file: impl FnMut(&Path)) -> Result<(), String> {
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| format!("watch: {}", e))?;
+    watcher.watch(dir, RecursiveMode::NonRecursive).map_err(|e| format!("watch {}: {}", dir.display(), e))?;
+
+    let mut existing: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| format!("read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    existing.sort();
+    for path in existing {
+        on_file(&path);
+    }
+
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        let wait = pending.values().map(|&seen| (seen + SETTLE).saturating_duration_since(Instant::now())).min();
+        match rx.recv_timeout(wait.unwrap_or(Duration::from_secs(3600))) {
+            Ok(Ok(event)) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths {
+                    pending.insert(path, Instant::now());
+                }
+            }
+            Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => {}
+            Ok(Err(e)) => return Err(format!("watch: {}", e)),
+            Err(RecvTimeoutError::Disconnected) => return Err("watcher stopped".into()),
+        }
+        let mut settled: Vec<PathBuf> = pending.iter().filter(|(_, seen)| seen.elapsed() >= SETTLE).map(|(path, _)| path.clone()).collect();
+        settled.sort();
+        for path in settled {
+            pending.remove(&path);
+            // Renamed away or deleted in the meantime, or a directory such as the done folder
+            if path.is_file() {
+                on_file(&path);
+            }
+        }
+    }
+}
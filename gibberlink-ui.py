@@ -14,11 +14,344 @@ If the Rust binary is missing, the script will attempt to build it with Cargo.
 """
 
 import argparse
+import json
 import os
+import queue
 import subprocess
 import sys
+import threading
+import time
 from typing import Optional
 
+# ggwave limits/timing, used to validate input before calling the CLI
+MAX_VARIABLE_PAYLOAD = 140
+# Longer payloads are sent as up to 255 chunks with an 8-byte header each (gibberlink-core/src/chunk.rs)
+CHUNK_DATA = MAX_VARIABLE_PAYLOAD - 8
+MAX_CHUNKED_PAYLOAD = CHUNK_DATA * 255
+CHUNK_GAP_SECONDS = 0.1
+# The input meter shows the top this many dB below full scale
+LEVEL_RANGE_DB = 60
+# How `--list-input-devices` names the outputs `--monitor-of` can capture
+MONITOR_PREFIX = "Monitor of "
+FRAME_SECONDS = 1024 / 48000
+MARKER_FRAMES = 16
+PROTOCOL_FRAMES_PER_TX = {"normal": 9, "fast": 6, "fastest": 3}
+# family -> (bytes per tx, extra)
+PROTOCOL_FAMILIES = {"audible": (3, 1), "ultrasound": (3, 1), "dt": (1, 2), "mt": (1, 1)}
+
+
+def estimate_airtime(protocol: str, nbytes: int) -> float:
+    """Approximate transmission length in seconds for a variable-length ggwave payload."""
+    family, _, speed = protocol.partition(":")
+    bytes_per_tx, extra = PROTOCOL_FAMILIES.get(family, (3, 1))
+    frames_per_tx = PROTOCOL_FRAMES_PER_TX.get(speed or "normal", 9)
+    ecc = 2 if nbytes < 4 else max(4, 2 * (nbytes // 5))
+    total = 3 + nbytes + ecc  # length header + payload + ECC
+    data_frames = extra * -(-total // bytes_per_tx) * frames_per_tx
+    return (2 * MARKER_FRAMES + data_frames) * FRAME_SECONDS
+
+
+def chunk_sizes(nbytes: int) -> list:
+    """Sizes of the transmissions the CLI sends for a payload, chunk headers included."""
+    if nbytes <= MAX_VARIABLE_PAYLOAD:
+        return [nbytes]
+    full, rest = divmod(nbytes, CHUNK_DATA)
+    return [MAX_VARIABLE_PAYLOAD] * full + ([rest + 8] if rest else [])
+
+
+def config_path() -> str:
+    """Presets file shared with the CLI (`--preset`), matching Rust's `dirs::config_dir()`."""
+    if os.name == "nt":
+        base = os.environ.get("APPDATA") or os.path.expanduser("~")
+    elif sys.platform == "darwin":
+        base = os.path.expanduser("~/Library/Application Support")
+    else:
+        base = os.environ.get("XDG_CONFIG_HOME") or os.path.expanduser("~/.config")
+    return os.path.join(base, "gibberlink", "presets.toml")
+
+
+def _parse_toml_minimal(raw: str) -> dict:
+    # Fallback for Pythons without tomllib: only the subset save_config() writes
+    data: dict = {}
+    table = data
+    for line in raw.splitlines():
+        line = line.strip()
+        if not line or line.startswith("#"):
+            continue
+        if line.startswith("[") and line.endswith("]"):
+            table = data
+            for part in line[1:-1].split(".", 1):
+                part = part.strip()
+                key = json.loads(part) if part.startswith('"') else part
+                table = table.setdefault(key, {})
+            continue
+        key, _, value = line.partition("=")
+        key = key.strip()
+        key = json.loads(key) if key.startswith('"') else key
+        value = value.strip()
+        table[key] = {"true": True, "false": False}.get(value, json.loads(value) if value[:1] in '"-0123456789' else value)
+    return data
+
+
+def load_config(path: Optional[str] = None) -> dict:
+    path = path or config_path()
+    try:
+        with open(path, "rb") as f:
+            raw = f.read()
+    except OSError:
+        return {}
+    try:
+        import tomllib
+        return tomllib.loads(raw.decode("utf-8"))
+    except ImportError:
+        return _parse_toml_minimal(raw.decode("utf-8"))
+    except Exception as e:
+        print(f"Ignoring unreadable config {path}: {e}", file=sys.stderr)
+        return {}
+
+
+def _toml_key(key: str) -> str:
+    bare = key and all(c.isascii() and (c.isalnum() or c in "_-") for c in key)
+    return key if bare else json.dumps(key, ensure_ascii=False)
+
+
+def _toml_value(value) -> str:
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    if isinstance(value, (int, float)):
+        return repr(value)
+    return json.dumps(str(value), ensure_ascii=False)
+
+
+def save_config(config: dict, path: Optional[str] = None) -> None:
+    path = path or config_path()
+    # top-level keys (e.g. max_freq_hz) must precede the first table header
+    lines = [f"{_toml_key(k)} = {_toml_value(v)}" for k, v in config.items() if not isinstance(v, dict)]
+    if lines:
+        lines.append("")
+    for name, table in config.items():
+        if not isinstance(table, dict):
+            continue
+        nested = {k: v for k, v in table.items() if isinstance(v, dict)}
+        flat = {k: v for k, v in table.items() if not isinstance(v, dict)}
+        if flat:
+            lines.append(f"[{name}]")
+            lines += [f"{_toml_key(k)} = {_toml_value(v)}" for k, v in flat.items()]
+            lines.append("")
+        for key, sub in nested.items():
+            lines.append(f"[{name}.{json.dumps(key, ensure_ascii=False)}]")
+            lines += [f"{_toml_key(k)} = {_toml_value(v)}" for k, v in sub.items()]
+            lines.append("")
+    os.makedirs(os.path.dirname(path), exist_ok=True)
+    with open(path, "w", encoding="utf-8") as f:
+        f.write("\n".join(lines))
+
+
+# UI string catalogues, keyed by the English text
+TRANSLATIONS = {
+    "es": {
+        "Text to Gibberlink (ggwave)": "Texto a Gibberlink (ggwave)",
+        "Preset:": "Preajuste:",
+        "Presets": "Preajustes",
+        "Save": "Guardar",
+        "New...": "Nuevo...",
+        "Duplicate...": "Duplicar...",
+        "Delete": "Eliminar",
+        "Theme:": "Tema:",
+        "Scale:": "Escala:",
+        "Language:": "Idioma:",
+        "Text to encode:": "Texto a codificar:",
+        "Protocol:": "Protocolo:",
+        "Volume (0-100):": "Volumen (0-100):",
+        "Output file:": "Archivo de salida:",
+        "Play after generating": "Reproducir tras generar",
+        "Generate + Play": "Generar + reproducir",
+        "Please enter some text.": "Introduce algún texto.",
+        "Generating...": "Generando...",
+        "Failed: {}": "Error: {}",
+        "Enter some text": "Introduce texto",
+        "Too long by {} bytes": "Sobran {} bytes",
+        "{} / {} bytes": "{} / {} bytes",
+        "{} bytes in {} chunks": "{} bytes en {} fragmentos",
+        "~{:.1f} s on air": "~{:.1f} s en el aire",
+        "Tip: Very high volumes (>50) can distort or be uncomfortable.": "Consejo: volúmenes muy altos (>50) pueden distorsionar o resultar molestos.",
+        "Decode from WAV -> text:": "Decodificar WAV -> texto:",
+        "Browse...": "Examinar...",
+        "Use Last Output": "Usar última salida",
+        "Decoded text:": "Texto decodificado:",
+        "Decode": "Decodificar",
+        "Please choose or provide a WAV path.": "Elige o indica una ruta WAV.",
+        "Preset name:": "Nombre del preajuste:",
+        "Save preset": "Guardar preajuste",
+        "New preset": "Nuevo preajuste",
+        "Duplicate preset": "Duplicar preajuste",
+        "Delete preset": "Eliminar preajuste",
+        "Overwrite preset {!r}?": "¿Sobrescribir el preajuste {!r}?",
+        "Delete preset {!r}?": "¿Eliminar el preajuste {!r}?",
+        "Saved preset {!r}": "Preajuste {!r} guardado",
+        "Deleted preset {!r}": "Preajuste {!r} eliminado",
+        "Language changes apply after restarting the UI": "El cambio de idioma se aplica al reiniciar la interfaz",
+        "Input level:": "Nivel de entrada:",
+        "Message {}: chunk {}/{}": "Mensaje {}: fragmento {}/{}",
+        "Listen": "Escuchar",
+        "Stop": "Detener",
+        "Listening...": "Escuchando...",
+        "Listening failed: {}": "Error al escuchar: {}",
+        "Text": "Texto",
+        "Devices": "Dispositivos",
+        "Output device:": "Dispositivo de salida:",
+        "Input device:": "Dispositivo de entrada:",
+        "Play test tone": "Reproducir tono de prueba",
+        "Test microphone": "Probar micrófono",
+        "Refresh": "Actualizar",
+        "Could not list devices: {}": "No se pudieron listar los dispositivos: {}",
+        "~{:.0f} s left": "quedan ~{:.0f} s",
+        "Receive": "Recibir",
+        "Files": "Archivos",
+        "File to send:": "Archivo a enviar:",
+        "Reliable (resend lost chunks)": "Fiable (reenviar fragmentos perdidos)",
+        "Save received files to:": "Guardar archivos recibidos en:",
+        "Not a readable file of at most {} bytes": "No es un archivo legible de como máximo {} bytes",
+        "{} bytes": "{} bytes",
+        "Sent": "Enviado",
+        "Send": "Enviar",
+        "Cancel": "Cancelar",
+    },
+    "de": {
+        "Text to Gibberlink (ggwave)": "Text zu Gibberlink (ggwave)",
+        "Preset:": "Voreinstellung:",
+        "Presets": "Voreinstellungen",
+        "Save": "Speichern",
+        "New...": "Neu...",
+        "Duplicate...": "Duplizieren...",
+        "Delete": "Löschen",
+        "Theme:": "Design:",
+        "Scale:": "Skalierung:",
+        "Language:": "Sprache:",
+        "Text to encode:": "Zu kodierender Text:",
+        "Protocol:": "Protokoll:",
+        "Volume (0-100):": "Lautstärke (0-100):",
+        "Output file:": "Ausgabedatei:",
+        "Play after generating": "Nach dem Erzeugen abspielen",
+        "Generate + Play": "Erzeugen + abspielen",
+        "Please enter some text.": "Bitte Text eingeben.",
+        "Generating...": "Wird erzeugt...",
+        "Failed: {}": "Fehlgeschlagen: {}",
+        "Enter some text": "Text eingeben",
+        "Too long by {} bytes": "{} Bytes zu lang",
+        "{} / {} bytes": "{} / {} Bytes",
+        "{} bytes in {} chunks": "{} Bytes in {} Teilen",
+        "~{:.1f} s on air": "~{:.1f} s Sendezeit",
+        "Tip: Very high volumes (>50) can distort or be uncomfortable.": "Tipp: Sehr hohe Lautstärken (>50) können verzerren oder unangenehm sein.",
+        "Decode from WAV -> text:": "WAV -> Text dekodieren:",
+        "Browse...": "Durchsuchen...",
+        "Use Last Output": "Letzte Ausgabe verwenden",
+        "Decoded text:": "Dekodierter Text:",
+        "Decode": "Dekodieren",
+        "Please choose or provide a WAV path.": "Bitte eine WAV-Datei wählen oder angeben.",
+        "Preset name:": "Name der Voreinstellung:",
+        "Save preset": "Voreinstellung speichern",
+        "New preset": "Neue Voreinstellung",
+        "Duplicate preset": "Voreinstellung duplizieren",
+        "Delete preset": "Voreinstellung löschen",
+        "Overwrite preset {!r}?": "Voreinstellung {!r} überschreiben?",
+        "Delete preset {!r}?": "Voreinstellung {!r} löschen?",
+        "Saved preset {!r}": "Voreinstellung {!r} gespeichert",
+        "Deleted preset {!r}": "Voreinstellung {!r} gelöscht",
+        "Language changes apply after restarting the UI": "Die Sprachänderung wird nach einem Neustart wirksam",
+        "Input level:": "Eingangspegel:",
+        "Message {}: chunk {}/{}": "Nachricht {}: Teil {}/{}",
+        "Listen": "Empfangen",
+        "Stop": "Stopp",
+        "Listening...": "Empfange...",
+        "Listening failed: {}": "Empfang fehlgeschlagen: {}",
+        "Text": "Text",
+        "Devices": "Geräte",
+        "Output device:": "Ausgabegerät:",
+        "Input device:": "Eingabegerät:",
+        "Play test tone": "Testton abspielen",
+        "Test microphone": "Mikrofon testen",
+        "Refresh": "Aktualisieren",
+        "Could not list devices: {}": "Geräte konnten nicht aufgelistet werden: {}",
+        "~{:.0f} s left": "noch ~{:.0f} s",
+        "Receive": "Empfangen",
+        "Files": "Dateien",
+        "File to send:": "Zu sendende Datei:",
+        "Reliable (resend lost chunks)": "Zuverlässig (verlorene Teile erneut senden)",
+        "Save received files to:": "Empfangene Dateien speichern in:",
+        "Not a readable file of at most {} bytes": "Keine lesbare Datei mit höchstens {} Bytes",
+        "{} bytes": "{} Bytes",
+        "Sent": "Gesendet",
+        "Send": "Senden",
+        "Cancel": "Abbrechen",
+    },
+}
+UI_LANGUAGES = ["en"] + sorted(TRANSLATIONS)
+# Families tried in order for message panes; the first installed one wins
+MESSAGE_FONT_FALLBACKS = {
+    "ja": ["Noto Sans CJK JP", "Yu Gothic", "Hiragino Sans", "MS Gothic"],
+    "zh": ["Noto Sans CJK SC", "Microsoft YaHei", "PingFang SC", "SimSun"],
+    "ko": ["Noto Sans CJK KR", "Malgun Gothic", "Apple SD Gothic Neo"],
+    "ar": ["Noto Naskh Arabic", "Noto Sans Arabic", "Segoe UI", "Geeza Pro"],
+    "he": ["Noto Sans Hebrew", "Segoe UI", "Arial Hebrew"],
+    "hi": ["Noto Sans Devanagari", "Nirmala UI", "Kohinoor Devanagari"],
+    "ru": ["Noto Sans", "DejaVu Sans", "Segoe UI"],
+    "": ["Noto Sans", "DejaVu Sans", "Segoe UI", "Helvetica Neue", "Arial Unicode MS"],
+}
+_language = "en"
+
+
+def detect_language(configured: Optional[str] = None) -> str:
+    lang = configured or os.environ.get("GIBBERLINK_LANG") or os.environ.get("LC_ALL") or os.environ.get("LANG") or ""
+    if not lang and os.name == "nt":
+        import locale
+        lang = locale.getdefaultlocale()[0] or ""
+    return lang.split(".")[0].split("_")[0].split("-")[0].lower() or "en"
+
+
+def set_language(lang: str) -> None:
+    global _language
+    _language = lang
+
+
+def tr(message: str) -> str:
+    return TRANSLATIONS.get(_language, {}).get(message, message)
+
+
+def message_font_family(families, lang: str, preferred: Optional[str] = None) -> Optional[str]:
+    """Pick an installed font for message text, preferring one that covers the UI language's script."""
+    installed = set(families)
+    for family in [preferred] + MESSAGE_FONT_FALLBACKS.get(lang, []) + MESSAGE_FONT_FALLBACKS[""]:
+        if family and family in installed:
+            return family
+    return None
+
+
+# name -> (window bg, fg, field bg, select bg, ok, error, muted)
+THEME_PALETTES = {
+    "light": ("#f0f0f0", "#000000", "#ffffff", "#3874d8", "#0a0", "#a00", "#666"),
+    "dark": ("#1e1e1e", "#e6e6e6", "#2b2b2b", "#3a6ea5", "#4c4", "#f66", "#aaa"),
+    "high-contrast": ("#000000", "#ffffff", "#000000", "#ffff00", "#00ff00", "#ffff00", "#ffffff"),
+}
+UI_SCALES = ["100%", "125%", "150%", "175%", "200%"]
+
+
+def system_prefers_dark() -> bool:
+    try:
+        if sys.platform == "darwin":
+            out = subprocess.run(["defaults", "read", "-g", "AppleInterfaceStyle"], capture_output=True, text=True)
+            return "dark" in out.stdout.lower()
+        if os.name == "nt":
+            import winreg
+            key = winreg.OpenKey(winreg.HKEY_CURRENT_USER, r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize")
+            return winreg.QueryValueEx(key, "AppsUseLightTheme")[0] == 0
+        if "dark" in os.environ.get("GTK_THEME", "").lower():
+            return True
+        out = subprocess.run(["gsettings", "get", "org.gnome.desktop.interface", "color-scheme"], capture_output=True, text=True)
+        return "dark" in out.stdout.lower()
+    except Exception:
+        return False
+
 
 def ensure_binary() -> str:
     # Prefer a bundled CLI when running as a packaged (PyInstaller) app
@@ -45,8 +378,15 @@ def ensure_binary() -> str:
 
     # 4) Try building it from source
     print("Building Rust gibberlink-tx binary (first run only)...", flush=True)
+    crate_dir = os.path.join(here, "gibberlink-tx")
     try:
-        subprocess.check_call(["cargo", "build", "--release"], cwd=os.path.join(here, "gibberlink-tx"))
+        # Playback (the default `audio` feature) needs the ALSA headers on Linux, so fall back to a
+        # build that only writes the WAV rather than failing outright
+        try:
+            subprocess.check_call(["cargo", "build", "--release"], cwd=crate_dir)
+        except subprocess.CalledProcessError:
+            print("Build with audio playback failed (on Linux, install libasound2-dev); building without it...", file=sys.stderr, flush=True)
+            subprocess.check_call(["cargo", "build", "--release", "--no-default-features", "--features", "extras"], cwd=crate_dir)
     except FileNotFoundError:
         print("Cargo not found. Please install Rust (https://rustup.rs/) to build the binary.", file=sys.stderr)
         sys.exit(2)
@@ -133,6 +473,7 @@ def run_ui() -> int:
         cmd = [exe, "--out", out_path, "--protocol", protocol, "--volume", str(volume)]
         if play_var.get():
             cmd.append("--play")
+            cmd += device_args("output_device", "--device")
         # pass text via arg (handles spaces safely)
         cmd += ["--text", txt]
         status_var.set("Generating...")
@@ -219,6 +560,7 @@ def main() -> int:
     parser.add_argument("--no-play", dest="play", action="store_false", help="Do not play after generating")
     parser.add_argument("--ui", action="store_true", help="Open a small UI for text + volume")
     parser.add_argument("--decode", dest="decode_wav", help="Decode payload from WAV -> text and print")
+    parser.add_argument("--payload-format", default="text", choices=["text", "cbor", "msgpack"], help="Send JSON compactly as cbor/msgpack (default text)")
     args = parser.parse_args()
 
     if args.ui:
@@ -228,10 +570,10 @@ def main() -> int:
 
     # Decode CLI mode
     if args.decode_wav:
-        res = subprocess.run([exe, "--decode-wav", args.decode_wav])
+        res = subprocess.run([exe, "--decode-wav", args.decode_wav, "--payload-format", args.payload_format])
         return res.returncode
 
-    cmd = [exe, "--out", args.out, "--protocol", args.protocol, "--volume", str(args.volume)]
+    cmd = [exe, "--out", args.out, "--protocol", args.protocol, "--volume", str(args.volume), "--payload-format", args.payload_format]
     if args.text:
         cmd += ["--text", args.text]
         input_data = None
@@ -248,28 +590,54 @@ def main() -> int:
 def run_ui_fixed() -> int:
     try:
         import tkinter as tk
-        from tkinter import ttk, filedialog
+        from tkinter import ttk, filedialog, messagebox, simpledialog
+        import tkinter.font as tkfont
     except Exception as e:
         print(f"Tkinter is not available: {e}", file=sys.stderr)
         return 2
 
     exe = ensure_binary()
 
-    root = tk.Tk()
-    root.title("Text to Gibberlink (ggwave)")
-    root.geometry("680x520")
+    # Presets (shared with `gibberlink-tx --preset NAME`) and UI settings
+    config = load_config()
+    presets = config.setdefault("presets", {})
+    ui_settings = config.setdefault("ui", {})
+    language = detect_language(ui_settings.get("language"))
+    set_language(language)
 
-    mainframe = ttk.Frame(root, padding=12)
-    mainframe.pack(fill=tk.BOTH, expand=True)
+    try:
+        from tkinterdnd2 import DND_FILES as dnd_files, TkinterDnD
+        root = TkinterDnD.Tk()
+    except ImportError:
+        dnd_files = None
+        root = tk.Tk()
+    root.title(tr("Text to Gibberlink (ggwave)"))
+    theme_var = tk.StringVar(value=ui_settings.get("theme", "system"))
+    scale_var = tk.StringVar(value=f"{int(float(ui_settings.get('scale', 1.0)) * 100)}%")
+    initial_scale = float(ui_settings.get("scale", 1.0))
+    root.geometry(f"{int(680 * initial_scale)}x{int(600 * initial_scale)}")
+    presetbar = ttk.Frame(root, padding=(12, 12, 12, 0))
+    presetbar.pack(fill=tk.X)
+    ttk.Label(presetbar, text=tr("Preset:")).pack(side=tk.LEFT)
+    preset_var = tk.StringVar()
+    preset_combo = ttk.Combobox(presetbar, textvariable=preset_var, state="readonly", width=24)
+    preset_combo.pack(side=tk.LEFT, padx=(4, 8))
+
+    colors = dict(zip(("ok", "error", "muted"), THEME_PALETTES["light"][4:]))
+
+    notebook = ttk.Notebook(root, padding=(12, 12, 12, 0))
+    notebook.pack(fill=tk.BOTH, expand=True)
+    mainframe = ttk.Frame(notebook, padding=12)
+    notebook.add(mainframe, text=tr("Text"))
 
     # Text input
-    ttk.Label(mainframe, text="Text to encode:").grid(row=0, column=0, sticky="w")
+    ttk.Label(mainframe, text=tr("Text to encode:")).grid(row=0, column=0, sticky="w")
     text_box = tk.Text(mainframe, height=5, wrap=tk.WORD)
     text_box.grid(row=1, column=0, columnspan=4, sticky="nsew", pady=(4, 8))
     text_box.insert("1.0", "hello world")
 
     # Protocol dropdown
-    ttk.Label(mainframe, text="Protocol:").grid(row=2, column=0, sticky="w")
+    ttk.Label(mainframe, text=tr("Protocol:")).grid(row=2, column=0, sticky="w")
     protocol_var = tk.StringVar(value="audible:fast")
     protocol_options = [
         "audible:normal", "audible:fast", "audible:fastest",
@@ -281,24 +649,24 @@ def run_ui_fixed() -> int:
     protocol_combo.grid(row=2, column=1, sticky="w")
 
     # Volume slider
-    ttk.Label(mainframe, text="Volume (0-100):").grid(row=2, column=2, sticky="e")
+    ttk.Label(mainframe, text=tr("Volume (0-100):")).grid(row=2, column=2, sticky="e")
     volume_var = tk.IntVar(value=75)
     volume_slider = ttk.Scale(mainframe, from_=0, to=100, orient=tk.HORIZONTAL, variable=volume_var)
     volume_slider.grid(row=2, column=3, sticky="we")
 
     # Output filename
-    ttk.Label(mainframe, text="Output file:").grid(row=3, column=0, sticky="w", pady=(8, 0))
+    ttk.Label(mainframe, text=tr("Output file:")).grid(row=3, column=0, sticky="w", pady=(8, 0))
     out_var = tk.StringVar(value="gibberlink.wav")
     out_entry = ttk.Entry(mainframe, textvariable=out_var)
     out_entry.grid(row=3, column=1, columnspan=3, sticky="we", pady=(8, 0))
 
     # Play checkbox
     play_var = tk.BooleanVar(value=True)
-    ttk.Checkbutton(mainframe, text="Play after generating", variable=play_var).grid(row=4, column=0, columnspan=2, sticky="w", pady=(8, 0))
+    ttk.Checkbutton(mainframe, text=tr("Play after generating"), variable=play_var).grid(row=4, column=0, columnspan=2, sticky="w", pady=(8, 0))
 
     # Status label
     status_var = tk.StringVar(value="")
-    status_label = ttk.Label(mainframe, textvariable=status_var, foreground="#0a0")
+    status_label = ttk.Label(mainframe, textvariable=status_var, foreground=colors["ok"])
     status_label.grid(row=5, column=0, columnspan=4, sticky="w", pady=(8, 0))
 
     # Grid weights
@@ -312,7 +680,7 @@ def run_ui_fixed() -> int:
     def run_encode() -> None:
         txt = text_box.get("1.0", tk.END).strip()
         if not txt:
-            status_var.set("Please enter some text.")
+            status_var.set(tr("Please enter some text."))
             return
         out_path = out_var.get().strip() or "gibberlink.wav"
         protocol = protocol_var.get()
@@ -321,31 +689,68 @@ def run_ui_fixed() -> int:
         if play_var.get():
             cmd.append("--play")
         cmd += ["--text", txt]
-        status_var.set("Generating...")
+        status_var.set(tr("Generating..."))
         root.update_idletasks()
         try:
             completed = subprocess.run(cmd, capture_output=True, text=True)
             if completed.returncode == 0:
                 msg = completed.stdout.strip() or "Done."
+                status_label.configure(foreground=colors["ok"])
                 status_var.set(msg)
             else:
                 err = completed.stderr.strip() or completed.stdout.strip() or f"Error code {completed.returncode}"
-                status_label.configure(foreground="#a00")
+                status_label.configure(foreground=colors["error"])
                 status_var.set(err)
         except Exception as e:
-            status_label.configure(foreground="#a00")
-            status_var.set(f"Failed: {e}")
+            status_label.configure(foreground=colors["error"])
+            status_var.set(tr("Failed: {}").format(e))
 
-    ttk.Button(mainframe, text="Generate + Play", command=run_encode).grid(row=4, column=3, sticky="e", pady=(8, 0))
+    encode_btn = ttk.Button(mainframe, text=tr("Generate + Play"), command=run_encode)
+    encode_btn.grid(row=4, column=3, sticky="e", pady=(8, 0))
+
+    # Live payload validation: byte count vs. protocol limit, estimated airtime
+    payload_var = tk.StringVar(value="")
+    payload_label = ttk.Label(mainframe, textvariable=payload_var, foreground=colors["muted"])
+    payload_label.grid(row=0, column=1, columnspan=3, sticky="e")
+
+    def validate_payload(*_args) -> None:
+        text_box.edit_modified(False)
+        nbytes = len(text_box.get("1.0", "end-1c").strip().encode("utf-8"))
+        protocol = protocol_var.get()
+        if nbytes == 0:
+            error = tr("Enter some text")
+        elif nbytes > MAX_CHUNKED_PAYLOAD:
+            error = tr("Too long by {} bytes").format(nbytes - MAX_CHUNKED_PAYLOAD)
+        else:
+            error = None
+        chunks = chunk_sizes(nbytes)
+        if len(chunks) > 1:
+            summary = tr("{} bytes in {} chunks").format(nbytes, len(chunks))
+        else:
+            summary = tr("{} / {} bytes").format(nbytes, MAX_VARIABLE_PAYLOAD)
+        if error:
+            payload_label.configure(foreground=colors["error"])
+            payload_var.set(f"{summary} \u2014 {error}")
+            encode_btn.state(["disabled"])
+        else:
+            payload_label.configure(foreground=colors["muted"])
+            payload_var.set(f"{summary} \u00b7 " + tr("~{:.1f} s on air").format(
+                sum(estimate_airtime(protocol, n) for n in chunks) + CHUNK_GAP_SECONDS * (len(chunks) - 1)))
+            encode_btn.state(["!disabled"])
+
+    text_box.bind("<<Modified>>", validate_payload)
+    protocol_var.trace_add("write", validate_payload)
+    validate_payload()
 
     # Tip
-    ttk.Label(mainframe, text="Tip: Very high volumes (>50) can distort or be uncomfortable.", foreground="#666").grid(row=6, column=0, columnspan=4, sticky="w", pady=(6, 0))
+    tip_label = ttk.Label(mainframe, text=tr("Tip: Very high volumes (>50) can distort or be uncomfortable."), foreground=colors["muted"])
+    tip_label.grid(row=6, column=0, columnspan=4, sticky="w", pady=(6, 0))
 
     # Separator
     ttk.Separator(mainframe, orient=tk.HORIZONTAL).grid(row=7, column=0, columnspan=4, sticky="ew", pady=(12, 8))
 
     # Decode section
-    ttk.Label(mainframe, text="Decode from WAV -> text:").grid(row=8, column=0, sticky="w")
+    ttk.Label(mainframe, text=tr("Decode from WAV -> text:")).grid(row=8, column=0, sticky="w")
 
     decode_path_var = tk.StringVar(value="")
     ttk.Entry(mainframe, textvariable=decode_path_var).grid(row=9, column=0, columnspan=3, sticky="we", pady=(4, 4))
@@ -355,14 +760,14 @@ def run_ui_fixed() -> int:
         if path:
             decode_path_var.set(path)
 
-    ttk.Button(mainframe, text="Browse...", command=browse_wav).grid(row=9, column=3, sticky="e")
+    ttk.Button(mainframe, text=tr("Browse..."), command=browse_wav).grid(row=9, column=3, sticky="e")
 
     def use_last_output():
         decode_path_var.set(out_var.get().strip())
 
-    ttk.Button(mainframe, text="Use Last Output", command=use_last_output).grid(row=10, column=0, sticky="w")
+    ttk.Button(mainframe, text=tr("Use Last Output"), command=use_last_output).grid(row=10, column=0, sticky="w")
 
-    ttk.Label(mainframe, text="Decoded text:").grid(row=11, column=0, sticky="w", pady=(8, 0))
+    ttk.Label(mainframe, text=tr("Decoded text:")).grid(row=11, column=0, sticky="w", pady=(8, 0))
     decoded_box = tk.Text(mainframe, height=6, wrap=tk.WORD)
     decoded_box.grid(row=12, column=0, columnspan=4, sticky="nsew", pady=(4, 8))
 
@@ -370,7 +775,7 @@ def run_ui_fixed() -> int:
         path = decode_path_var.get().strip()
         decoded_box.delete("1.0", tk.END)
         if not path:
-            decoded_box.insert(tk.END, "Please choose or provide a WAV path.")
+            decoded_box.insert(tk.END, tr("Please choose or provide a WAV path."))
             return
         completed = subprocess.run([exe, "--decode-wav", path], capture_output=True, text=True)
         if completed.returncode == 0:
@@ -379,7 +784,474 @@ def run_ui_fixed() -> int:
             msg = completed.stderr.strip() or completed.stdout.strip() or f"Error code {completed.returncode}"
             decoded_box.insert(tk.END, msg)
 
-    ttk.Button(mainframe, text="Decode", command=run_decode).grid(row=10, column=3, sticky="e")
+    decode_btn = ttk.Button(mainframe, text=tr("Decode"), command=run_decode)
+    decode_btn.grid(row=10, column=3, sticky="e")
+
+    # Live receive through `--listen --json --levels`: the input level, and the chunks so far of the
+    # message coming in (ggwave reports nothing before a transmission decodes, so these are the cues)
+    listenbar = ttk.Frame(mainframe)
+    listenbar.grid(row=13, column=0, columnspan=4, sticky="we")
+    ttk.Label(listenbar, text=tr("Input level:")).pack(side=tk.LEFT)
+    level_bar = ttk.Progressbar(listenbar, maximum=LEVEL_RANGE_DB, length=100)
+    level_bar.pack(side=tk.LEFT, padx=(4, 8))
+    receive_bar = ttk.Progressbar(listenbar, maximum=1, length=100)
+    receive_bar.pack(side=tk.LEFT)
+    receive_var = tk.StringVar(value="")
+    receive_label = ttk.Label(listenbar, textvariable=receive_var, foreground=colors["muted"])
+    receive_label.pack(side=tk.LEFT, padx=(8, 0))
+    # "proc", its "events" and its last line of stderr ("said") while listening; each run gets its own queue
+    listener = {}
+
+    def read_events(proc, events) -> None:
+        for line in proc.stdout:
+            events.put(line)
+        events.put(proc.wait())
+
+    def handle_event(event: dict) -> None:
+        kind = event.get("type")
+        if kind == "level":
+            for bar in level_bars:
+                bar["value"] = max(0.0, LEVEL_RANGE_DB + event["rms_dbfs"])
+        elif kind == "receive_begin":
+            for bar in receive_bars:
+                bar.configure(maximum=event["count"], value=0)
+        elif kind == "receive_progress":
+            for bar in receive_bars:
+                bar["value"] = event["received"]
+            left = (event["count"] - event["received"]) * (estimate_airtime(protocol_var.get(), MAX_VARIABLE_PAYLOAD) + CHUNK_GAP_SECONDS)
+            receive_var.set(tr("Message {}: chunk {}/{}").format(event["id"], event["received"], event["count"])
+                            + " \u00b7 " + tr("~{:.0f} s left").format(left))
+        elif kind == "receive_end":
+            for bar in receive_bars:
+                bar["value"] = bar["maximum"] if event["ok"] else 0
+        elif kind == "decoded":
+            receive_var.set("")
+            decoded_box.insert(tk.END, (event["text"] if event["text"] is not None else event["base64"]) + "\n")
+            decoded_box.see(tk.END)
+        elif kind == "warning":
+            receive_var.set(event["message"])
+
+    def poll_events() -> None:
+        while listener:
+            try:
+                line = listener["events"].get_nowait()
+            except queue.Empty:
+                break
+            if isinstance(line, int):
+                # the CLI's own last word ("Listen failed: ...") if it had one
+                said = listener.get("said") or tr("Listening failed: {}").format(line)
+                stop_listening()
+                receive_var.set(said)
+                break
+            try:
+                handle_event(json.loads(line))
+            except (ValueError, KeyError):
+                # "Saved report.pdf (...) to ..." and the like
+                listener["said"] = line.strip()
+                receive_var.set(listener["said"])
+        if listener:
+            root.after(50, poll_events)
+
+    def start_listening() -> None:
+        cmd = [exe, "--listen", "--json", "--levels"] + device_args("input_device", "--input-device")
+        if save_dir_var.get().strip():
+            cmd += ["--save-dir", save_dir_var.get().strip()]
+        if reliable_var.get():
+            cmd.append("--ack")
+        try:
+            # stderr too, so a failure to open the device can be shown; events are the lines that parse as JSON
+            proc = subprocess.Popen(cmd, stdout=subprocess.PIPE, stderr=subprocess.STDOUT, text=True)
+        except OSError as e:
+            receive_var.set(tr("Listening failed: {}").format(e))
+            return
+        listener.update(proc=proc, events=queue.Queue())
+        threading.Thread(target=read_events, args=(proc, listener["events"]), daemon=True).start()
+        for button in listen_buttons:
+            button.configure(text=tr("Stop"))
+        receive_var.set(tr("Listening..."))
+        poll_events()
+
+    def stop_listening() -> None:
+        proc = listener.pop("proc", None)
+        listener.clear()
+        if proc and proc.poll() is None:
+            proc.terminate()
+        for button, label in zip(listen_buttons, (tr("Listen"), tr("Test microphone"), tr("Receive"))):
+            button.configure(text=label)
+        for bar in level_bars:
+            bar["value"] = 0
+        receive_var.set("")
+
+    listen_btn = ttk.Button(listenbar, text=tr("Listen"), command=lambda: stop_listening() if listener else start_listening())
+    listen_btn.pack(side=tk.RIGHT)
+    listen_buttons = [listen_btn]
+    level_bars = [level_bar]
+    receive_bars = [receive_bar]
+
+    # Devices tab: the output and input devices the CLI can open (remembered in [ui]), with a test
+    # tone for the speaker and the listener's level meter for the microphone
+    devicesframe = ttk.Frame(notebook, padding=12)
+    notebook.add(devicesframe, text=tr("Devices"))
+    device_vars = {key: tk.StringVar(value=ui_settings.get(key, "")) for key in ("output_device", "input_device")}
+    device_combos = {}
+    for row, (key, label) in enumerate((("output_device", "Output device:"), ("input_device", "Input device:"))):
+        ttk.Label(devicesframe, text=tr(label)).grid(row=2 * row, column=0, sticky="w", pady=(8 * row, 0))
+        device_combos[key] = ttk.Combobox(devicesframe, textvariable=device_vars[key], state="readonly", width=40)
+        device_combos[key].grid(row=2 * row + 1, column=0, columnspan=2, sticky="we", pady=(4, 0))
+        device_combos[key].bind("<<ComboboxSelected>>", lambda _e: save_ui_settings())
+    devicesframe.columnconfigure(1, weight=1)
+    device_status_var = tk.StringVar(value="")
+    device_status = ttk.Label(devicesframe, textvariable=device_status_var, foreground=colors["muted"], wraplength=500)
+    device_status.grid(row=5, column=0, columnspan=3, sticky="w", pady=(12, 0))
+
+    def device_args(key: str, flag: str) -> list:
+        # "" is the system default; "Monitor of X" captures what output X plays
+        name = device_vars[key].get()
+        if name.startswith(MONITOR_PREFIX) and key == "input_device":
+            return ["--monitor-of", name[len(MONITOR_PREFIX):]]
+        return [flag, name] if name else []
+
+    def list_devices(flag: str) -> list:
+        # `--list-*-devices` prints "* name (rates)" for the default and "  name (rates)" otherwise, and
+        # "  Monitor of X (--monitor-of ...)" for the outputs that can be captured
+        completed = subprocess.run([exe, flag], capture_output=True, text=True)
+        if completed.returncode != 0:
+            device_status_var.set(tr("Could not list devices: {}").format((completed.stderr.strip().splitlines() or ["?"])[-1]))
+            return []
+        return [line[2:].rsplit(" (", 1)[0] for line in completed.stdout.splitlines() if line[2:].strip()]
+
+    def refresh_devices() -> None:
+        device_status_var.set("")
+        for key, flag in (("output_device", "--list-output-devices"), ("input_device", "--list-input-devices")):
+            device_combos[key]["values"] = [""] + list_devices(flag)
+
+    def play_test_tone() -> None:
+        volume = max(0, min(100, int(volume_var.get())))
+        completed = subprocess.run([exe, "--test-tone", "--volume", str(volume)] + device_args("output_device", "--device"), capture_output=True, text=True)
+        device_status_var.set("" if completed.returncode == 0 else (completed.stderr.strip().splitlines() or [str(completed.returncode)])[-1])
+
+    ttk.Button(devicesframe, text=tr("Play test tone"), command=play_test_tone).grid(row=1, column=2, sticky="e", padx=(8, 0), pady=(4, 0))
+    mic_btn = ttk.Button(devicesframe, text=tr("Test microphone"), command=lambda: stop_listening() if listener else start_listening())
+    mic_btn.grid(row=3, column=2, sticky="e", padx=(8, 0), pady=(4, 0))
+    ttk.Label(devicesframe, text=tr("Input level:")).grid(row=4, column=0, sticky="w", pady=(8, 0))
+    mic_bar = ttk.Progressbar(devicesframe, maximum=LEVEL_RANGE_DB)
+    mic_bar.grid(row=4, column=1, columnspan=2, sticky="we", pady=(8, 0))
+    ttk.Button(devicesframe, text=tr("Refresh"), command=refresh_devices).grid(row=6, column=2, sticky="e", pady=(12, 0))
+    listen_buttons.append(mic_btn)
+    level_bars.append(mic_bar)
+    refresh_devices()
+
+    # Files tab: `--file` to send, the listener with `--save-dir` to receive. Progress while sending is
+    # the estimated airtime (the CLI plays the whole transmission in one go); while receiving it is the
+    # chunks heard so far. "Reliable" sends with `--reliable` and receives with `--ack`, so lost
+    # chunks are asked for again and resent
+    filesframe = ttk.Frame(notebook, padding=12)
+    notebook.add(filesframe, text=tr("Files"))
+    filesframe.columnconfigure(1, weight=1)
+    send_path_var = tk.StringVar(value="")
+    save_dir_var = tk.StringVar(value=ui_settings.get("save_dir", ""))
+    reliable_var = tk.BooleanVar(value=False)
+    ttk.Label(filesframe, text=tr("File to send:")).grid(row=0, column=0, sticky="w")
+    send_entry = ttk.Entry(filesframe, textvariable=send_path_var)
+    send_entry.grid(row=0, column=1, sticky="we", padx=(4, 4))
+
+    def browse_file() -> None:
+        path = filedialog.askopenfilename(title=tr("File to send:"))
+        if path:
+            send_path_var.set(path)
+
+    ttk.Button(filesframe, text=tr("Browse..."), command=browse_file).grid(row=0, column=2, sticky="e")
+    send_info_var = tk.StringVar(value="")
+    send_info = ttk.Label(filesframe, textvariable=send_info_var, foreground=colors["muted"])
+    send_info.grid(row=1, column=1, columnspan=2, sticky="w", pady=(4, 0))
+    send_bar = ttk.Progressbar(filesframe, maximum=1)
+    send_bar.grid(row=2, column=0, columnspan=2, sticky="we", pady=(8, 0))
+    ttk.Checkbutton(filesframe, text=tr("Reliable (resend lost chunks)"), variable=reliable_var).grid(row=3, column=0, columnspan=2, sticky="w", pady=(8, 0))
+    ttk.Separator(filesframe, orient=tk.HORIZONTAL).grid(row=4, column=0, columnspan=3, sticky="ew", pady=(12, 8))
+    ttk.Label(filesframe, text=tr("Save received files to:")).grid(row=5, column=0, sticky="w")
+    ttk.Entry(filesframe, textvariable=save_dir_var).grid(row=5, column=1, sticky="we", padx=(4, 4))
+
+    def browse_save_dir() -> None:
+        path = filedialog.askdirectory(title=tr("Save received files to:"))
+        if path:
+            save_dir_var.set(path)
+            save_ui_settings()
+
+    ttk.Button(filesframe, text=tr("Browse..."), command=browse_save_dir).grid(row=5, column=2, sticky="e")
+    file_receive_bar = ttk.Progressbar(filesframe, maximum=1)
+    file_receive_bar.grid(row=6, column=0, columnspan=2, sticky="we", pady=(8, 0))
+    file_receive_btn = ttk.Button(filesframe, text=tr("Receive"), command=lambda: stop_listening() if listener else start_listening())
+    file_receive_btn.grid(row=6, column=2, sticky="e", pady=(8, 0))
+    ttk.Label(filesframe, textvariable=receive_var, foreground=colors["muted"]).grid(row=7, column=0, columnspan=3, sticky="w", pady=(4, 0))
+    listen_buttons.append(file_receive_btn)
+    receive_bars.append(file_receive_bar)
+    # "proc", "started", "airtime" and, once it exits, "result" of the transmission in progress
+    sender = {}
+
+    def file_airtime(path: str) -> Optional[float]:
+        try:
+            # the envelope adds the name, a 4-byte size and a 32-byte SHA-256 (gibberlink-core/src/file.rs)
+            nbytes = os.path.getsize(path) + 3 + len(os.path.basename(path).encode("utf-8")) + 4 + 32
+        except OSError:
+            return None
+        if nbytes > MAX_CHUNKED_PAYLOAD:
+            return None
+        chunks = chunk_sizes(nbytes)
+        return sum(estimate_airtime(protocol_var.get(), n) for n in chunks) + CHUNK_GAP_SECONDS * (len(chunks) - 1)
+
+    def describe_file(*_args) -> None:
+        path = send_path_var.get().strip()
+        if not path:
+            send_info_var.set("")
+            return
+        airtime = file_airtime(path)
+        if airtime is None:
+            send_info_var.set(tr("Not a readable file of at most {} bytes").format(MAX_CHUNKED_PAYLOAD))
+        else:
+            send_info_var.set(tr("{} bytes").format(os.path.getsize(path)) + " \u00b7 " + tr("~{:.1f} s on air").format(airtime))
+
+    def wait_for_send(proc) -> None:
+        _out, err = proc.communicate()
+        sender["result"] = (proc.returncode, err.strip())
+
+    def poll_send() -> None:
+        if "result" in sender:
+            code, err = sender.pop("result")
+            sender.clear()
+            send_btn.configure(text=tr("Send"))
+            send_bar["value"] = 1 if code == 0 and not err else 0
+            # playback failing is only a warning on stderr, so show whatever the CLI said last
+            send_info_var.set((err.splitlines() or [tr("Sent") if code == 0 else str(code)])[-1])
+            return
+        elapsed = time.monotonic() - sender["started"]
+        send_bar["value"] = min(0.99, elapsed / sender["airtime"])
+        send_info_var.set(tr("~{:.0f} s left").format(max(0.0, sender["airtime"] - elapsed)))
+        root.after(200, poll_send)
+
+    def send_file() -> None:
+        if sender:
+            sender["proc"].terminate()
+            return
+        path = send_path_var.get().strip()
+        airtime = file_airtime(path)
+        if airtime is None:
+            describe_file()
+            return
+        volume = max(0, min(100, int(volume_var.get())))
+        cmd = [exe, "--file", path, "--protocol", protocol_var.get(), "--volume", str(volume)] + device_args("output_device", "--device")
+        # --reliable plays (and listens for acknowledgements) itself
+        cmd += ["--reliable"] + device_args("input_device", "--input-device") if reliable_var.get() else ["--play", "--no-wav"]
+        try:
+            proc = subprocess.Popen(cmd, stdout=subprocess.PIPE, stderr=subprocess.PIPE, text=True)
+        except OSError as e:
+            send_info_var.set(tr("Failed: {}").format(e))
+            return
+        sender.update(proc=proc, started=time.monotonic(), airtime=max(airtime, 0.1))
+        threading.Thread(target=wait_for_send, args=(proc,), daemon=True).start()
+        send_btn.configure(text=tr("Cancel"))
+        send_bar["value"] = 0
+        poll_send()
+
+    send_btn = ttk.Button(filesframe, text=tr("Send"), command=send_file)
+    send_btn.grid(row=2, column=2, sticky="e", pady=(8, 0))
+    send_path_var.trace_add("write", describe_file)
+    protocol_var.trace_add("write", describe_file)
+    # Drag-and-drop needs the tkdnd extension, which stock Tk lacks; it is used when tkinterdnd2 is installed
+    if dnd_files:
+        send_entry.drop_target_register(dnd_files)
+        send_entry.dnd_bind("<<Drop>>", lambda e: send_path_var.set(root.tk.splitlist(e.data)[0]))
+    root.protocol("WM_DELETE_WINDOW", lambda: (stop_listening(), root.destroy()))
+
+    def refresh_presets(selected: str = "") -> None:
+        preset_combo["values"] = sorted(presets)
+        preset_var.set(selected)
+
+    def apply_preset(_event=None) -> None:
+        preset = presets.get(preset_var.get(), {})
+        if preset.get("protocol") in protocol_options:
+            protocol_var.set(preset["protocol"])
+        if "volume" in preset:
+            volume_var.set(max(0, min(100, int(preset["volume"]))))
+        status_var.set(tr("Preset:") + f" {preset_var.get()}")
+
+    def store_preset(name: str) -> None:
+        presets[name] = {"protocol": protocol_var.get(), "volume": max(0, min(100, int(volume_var.get())))}
+        try:
+            save_config(config)
+        except OSError as e:
+            messagebox.showerror(tr("Presets"), f"Could not save {config_path()}: {e}")
+            return
+        refresh_presets(name)
+        status_var.set(tr("Saved preset {!r}").format(name))
+
+    def ask_name(title: str, initial: str = "") -> Optional[str]:
+        name = simpledialog.askstring(title, tr("Preset name:"), initialvalue=initial, parent=root)
+        name = (name or "").strip()
+        if name and name in presets and not messagebox.askyesno(title, tr("Overwrite preset {!r}?").format(name)):
+            return None
+        return name or None
+
+    def save_preset() -> None:
+        name = preset_var.get() or ask_name(tr("Save preset"))
+        if name:
+            store_preset(name)
+
+    def new_preset() -> None:
+        name = ask_name(tr("New preset"))
+        if name:
+            store_preset(name)
+
+    def duplicate_preset() -> None:
+        current = preset_var.get()
+        if not current:
+            return
+        name = ask_name(tr("Duplicate preset"), f"{current} copy")
+        if name:
+            presets[name] = dict(presets[current])
+            save_config(config)
+            refresh_presets(name)
+            apply_preset()
+
+    def delete_preset() -> None:
+        current = preset_var.get()
+        if not current or not messagebox.askyesno(tr("Delete preset"), tr("Delete preset {!r}?").format(current)):
+            return
+        presets.pop(current, None)
+        save_config(config)
+        refresh_presets()
+        status_var.set(tr("Deleted preset {!r}").format(current))
+
+    preset_combo.bind("<<ComboboxSelected>>", apply_preset)
+    ttk.Button(presetbar, text=tr("Save"), command=save_preset).pack(side=tk.LEFT)
+    ttk.Button(presetbar, text=tr("New..."), command=new_preset).pack(side=tk.LEFT, padx=(4, 0))
+    ttk.Button(presetbar, text=tr("Duplicate..."), command=duplicate_preset).pack(side=tk.LEFT, padx=(4, 0))
+    ttk.Button(presetbar, text=tr("Delete"), command=delete_preset).pack(side=tk.LEFT, padx=(4, 0))
+    refresh_presets()
+
+    # Theme and scaling
+    style = ttk.Style(root)
+    base_theme = style.theme_use()
+    base_tk_scaling = float(root.tk.call("tk", "scaling"))
+    base_font_sizes = {name: tkfont.nametofont(name).cget("size") for name in tkfont.names(root) if name.startswith("Tk")}
+
+    def apply_theme(*_args) -> None:
+        theme = theme_var.get()
+        if theme == "system":
+            theme = "dark" if system_prefers_dark() else "light"
+        bg, fg, field, select, *status_colors = THEME_PALETTES[theme]
+        # native look for light; clam is the only built-in theme that honours custom colours
+        style.theme_use(base_theme if theme == "light" else "clam")
+        if theme != "light" or base_theme == "clam":
+            style.configure(".", background=bg, foreground=fg, fieldbackground=field, selectbackground=select)
+            style.map("TCombobox", fieldbackground=[("readonly", field)], foreground=[("readonly", fg)])
+        if theme == "high-contrast":
+            style.configure(".", focuscolor=select, bordercolor=fg, lightcolor=fg, darkcolor=fg)
+            style.configure("TButton", borderwidth=2)
+        previous = dict(colors)
+        colors.update(zip(("ok", "error", "muted"), status_colors))
+        for label in (status_label, payload_label, tip_label, receive_label, device_status, send_info):
+            current = str(label.cget("foreground"))
+            key = next((k for k, v in previous.items() if v == current), "muted")
+            label.configure(foreground=colors[key])
+        root.configure(background=bg)
+        root.option_add("*TCombobox*Listbox.background", field)
+        root.option_add("*TCombobox*Listbox.foreground", fg)
+        for box in (text_box, decoded_box):
+            box.configure(background=field, foreground=fg, insertbackground=fg, selectbackground=select)
+
+    def apply_scale(*_args) -> Optional[float]:
+        try:
+            scale = int(scale_var.get().rstrip("%")) / 100
+        except ValueError:
+            return None
+        scale = max(0.5, min(4.0, scale))
+        root.tk.call("tk", "scaling", base_tk_scaling * scale)
+        for name, size in base_font_sizes.items():
+            tkfont.nametofont(name).configure(size=round(size * scale))
+        if message_family:
+            message_font.configure(size=round(base_font_sizes["TkTextFont"] * scale))
+        return scale
+
+    message_family = message_font_family(tkfont.families(root), language, ui_settings.get("message_font"))
+    if message_family:
+        message_font = tkfont.Font(root, family=message_family, size=tkfont.nametofont("TkTextFont").cget("size"))
+        for box in (text_box, decoded_box):
+            box.configure(font=message_font)
+
+    def save_ui_settings(*_args) -> None:
+        ui_settings["theme"] = theme_var.get()
+        for key, var in device_vars.items():
+            ui_settings[key] = var.get()
+        ui_settings["save_dir"] = save_dir_var.get().strip()
+        scale = apply_scale()
+        if scale:
+            ui_settings["scale"] = scale
+        try:
+            save_config(config)
+        except OSError as e:
+            status_var.set(f"Could not save settings: {e}")
+
+    theme_combo = ttk.Combobox(presetbar, textvariable=theme_var, values=["system", "light", "dark", "high-contrast"], state="readonly", width=12)
+    scale_combo = ttk.Combobox(presetbar, textvariable=scale_var, values=UI_SCALES, width=5)
+    scale_combo.pack(side=tk.RIGHT)
+    ttk.Label(presetbar, text=tr("Scale:")).pack(side=tk.RIGHT, padx=(8, 4))
+    theme_combo.pack(side=tk.RIGHT)
+    ttk.Label(presetbar, text=tr("Theme:")).pack(side=tk.RIGHT, padx=(8, 4))
+    language_var = tk.StringVar(value=language if language in UI_LANGUAGES else "en")
+    language_combo = ttk.Combobox(presetbar, textvariable=language_var, values=UI_LANGUAGES, state="readonly", width=4)
+    language_combo.pack(side=tk.RIGHT)
+    ttk.Label(presetbar, text=tr("Language:")).pack(side=tk.RIGHT, padx=(8, 4))
+
+    def save_language(_event=None) -> None:
+        ui_settings["language"] = language_var.get()
+        save_ui_settings()
+        status_var.set(tr("Language changes apply after restarting the UI"))
+
+    language_combo.bind("<<ComboboxSelected>>", save_language)
+    theme_combo.bind("<<ComboboxSelected>>", lambda _e: (apply_theme(), save_ui_settings()))
+    scale_combo.bind("<<ComboboxSelected>>", save_ui_settings)
+    scale_combo.bind("<Return>", save_ui_settings)
+    apply_theme()
+    apply_scale()
+
+    # Keyboard navigation: Tab leaves the text boxes instead of inserting a tab, and
+    # the slider gets arrow-key steps (ttk.Scale has no key bindings of its own)
+    def focus_next(event):
+        event.widget.tk_focusNext().focus_set()
+        return "break"
+
+    def focus_prev(event):
+        event.widget.tk_focusPrev().focus_set()
+        return "break"
+
+    for box in (text_box, decoded_box):
+        box.bind("<Tab>", focus_next)
+        box.bind("<Shift-Tab>", focus_prev)
+        box.bind("<ISO_Left_Tab>", focus_prev)
+
+    def step_volume(delta: int):
+        volume_var.set(max(0, min(100, int(volume_var.get()) + delta)))
+        return "break"
+
+    volume_slider.configure(takefocus=True)
+    volume_slider.bind("<Left>", lambda _e: step_volume(-1))
+    volume_slider.bind("<Right>", lambda _e: step_volume(1))
+    volume_slider.bind("<Prior>", lambda _e: step_volume(10))
+    volume_slider.bind("<Next>", lambda _e: step_volume(-10))
+
+    def invoke_if_enabled(button):
+        if button.instate(["!disabled"]):
+            button.invoke()
+        return "break"
+
+    root.bind("<Control-Return>", lambda _e: invoke_if_enabled(encode_btn))
+    root.bind("<Control-d>", lambda _e: invoke_if_enabled(decode_btn))
+    root.bind("<Control-o>", lambda _e: browse_wav())
+    root.bind("<Control-l>", lambda _e: use_last_output())
+    root.bind("<Control-s>", lambda _e: save_preset())
+    for button in (encode_btn, decode_btn):
+        button.bind("<Return>", lambda e: invoke_if_enabled(e.widget))
+    text_box.focus_set()
 
     root.mainloop()
     return 0